@@ -0,0 +1,6 @@
+-- Randomization bias for profile sections
+
+alter table profile_section
+    add column randomization_bias text default 'Uniform' not null
+        constraint randomization_bias
+            check (randomization_bias in ('Uniform', 'FavorRecent', 'FavorRating'));
@@ -0,0 +1,6 @@
+-- Grouping granularity for randomizing oldest tracks
+
+alter table profile_section
+    add column oldest_bucket text default 'YearMonth' not null
+        constraint oldest_bucket
+            check (oldest_bucket in ('Year', 'YearMonth', 'YearWeek'));
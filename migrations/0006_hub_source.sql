@@ -0,0 +1,130 @@
+-- no-transaction
+-- Adds plex hubs as a profile source
+--
+-- SQLite cannot alter an existing check constraint, so the profile table is rebuilt.
+-- Foreign keys are disabled while rebuilding so that profile sections are not deleted.
+
+pragma foreign_keys = off;
+
+begin transaction;
+
+drop view if exists v_profile;
+
+CREATE TABLE profile_new
+(
+    profile_id         integer                       not null
+        constraint profile_pk
+            primary key autoincrement,
+    playlist_id        TEXT                          not null,
+    profile_title      TEXT    default 'New Profile' not null,
+    profile_summary    TEXT,
+    enabled            boolean default 1             not null,
+    profile_source     TEXT                          not null,
+    profile_source_id  TEXT,
+    refresh_interval   integer default 5             not null,
+    time_limit         integer default 0,
+    track_limit        integer default 0,
+    active_hours_start integer
+        constraint active_hours_start
+            check (active_hours_start is null or (active_hours_start >= 0 and active_hours_start <= 23)),
+    active_hours_end   integer
+        constraint active_hours_end
+            check (active_hours_end is null or (active_hours_end >= 0 and active_hours_end <= 23)),
+    hub_identifier     TEXT,
+    constraint enabled_boolean
+        check (enabled in (0, 1)),
+    constraint profile_source
+        check (profile_source in ('Library', 'Collection', 'Playlist', 'SingleArtist', 'Hub')),
+    constraint refresh_interval
+        check (refresh_interval in (2, 3, 4, 5, 6, 10, 12, 15, 20, 30)),
+    constraint time_limit
+        check (time_limit >= 0),
+    constraint track_limit
+        check (track_limit >= 0)
+);
+
+insert into profile_new (profile_id,
+                         playlist_id,
+                         profile_title,
+                         profile_summary,
+                         enabled,
+                         profile_source,
+                         profile_source_id,
+                         refresh_interval,
+                         time_limit,
+                         track_limit,
+                         active_hours_start,
+                         active_hours_end)
+select profile_id,
+       playlist_id,
+       profile_title,
+       profile_summary,
+       enabled,
+       profile_source,
+       profile_source_id,
+       refresh_interval,
+       time_limit,
+       track_limit,
+       active_hours_start,
+       active_hours_end
+from profile;
+
+drop table profile;
+
+alter table profile_new
+    rename to profile;
+
+CREATE UNIQUE INDEX profile_profile_title_uindex
+    on profile (profile_title);
+
+-- Profiles view
+
+drop view if exists v_profile;
+create view v_profile as
+select profile_id,
+       playlist_id,
+       profile_title,
+       profile_summary,
+       enabled,
+       profile_source,
+       profile_source_id,
+       refresh_interval,
+       time_limit,
+       track_limit,
+       active_hours_start,
+       active_hours_end,
+       hub_identifier,
+       num_sections,
+       num_sections >= 3                                                                  has_max_sections,
+       (cast(time_limit as real) / cast(num_sections as real))                            section_time_limit,
+       cast((60.0 / refresh_interval) as integer)                                         refreshes_per_hour,
+       cast(strftime('%s', current_timestamp) -
+            (strftime('%s', current_timestamp) % (refresh_interval * 60.0)) as integer)   current_refresh,
+       cast(strftime('%s', current_timestamp) +
+            ((refresh_interval * 60.0) -
+             (strftime('%s', current_timestamp)) % (refresh_interval * 60.0)) as integer) next_refresh_at,
+       (cast(strftime('%M', current_timestamp) as real) % refresh_interval == 0)          eligible_for_refresh
+from (select profile_id,
+             playlist_id,
+             profile_title,
+             profile_summary,
+             enabled,
+             profile_source,
+             profile_source_id,
+             refresh_interval,
+             case when time_limit == 0 then 365 * 24 else time_limit end time_limit,
+             track_limit,
+             active_hours_start,
+             active_hours_end,
+             hub_identifier,
+             (select count(1)
+              from profile_section ps
+              where profile_id = p.profile_id
+                and p.enabled = 1
+                and ps.enabled = 1)                                      num_sections
+      from profile p
+      order by profile_title);
+
+commit;
+
+pragma foreign_keys = on;
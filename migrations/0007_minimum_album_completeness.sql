@@ -0,0 +1,6 @@
+-- Minimum album completeness for profile sections
+
+alter table profile_section
+    add column minimum_album_completeness integer default 0 not null
+        constraint minimum_album_completeness
+            check (minimum_album_completeness >= 0 and minimum_album_completeness <= 100);
@@ -0,0 +1,56 @@
+-- Custom merge order of profile sections, stored as a JSON array of section types
+
+alter table profile
+    add column merge_order text;
+
+-- Profiles view
+
+drop view if exists v_profile;
+create view v_profile as
+select profile_id,
+       playlist_id,
+       profile_title,
+       profile_summary,
+       enabled,
+       profile_source,
+       profile_source_id,
+       refresh_interval,
+       time_limit,
+       track_limit,
+       active_hours_start,
+       active_hours_end,
+       hub_identifier,
+       empty_section_behavior,
+       merge_order,
+       num_sections,
+       num_sections >= 3                                                                  has_max_sections,
+       (cast(time_limit as real) / cast(num_sections as real))                            section_time_limit,
+       cast((60.0 / refresh_interval) as integer)                                         refreshes_per_hour,
+       cast(strftime('%s', current_timestamp) -
+            (strftime('%s', current_timestamp) % (refresh_interval * 60.0)) as integer)   current_refresh,
+       cast(strftime('%s', current_timestamp) +
+            ((refresh_interval * 60.0) -
+             (strftime('%s', current_timestamp)) % (refresh_interval * 60.0)) as integer) next_refresh_at,
+       (cast(strftime('%M', current_timestamp) as real) % refresh_interval == 0)          eligible_for_refresh
+from (select profile_id,
+             playlist_id,
+             profile_title,
+             profile_summary,
+             enabled,
+             profile_source,
+             profile_source_id,
+             refresh_interval,
+             case when time_limit == 0 then 365 * 24 else time_limit end time_limit,
+             track_limit,
+             active_hours_start,
+             active_hours_end,
+             hub_identifier,
+             empty_section_behavior,
+             merge_order,
+             (select count(1)
+              from profile_section ps
+              where profile_id = p.profile_id
+                and p.enabled = 1
+                and ps.enabled = 1)                                      num_sections
+      from profile p
+      order by profile_title);
@@ -0,0 +1,78 @@
+-- Number of previous refreshes whose tracks are excluded from the next refresh
+
+alter table profile
+    add column avoid_recent_repeats_cycles integer default 0 not null
+        constraint avoid_recent_repeats_cycles
+            check (avoid_recent_repeats_cycles >= 0);
+
+-- Track guids used by each refresh of a profile
+
+drop table if exists profile_refresh_history;
+CREATE TABLE profile_refresh_history
+(
+    profile_refresh_history_id integer                           not null
+        constraint profile_refresh_history_pk
+            primary key autoincrement,
+    profile_id                 integer                           not null
+        constraint profile_refresh_history_profile_profile_id_fk
+            references profile on delete cascade,
+    refreshed_at               text    default current_timestamp not null,
+    track_guids                text                              not null
+);
+
+CREATE INDEX profile_refresh_history_profile_id_index
+    on profile_refresh_history (profile_id);
+
+-- Profiles view
+
+drop view if exists v_profile;
+create view v_profile as
+select profile_id,
+       playlist_id,
+       profile_title,
+       profile_summary,
+       enabled,
+       profile_source,
+       profile_source_id,
+       refresh_interval,
+       time_limit,
+       track_limit,
+       active_hours_start,
+       active_hours_end,
+       hub_identifier,
+       empty_section_behavior,
+       merge_order,
+       avoid_recent_repeats_cycles,
+       num_sections,
+       num_sections >= 3                                                                  has_max_sections,
+       (cast(time_limit as real) / cast(num_sections as real))                            section_time_limit,
+       cast((60.0 / refresh_interval) as integer)                                         refreshes_per_hour,
+       cast(strftime('%s', current_timestamp) -
+            (strftime('%s', current_timestamp) % (refresh_interval * 60.0)) as integer)   current_refresh,
+       cast(strftime('%s', current_timestamp) +
+            ((refresh_interval * 60.0) -
+             (strftime('%s', current_timestamp)) % (refresh_interval * 60.0)) as integer) next_refresh_at,
+       (cast(strftime('%M', current_timestamp) as real) % refresh_interval == 0)          eligible_for_refresh
+from (select profile_id,
+             playlist_id,
+             profile_title,
+             profile_summary,
+             enabled,
+             profile_source,
+             profile_source_id,
+             refresh_interval,
+             case when time_limit == 0 then 365 * 24 else time_limit end time_limit,
+             track_limit,
+             active_hours_start,
+             active_hours_end,
+             hub_identifier,
+             empty_section_behavior,
+             merge_order,
+             avoid_recent_repeats_cycles,
+             (select count(1)
+              from profile_section ps
+              where profile_id = p.profile_id
+                and p.enabled = 1
+                and ps.enabled = 1)                                      num_sections
+      from profile p
+      order by profile_title);
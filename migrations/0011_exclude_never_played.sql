@@ -0,0 +1,6 @@
+-- Excludes tracks without a last played timestamp from oldest sections
+
+alter table profile_section
+    add column exclude_never_played boolean default 0 not null
+        constraint exclude_never_played
+            check (exclude_never_played in (0, 1));
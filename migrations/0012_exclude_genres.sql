@@ -0,0 +1,4 @@
+-- Genres excluded from profile sections, stored as a JSON array of genre names
+
+alter table profile_section
+    add column exclude_genres text default '[]' not null;
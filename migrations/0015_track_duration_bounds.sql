@@ -0,0 +1,7 @@
+-- Tracks shorter or longer than these bounds are removed from profile sections. `0` means no bound.
+
+alter table profile_section
+    add column min_duration_seconds integer default 0 not null;
+
+alter table profile_section
+    add column max_duration_seconds integer default 0 not null;
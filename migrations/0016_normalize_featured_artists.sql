@@ -0,0 +1,6 @@
+-- Counts tracks with featuring credits against the primary artist's per-artist limit
+
+alter table profile_section
+    add column normalize_featured_artists boolean default 0 not null
+        constraint normalize_featured_artists
+            check (normalize_featured_artists in (0, 1));
@@ -0,0 +1,84 @@
+-- Number of previous versions of a playlist kept as snapshots before each refresh
+
+alter table profile
+    add column snapshot_count integer default 0 not null
+        constraint snapshot_count
+            check (snapshot_count >= 0);
+
+-- Track ids of a playlist taken before it was refreshed
+
+drop table if exists playlist_snapshot;
+CREATE TABLE playlist_snapshot
+(
+    playlist_snapshot_id integer                           not null
+        constraint playlist_snapshot_pk
+            primary key autoincrement,
+    profile_id           integer                           not null
+        constraint playlist_snapshot_profile_profile_id_fk
+            references profile on delete cascade,
+    created_at           text    default current_timestamp not null,
+    track_ids            text                              not null
+);
+
+CREATE INDEX playlist_snapshot_profile_id_index
+    on playlist_snapshot (profile_id);
+
+-- Profiles view
+
+drop view if exists v_profile;
+create view v_profile as
+select profile_id,
+       playlist_id,
+       profile_title,
+       profile_summary,
+       enabled,
+       profile_source,
+       profile_source_id,
+       refresh_interval,
+       time_limit,
+       track_limit,
+       active_hours_start,
+       active_hours_end,
+       hub_identifier,
+       empty_section_behavior,
+       merge_order,
+       avoid_recent_repeats_cycles,
+       merge_strategy,
+       summary_only,
+       snapshot_count,
+       num_sections,
+       num_sections >= 3                                                                  has_max_sections,
+       (cast(time_limit as real) / cast(num_sections as real))                            section_time_limit,
+       cast((60.0 / refresh_interval) as integer)                                         refreshes_per_hour,
+       cast(strftime('%s', current_timestamp) -
+            (strftime('%s', current_timestamp) % (refresh_interval * 60.0)) as integer)   current_refresh,
+       cast(strftime('%s', current_timestamp) +
+            ((refresh_interval * 60.0) -
+             (strftime('%s', current_timestamp)) % (refresh_interval * 60.0)) as integer) next_refresh_at,
+       (cast(strftime('%M', current_timestamp) as real) % refresh_interval == 0)          eligible_for_refresh
+from (select profile_id,
+             playlist_id,
+             profile_title,
+             profile_summary,
+             enabled,
+             profile_source,
+             profile_source_id,
+             refresh_interval,
+             case when time_limit == 0 then 365 * 24 else time_limit end time_limit,
+             track_limit,
+             active_hours_start,
+             active_hours_end,
+             hub_identifier,
+             empty_section_behavior,
+             merge_order,
+             avoid_recent_repeats_cycles,
+             merge_strategy,
+             summary_only,
+             snapshot_count,
+             (select count(1)
+              from profile_section ps
+              where profile_id = p.profile_id
+                and p.enabled = 1
+                and ps.enabled = 1)                                      num_sections
+      from profile p
+      order by profile_title);
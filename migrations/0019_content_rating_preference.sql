@@ -0,0 +1,6 @@
+-- Determines which version of a track is kept when explicit and clean versions are deduplicated
+
+alter table profile_section
+    add column content_rating_preference text default 'NoPreference' not null
+        constraint content_rating_preference
+            check (content_rating_preference in ('NoPreference', 'PreferExplicit', 'PreferClean'));
@@ -0,0 +1,8 @@
+-- The time at which a profile was disabled, used when pruning disabled profiles
+
+alter table profile
+    add column disabled_at text;
+
+update profile
+set disabled_at = current_timestamp
+where enabled = 0;
@@ -0,0 +1,6 @@
+-- If false, tracks played within the last day are kept in the section
+
+alter table profile_section
+    add column exclude_recently_played boolean default 1 not null
+        constraint exclude_recently_played
+            check (exclude_recently_played in (0, 1));
@@ -0,0 +1,6 @@
+-- If true, a least played section also includes tracks that have never been played
+
+alter table profile_section
+    add column least_played_includes_unplayed boolean default 0 not null
+        constraint least_played_includes_unplayed
+            check (least_played_includes_unplayed in (0, 1));
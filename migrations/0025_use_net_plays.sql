@@ -0,0 +1,6 @@
+-- If true, a least played section orders tracks by their plays minus their skips
+
+alter table profile_section
+    add column use_net_plays boolean default 0 not null
+        constraint use_net_plays
+            check (use_net_plays in (0, 1));
@@ -0,0 +1,6 @@
+-- The number of tracks at the front of a randomized section that keep their sorted order
+
+alter table profile_section
+    add column keep_top_n_fixed integer default 0 not null
+        constraint keep_top_n_fixed
+            check (keep_top_n_fixed >= 0);
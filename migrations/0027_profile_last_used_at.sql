@@ -0,0 +1,4 @@
+-- When a profile was last previewed, refreshed, or edited, used to list recently used profiles first
+
+alter table profile
+    add column last_used_at text;
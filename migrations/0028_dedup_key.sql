@@ -0,0 +1,6 @@
+-- Determines which fields of a track are compared when deduplicating tracks by title and artist
+
+alter table profile_section
+    add column dedup_key text default 'TitleArtist' not null
+        constraint dedup_key
+            check (dedup_key in ('TitleArtist', 'TitleOnly', 'GuidOnly'));
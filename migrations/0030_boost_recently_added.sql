@@ -0,0 +1,6 @@
+-- If true, an unplayed section orders recently added tracks before older tracks of the same rating
+
+alter table profile_section
+    add column boost_recently_added boolean default 0 not null
+        constraint boost_recently_added
+            check (boost_recently_added in (0, 1));
@@ -0,0 +1,5 @@
+-- A hash of the track ids written by the last refresh, used to skip refreshes that would not
+-- change the playlist
+
+alter table profile
+    add column track_ids_hash text;
@@ -0,0 +1,6 @@
+-- Determines how tracks without a duration are counted against a section's time limit
+
+alter table profile_section
+    add column missing_duration_policy text default 'CountAsZero' not null
+        constraint missing_duration_policy
+            check (missing_duration_policy in ('CountAsZero', 'Drop', 'AssumeDefault'));
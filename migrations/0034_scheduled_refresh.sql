@@ -0,0 +1,16 @@
+-- One-time refreshes of a profile, removed once they fire
+
+drop table if exists scheduled_refresh;
+CREATE TABLE scheduled_refresh
+(
+    scheduled_refresh_id integer not null
+        constraint scheduled_refresh_pk
+            primary key autoincrement,
+    profile_id           integer not null
+        constraint scheduled_refresh_profile_profile_id_fk
+            references profile on delete cascade,
+    refresh_at           integer not null
+);
+
+CREATE INDEX scheduled_refresh_refresh_at_index
+    on scheduled_refresh (refresh_at);
@@ -0,0 +1,7 @@
+-- If true, plex returns a section's tracks in a random order, and they are not sorted or shuffled
+-- locally
+
+alter table profile_section
+    add column server_random boolean default 0 not null
+        constraint server_random
+            check (server_random in (0, 1));
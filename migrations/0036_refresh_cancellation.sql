@@ -0,0 +1,12 @@
+-- Requests to cancel the refresh of a profile, taken by the refresh loop
+
+drop table if exists refresh_cancellation;
+CREATE TABLE refresh_cancellation
+(
+    profile_id   integer                           not null
+        constraint refresh_cancellation_pk
+            primary key
+        constraint refresh_cancellation_profile_profile_id_fk
+            references profile on delete cascade,
+    requested_at text    default current_timestamp not null
+);
@@ -0,0 +1,5 @@
+-- Raw plex filters added to those hitomi builds for a section, stored as a JSON object of filter
+-- keys to values
+
+alter table profile_section
+    add column extra_filters text default '{}' not null;
@@ -0,0 +1,5 @@
+-- The number of plays a track needs to be included in a least played or oldest section. Values
+-- of 0 and 1 both require a single play.
+
+alter table profile_section
+    add column minimum_plays integer default 0 not null;
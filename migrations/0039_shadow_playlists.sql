@@ -0,0 +1,14 @@
+-- Playlists created in plex for shadow profiles, so that cleanup only deletes playlists created
+-- by hitomi
+
+drop table if exists shadow_playlist;
+CREATE TABLE shadow_playlist
+(
+    playlist_id text                              not null
+        constraint shadow_playlist_pk
+            primary key,
+    profile_id  integer                           not null
+        constraint shadow_playlist_profile_profile_id_fk
+            references profile on delete cascade,
+    created_at  text    default current_timestamp not null
+);
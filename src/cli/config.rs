@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
+use simplelog::info;
 
-use crate::config::ConfigBuilder as AppConfigBuilder;
-use crate::db;
+use crate::config::{ConfigBuilder as AppConfigBuilder, SummaryMode};
+use crate::plex::tv::{PlexTvClient, PIN_POLL_INTERVAL, PLEX_TV_URL};
+use crate::{config, db};
 
 #[derive(Args, PartialEq)]
 pub struct CliConfig {
@@ -12,7 +14,12 @@ pub struct CliConfig {
 
 #[derive(Subcommand, PartialEq)]
 enum ConfigCmds {
-    Create(CreateArgs),
+    Create(Box<CreateArgs>),
+    /// Sign in to plex.tv in a browser and save the resulting plex token
+    Login,
+    /// Print each config value and where it comes from, and the environment variables read by the
+    /// config wizard
+    Sources,
     Update(UpdateArgs),
     View,
 }
@@ -29,6 +36,28 @@ struct CreateArgs {
     profiles_directory: String,
     #[arg(long)]
     primary_section_id: u32,
+    #[arg(long)]
+    playlist_title_prefix: Option<String>,
+    #[arg(long)]
+    playlist_title_suffix: Option<String>,
+    #[arg(long, value_enum, default_value_t = SummaryMode::default())]
+    summary_mode: SummaryMode,
+    #[arg(long)]
+    default_summary_template: Option<String>,
+    #[arg(long)]
+    playlist_label: Option<String>,
+    #[arg(long)]
+    max_requests_per_refresh: Option<u32>,
+    #[arg(long, value_delimiter = ',')]
+    fallback_section_ids: Vec<u32>,
+    #[arg(long, default_value_t = false)]
+    summarize_skipped_refreshes: bool,
+    #[arg(long, default_value_t = false)]
+    skip_unchanged_refreshes: bool,
+    #[arg(long)]
+    title_length_warning: Option<u32>,
+    #[arg(long)]
+    refresh_retries: Option<u32>,
 }
 
 #[derive(Args, PartialEq)]
@@ -37,17 +66,35 @@ struct UpdateArgs {
     profiles_directory: Option<String>,
 }
 
-pub async fn run_config_cmd(cfg: CliConfig) -> Result<()> {
+pub async fn run_config_cmd(cfg: CliConfig, database_url: Option<&str>) -> Result<()> {
     match cfg.config_cmds {
         ConfigCmds::Create(cmd) => {
             let new_config = AppConfigBuilder::default()
                 .plex_token(cmd.plex_token)
                 .plex_url(cmd.plex_url)
                 .primary_section_id(cmd.primary_section_id)
+                .playlist_title_prefix(cmd.playlist_title_prefix)
+                .playlist_title_suffix(cmd.playlist_title_suffix)
+                .summary_mode(cmd.summary_mode)
+                .default_summary_template(cmd.default_summary_template)
+                .playlist_label(cmd.playlist_label)
+                .max_requests_per_refresh(cmd.max_requests_per_refresh)
+                .fallback_section_ids(cmd.fallback_section_ids)
+                .summarize_skipped_refreshes(cmd.summarize_skipped_refreshes)
+                .skip_unchanged_refreshes(cmd.skip_unchanged_refreshes)
+                .title_length_warning(cmd.title_length_warning)
+                .refresh_retries(cmd.refresh_retries)
                 .build()?;
 
             db::config::save_config(&new_config).await?;
         }
+        ConfigCmds::Login => login().await?,
+        ConfigCmds::Sources => {
+            let resolver = config::resolve_config()
+                .await?
+                .with_database_url(db::resolve_database_url(database_url));
+            println!("{resolver}");
+        }
         ConfigCmds::View => {
             // let _config = AppConfig::load_config().await;
             let _config = db::config::fetch_config().await?;
@@ -67,3 +114,33 @@ pub async fn run_config_cmd(cfg: CliConfig) -> Result<()> {
 
     Ok(())
 }
+
+/// Signs in to plex.tv with a PIN and saves the resulting token
+///
+/// If no config exists yet, the config wizard is started with the new token.
+async fn login() -> Result<()> {
+    let client = PlexTvClient::new(PLEX_TV_URL)?;
+    let pin = client.create_pin().await?;
+
+    info!(
+        "Open the following link to sign in to plex:\n{}\nYour code is <b>{}</b>",
+        client.get_auth_url(&pin)?,
+        pin.get_code()
+    );
+    info!("Waiting for sign in...");
+    let plex_token = client
+        .wait_for_token(&pin, PIN_POLL_INTERVAL, pin.get_expires_in())
+        .await?;
+    info!("Successfully signed in to plex!");
+
+    if db::config::have_config().await? {
+        let mut config = db::config::fetch_config().await?;
+        config.set_plex_token(&plex_token);
+        db::config::save_config(&config).await?;
+        info!("Saved the new plex token.");
+    } else {
+        config::run_config_wizard(Some(plex_token.to_string())).await?;
+    }
+
+    Ok(())
+}
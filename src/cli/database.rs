@@ -0,0 +1,92 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use simplelog::{info, warn};
+
+use crate::db::bundle;
+use crate::db::bundle::Bundle;
+use crate::profiles::derived;
+
+#[derive(Args, PartialEq)]
+pub struct CliDb {
+    #[command(subcommand)]
+    db_cmds: DbCmds,
+}
+
+#[derive(Subcommand, PartialEq)]
+enum DbCmds {
+    /// Write every profile, section, and config setting to a JSON bundle
+    Export {
+        /// The file to write the bundle to
+        file: String,
+        /// Include the plex token in the bundle
+        #[arg(long, default_value_t = false)]
+        include_token: bool,
+    },
+    /// Replace every profile and config setting with those in a JSON bundle
+    Import {
+        /// The bundle to import
+        file: String,
+    },
+    /// Re-derive the computed fields of every profile and report any that differ from the view
+    Recompute,
+}
+
+pub async fn run_db_cmd(db: CliDb) -> Result<()> {
+    match db.db_cmds {
+        DbCmds::Export {
+            file,
+            include_token,
+        } => {
+            let bundle = bundle::export_bundle(include_token).await?;
+            tokio::fs::write(&file, serde_json::to_string_pretty(&bundle)?).await?;
+            info!(
+                "Exported {} profile{} to `{file}`",
+                bundle.get_num_profiles(),
+                if bundle.get_num_profiles() == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            );
+        }
+        DbCmds::Import { file } => {
+            let bundle = Bundle::parse(&tokio::fs::read_to_string(&file).await?)?;
+
+            let import =
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                    "Replace every profile and config setting with the {} profile{} in `{file}`?",
+                    bundle.get_num_profiles(),
+                    if bundle.get_num_profiles() == 1 { "" } else { "s" }
+                ))
+                    .default(false)
+                    .interact()?;
+            if !import {
+                info!("Nothing was imported");
+                return Ok(());
+            }
+
+            bundle::import_bundle(&bundle).await?;
+            info!("Imported `{file}`");
+        }
+        DbCmds::Recompute => {
+            let mismatches = derived::recompute_profiles().await?;
+            if mismatches.is_empty() {
+                info!("The computed fields of every profile match the view.");
+            } else {
+                for mismatch in &mismatches {
+                    warn!("{mismatch}");
+                }
+                warn!(
+                    "Found {} mismatched field{}.",
+                    mismatches.len(),
+                    if mismatches.len() == 1 { "" } else { "s" }
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,15 +1,24 @@
 use crate::cli::config::CliConfig;
+use crate::cli::database::CliDb;
+use crate::cli::plex::CliPlex;
 use crate::cli::profile::CliProfile;
 use crate::cli::run::RunCmds;
+use crate::cli::util::CliUtil;
+use crate::cli::version::CliVersion;
 use crate::db;
 use crate::profiles::manager::ProfileManager;
+use crate::profiles::ProfileAction;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use log::Level;
 
 mod config;
+mod database;
+mod plex;
 mod profile;
 mod run;
+mod util;
+mod version;
 
 #[derive(PartialEq, Parser)]
 #[command(version, about, long_about = None)]
@@ -21,6 +30,12 @@ pub struct Cli {
     /// Set logging level, e.g. Debug, Info, Error.
     #[arg(long)]
     pub log_level: Option<Level>,
+    /// Never modify the plex server. Mutating requests are logged instead of being sent.
+    #[arg(long, global = true)]
+    pub read_only: bool,
+    /// Log the method, path, response status, and elapsed time of every request sent to plex
+    #[arg(long, global = true)]
+    pub trace_http: bool,
     /// hitomi commands
     #[command(subcommand)]
     pub commands: Commands,
@@ -31,19 +46,41 @@ pub enum Commands {
     Run(RunCmds),
     Profile(CliProfile),
     Config(CliConfig),
+    Plex(CliPlex),
+    /// Inspect the hitomi database
+    Db(CliDb),
+    /// Small debugging aids
+    Util(CliUtil),
+    /// Print version information, useful when filing bug reports
+    Version(CliVersion),
 }
 
 pub async fn run_cli_command(cli: Cli) -> Result<()> {
     db::initialize_pool(cli.database_url.as_deref()).await?;
+    crate::plex::set_read_only(cli.read_only);
+    crate::http_client::set_trace_http(cli.trace_http);
     match cli.commands {
         Commands::Run(run) => {
             run::execute_run_cmd(run).await?;
         }
         Commands::Profile(profile) => {
+            // Simulating never connects to plex, so it runs without a manager
+            if let ProfileAction::Simulate {
+                profile_file,
+                tracks_file,
+            } = &profile.profile_cmds
+            {
+                return profile::simulate_profile(profile_file, tracks_file).await;
+            }
+
             let manager = ProfileManager::new().await?;
             profile::run_profile_command(profile, manager).await?
         }
-        Commands::Config(cfg) => config::run_config_cmd(cfg).await?,
+        Commands::Config(cfg) => config::run_config_cmd(cfg, cli.database_url.as_deref()).await?,
+        Commands::Plex(plex) => plex::run_plex_cmd(plex).await?,
+        Commands::Db(db) => database::run_db_cmd(db).await?,
+        Commands::Util(util) => util::run_util_cmd(util).await?,
+        Commands::Version(version) => version::run_version_cmd(version).await?,
     }
 
     Ok(())
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+use itertools::Itertools;
+
+use crate::config;
+use crate::http_client;
+use crate::plex::{schema, stats, PlexClient};
+
+#[derive(Args, Debug, PartialEq)]
+pub struct CliPlex {
+    #[command(subcommand)]
+    pub plex_cmds: PlexCmds,
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum PlexCmds {
+    /// Perform a `GET` request against a plex API path and print the raw JSON response
+    Raw {
+        /// The API path, e.g. `library/metadata/12345`
+        path: String,
+    },
+    /// Fetch a small page of tracks and check that each can be read by `hitomi`, reporting the
+    /// first field that fails
+    CheckSchema {
+        /// The number of tracks to check
+        #[arg(long, default_value_t = 50)]
+        limit: i32,
+        /// The library section to read. Defaults to the primary music section.
+        #[arg(long)]
+        section_id: Option<u32>,
+    },
+    /// List the tracks added to a music library within the last number of days
+    Recent {
+        /// The number of days to look back
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        /// The library section to read. Defaults to the primary music section.
+        #[arg(long)]
+        section_id: Option<u32>,
+    },
+    /// Fetch tracks using raw plex filters and print them, to try filters before adding them to a
+    /// profile
+    Query {
+        /// A plex filter as `key=value`, e.g. `decade=1990`. May be repeated, and repeating a key
+        /// matches any of its values.
+        #[arg(long = "filter", value_parser = parse_filter)]
+        filters: Vec<(String, String)>,
+        /// A comma separated list of fields to sort by, e.g. `userRating:desc,viewCount`
+        #[arg(long, value_delimiter = ',')]
+        sort: Vec<String>,
+        /// The maximum number of tracks to fetch
+        #[arg(long, default_value_t = 50)]
+        limit: i32,
+    },
+    /// Display statistics about a music library
+    Stats {
+        #[command(subcommand)]
+        stats_cmds: StatsCmds,
+    },
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum StatsCmds {
+    /// Display a histogram of track counts by album release year
+    Years {
+        /// The library section to read. Defaults to the primary music section.
+        #[arg(long)]
+        section_id: Option<u32>,
+        /// Group years by decade
+        #[arg(long, default_value_t = false)]
+        decades: bool,
+    },
+}
+
+pub async fn run_plex_cmd(plex: CliPlex) -> Result<()> {
+    let config = config::load_config().await?;
+    let plex_client = PlexClient::initialize(&config).await?;
+
+    match plex.plex_cmds {
+        PlexCmds::Raw { path } => {
+            let json = plex_client.fetch_raw(&path).await?;
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        PlexCmds::CheckSchema { limit, section_id } => {
+            let tracks = plex_client.fetch_raw_tracks(section_id, limit).await?;
+            match schema::find_schema_error(&tracks) {
+                Some(err) => return Err(anyhow!("{err}")),
+                None => println!("All {} tracks deserialized successfully", tracks.len()),
+            }
+        }
+        PlexCmds::Recent { days, section_id } => {
+            let tracks = plex_client
+                .fetch_recently_added_tracks(days, section_id)
+                .await?;
+            println!(
+                "{} track{} added in the last {days} day{}",
+                tracks.len(),
+                if tracks.len() == 1 { "" } else { "s" },
+                if days == 1 { "" } else { "s" }
+            );
+            for track in tracks {
+                println!(
+                    "{}  {} - {}",
+                    track.get_added_at_str(),
+                    track.get_track_artist(),
+                    track.get_track_title()
+                );
+            }
+        }
+        PlexCmds::Query {
+            filters,
+            sort,
+            limit,
+        } => {
+            let tracks = plex_client
+                .fetch_music(
+                    build_query_filters(filters),
+                    sort.iter().map(String::as_str).collect(),
+                    Some(limit),
+                )
+                .await?;
+            println!(
+                "{} track{} found",
+                tracks.len(),
+                if tracks.len() == 1 { "" } else { "s" }
+            );
+            for track in tracks {
+                println!("{track}");
+            }
+        }
+        PlexCmds::Stats { stats_cmds } => match stats_cmds {
+            StatsCmds::Years {
+                section_id,
+                decades,
+            } => {
+                let tracks = plex_client.fetch_all_tracks(section_id).await?;
+                let counts = stats::count_tracks_by_year(&tracks, decades);
+                println!("{} tracks", tracks.len());
+                println!("{}", stats::format_year_histogram(&counts, decades));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Parses a `--filter` flag in the form `key=value`
+fn parse_filter(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("`{input}` is not a `key=value` filter")),
+    }
+}
+
+/// Builds the filters sent to plex from the `--filter` flags, joining the values of a repeated
+/// key so that plex matches any of them
+fn build_query_filters(filters: Vec<(String, String)>) -> HashMap<String, String> {
+    filters
+        .into_iter()
+        .into_group_map()
+        .into_iter()
+        .map(|(key, values)| (key, http_client::join_filter_values(&values)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use pretty_assertions::assert_eq;
+
+    use crate::cli::{Cli, Commands};
+
+    use super::*;
+
+    #[test]
+    fn test_build_query_filters_from_flags() {
+        let cli = Cli::try_parse_from([
+            "hitomi",
+            "plex",
+            "query",
+            "--filter",
+            "decade=1990",
+            "--filter",
+            "artist.id=1041",
+            "--filter",
+            "artist.id=2002",
+            "--filter",
+            "userRating>>=6",
+            "--sort",
+            "userRating:desc,viewCount",
+            "--limit",
+            "25",
+        ])
+        .unwrap();
+
+        let Commands::Plex(CliPlex {
+            plex_cmds:
+                PlexCmds::Query {
+                    filters,
+                    sort,
+                    limit,
+                },
+        }) = cli.commands
+        else {
+            panic!("expected a plex query command");
+        };
+
+        assert_eq!(
+            HashMap::from([
+                ("decade".to_string(), "1990".to_string()),
+                ("artist.id".to_string(), "1041,2002".to_string()),
+                ("userRating>>".to_string(), "6".to_string()),
+            ]),
+            build_query_filters(filters)
+        );
+        assert_eq!(vec!["userRating:desc", "viewCount"], sort);
+        assert_eq!(25, limit);
+
+        assert!(Cli::try_parse_from(["hitomi", "plex", "query", "--filter", "decade"]).is_err());
+    }
+}
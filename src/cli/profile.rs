@@ -1,13 +1,19 @@
-use anyhow::Result;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
 use clap::Args;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
+use jiff::Zoned;
 use simplelog::{debug, info};
 
-use crate::db;
+use crate::output;
+use crate::output::{JsonStyle, OutputFormat};
 use crate::profiles::manager::ProfileManager;
 use crate::profiles::profile::Profile;
+use crate::profiles::simulate;
 use crate::profiles::{wizards, ProfileAction};
+use crate::{db, utils};
 
 #[derive(Args, Debug, PartialEq)]
 pub struct CliProfile {
@@ -17,54 +23,225 @@ pub struct CliProfile {
 
 pub async fn run_profile_command(profile: CliProfile, mut manager: ProfileManager) -> Result<()> {
     match profile.profile_cmds {
-        ProfileAction::Create => {
+        ProfileAction::Advise { title } => {
+            let profile = resolve_profile(&title).await?;
+            manager.advise_interval(&profile).await?
+        }
+        ProfileAction::ArtistTracks { title, artist_name } => {
+            let profile = resolve_profile(&title).await?;
+            manager.print_artist_tracks(&profile, &artist_name).await?
+        }
+        ProfileAction::Audit { fix } => manager.audit_profiles(fix).await?,
+        ProfileAction::Bench {
+            title,
+            runs,
+            cached,
+        } => {
+            let profile = resolve_profile(&title).await?;
+            manager.bench_profile(&profile, runs, cached).await?
+        }
+        ProfileAction::Cancel { title } => {
+            let profile = resolve_profile(&title).await?;
+            db::profiles::request_refresh_cancellation(profile.get_profile_id()).await?;
+            info!(
+                "Requested cancellation of the refresh of `{}`",
+                profile.get_title()
+            )
+        }
+        ProfileAction::Create { save } => {
             let (profile, sections) = wizards::create_profile_wizard(&manager).await?;
-            manager.create_playlist(&profile, &sections).await?;
+            manager.create_playlist(&profile, &sections, save).await?;
             // db::profiles::create_profile(&profile, &sections).await?;
 
             info!("Profile created successfully!")
         }
         ProfileAction::Edit => {}
+        ProfileAction::Export {
+            title,
+            format,
+            output,
+            include_art,
+        } => {
+            let profile = resolve_profile(&title).await?;
+            let export = manager
+                .export_playlist(&profile, format, include_art)
+                .await?;
+            match output {
+                Some(output) => {
+                    tokio::fs::write(&output, export).await?;
+                    info!("Exported `{title}` to `{output}`")
+                }
+                None => print!("{export}"),
+            }
+        }
+        ProfileAction::Compare { title_a, title_b } => {
+            let left = resolve_profile(&title_a).await?;
+            let right = resolve_profile(&title_b).await?;
+            manager.compare_profiles(&left, &right).await?
+        }
+        ProfileAction::Filters { title } => {
+            let profile = resolve_profile(&title).await?;
+            manager.print_profile_filters(&profile).await?
+        }
         ProfileAction::Delete => {}
-        ProfileAction::List => manager.list_profiles_and_sections().await?,
-        ProfileAction::Preview => {
-            preview_playlist(&manager).await?;
+        ProfileAction::ImportDir { dir } => manager.import_profiles_from_dir(&dir).await?,
+        ProfileAction::List { sort_by, reverse } => {
+            manager.list_profiles_and_sections(sort_by, reverse).await?
+        }
+        ProfileAction::Next { enabled_only } => manager.print_next_refreshes(enabled_only).await?,
+        ProfileAction::Preview {
+            profile,
+            count,
+            relative_dates,
+            by_artist,
+            order_note,
+            alpha,
+        } => {
+            preview_playlist(
+                &manager,
+                profile,
+                count,
+                relative_dates,
+                by_artist,
+                order_note,
+                alpha,
+            )
+            .await?;
+        }
+        ProfileAction::Overlap { titles } => {
+            let mut profiles = vec![];
+            for title in titles {
+                profiles.push(resolve_profile(&title).await?);
+            }
+            manager.print_overlap(&profiles).await?
+        }
+        ProfileAction::Prune {
+            older_than,
+            delete_playlists,
+        } => manager.prune_profiles(older_than, delete_playlists).await?,
+        ProfileAction::Shadow {
+            title,
+            time_limit,
+            track_limit,
+        } => {
+            let profile = resolve_profile(&title).await?;
+            manager
+                .shadow_profile(&profile, time_limit, track_limit)
+                .await?
+        }
+        ProfileAction::ScheduleOnce { title, time } => {
+            let profile = resolve_profile(&title).await?;
+            schedule_once(&profile, &time).await?
+        }
+        ProfileAction::ShadowCleanup => manager.cleanup_shadows().await?,
+        // Simulating runs without a manager, so it is dispatched by `run_cli_command`
+        ProfileAction::Simulate { .. } => {}
+        ProfileAction::Restore { title, snapshot } => {
+            let profile = resolve_profile(&title).await?;
+            manager.restore_snapshot(&profile, snapshot).await?
         }
         ProfileAction::Update => {}
-        ProfileAction::View => view_playlist(&manager).await?,
+        ProfileAction::View {
+            profile,
+            output,
+            json_style,
+            alpha,
+        } => view_playlist(&manager, profile, output, json_style, alpha).await?,
     }
 
     Ok(())
 }
 
-async fn preview_playlist(manager: &ProfileManager) -> Result<()> {
+/// Schedules a single refresh of a profile, which is run by the refresh loop
+async fn schedule_once(profile: &Profile, time: &str) -> Result<()> {
+    let now = Zoned::now();
+    let refresh_at = utils::parse_schedule_time(time, &now)
+        .map_err(|err| anyhow!("Invalid time `{time}`: {err}"))?;
+    if refresh_at <= now {
+        return Err(anyhow!("`{time}` is not in the future"));
+    }
+
+    db::profiles::schedule_refresh(profile.get_profile_id(), refresh_at.timestamp().as_second())
+        .await?;
+    info!(
+        "Scheduled a refresh of `{}` at {}",
+        profile.get_title(),
+        refresh_at.strftime("%F %R")
+    );
+
+    Ok(())
+}
+
+/// Prints the playlist a profile file builds from a fixture file
+pub async fn simulate_profile(profile_file: &str, tracks_file: &str) -> Result<()> {
+    let profile_tracks =
+        simulate::simulate_profile(Path::new(profile_file), Path::new(tracks_file)).await?;
+    profile_tracks.print_preview(0, false);
+
+    Ok(())
+}
+
+async fn preview_playlist(
+    manager: &ProfileManager,
+    profile: Option<String>,
+    count: usize,
+    relative_dates: bool,
+    by_artist: bool,
+    order_note: bool,
+    alphabetical: bool,
+) -> Result<()> {
     if !manager.have_profiles().await? {
         println!("No profiles found.");
         return Ok(());
     }
 
-    let profile = select_profile("Select which profile you would like to preview:").await?;
-    manager.preview_playlist(&profile).await?;
+    let profile = resolve_or_select_profile(
+        profile,
+        "Select which profile you would like to preview:",
+        alphabetical,
+    )
+    .await?;
+    manager
+        .preview_playlist(&profile, count, relative_dates, by_artist, order_note)
+        .await?;
+    db::profiles::mark_profile_used(profile.get_profile_id()).await?;
 
     Ok(())
 }
 
-async fn view_playlist(manager: &ProfileManager) -> Result<()> {
+async fn view_playlist(
+    manager: &ProfileManager,
+    profile: Option<String>,
+    output: OutputFormat,
+    json_style: JsonStyle,
+    alphabetical: bool,
+) -> Result<()> {
     if !manager.have_profiles().await? {
         println!("No profiles found.");
         return Ok(());
     }
 
-    let profile = select_profile("Select which profile you would like to view:").await?;
-    println!("{profile}");
+    let profile = resolve_or_select_profile(
+        profile,
+        "Select which profile you would like to view:",
+        alphabetical,
+    )
+    .await?;
+    match output {
+        OutputFormat::Text => {
+            println!("{profile}");
+
+            // Print raw json of profile
+            debug!("{}\n", output::to_json(&profile, JsonStyle::Pretty)?);
+        }
+        OutputFormat::Json => println!("{}", output::to_json(&profile, json_style)?),
+    }
 
-    // Print raw json of profile
-    debug!("{}\n", serde_json::to_string_pretty(&profile).unwrap());
     Ok(())
 }
 
-async fn select_profile(prompt: &str) -> Result<Profile> {
-    let titles = db::profiles::fetch_profile_titles().await?;
+async fn select_profile(prompt: &str, alphabetical: bool) -> Result<Profile> {
+    let titles = db::profiles::fetch_profile_titles(alphabetical).await?;
     let selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .items(&titles)
@@ -77,3 +254,19 @@ async fn select_profile(prompt: &str) -> Result<Profile> {
 
     Ok(profile)
 }
+
+/// Returns the profile given on the command line, or asks the user to select one if none was given
+async fn resolve_or_select_profile(
+    arg: Option<String>,
+    prompt: &str,
+    alphabetical: bool,
+) -> Result<Profile> {
+    match arg {
+        Some(arg) => resolve_profile(&arg).await,
+        None => select_profile(prompt, alphabetical).await,
+    }
+}
+
+async fn resolve_profile(arg: &str) -> Result<Profile> {
+    db::profiles::resolve_profile(arg).await
+}
@@ -2,9 +2,13 @@ use std::time::Duration;
 
 use anyhow::Result;
 use clap::Args;
-use simplelog::info;
+use simplelog::{info, warn};
+use tokio::signal;
+use tokio::sync::watch;
 use tokio::time::sleep;
 
+use crate::db;
+use crate::profiles::active::ActiveRefreshes;
 use crate::profiles::manager::ProfileManager;
 
 #[derive(Args, Debug, PartialEq)]
@@ -24,22 +28,94 @@ fn print_title(looping: bool) {
     }
 }
 
+/// Returns a receiver that is set to `true` once the user presses `Ctrl+C`
+fn listen_for_shutdown() -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if signal::ctrl_c().await.is_ok() {
+            info!("Shutting down...");
+            let _ = tx.send(true);
+        }
+    });
+
+    rx
+}
+
+/// Cancels the refreshes requested by `profile cancel` as the requests arrive
+fn listen_for_cancellations(active_refreshes: ActiveRefreshes) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_secs(1)).await;
+            let profile_ids = match db::profiles::take_refresh_cancellations().await {
+                Ok(profile_ids) => profile_ids,
+                Err(err) => {
+                    warn!("Unable to check for cancelled refreshes: {err}");
+                    continue;
+                }
+            };
+
+            for profile_id in profile_ids {
+                if !active_refreshes.cancel(profile_id) {
+                    info!("Profile {profile_id} is not refreshing. Nothing to cancel.");
+                }
+            }
+        }
+    });
+}
+
+/// Logs the refreshes underway each time the process receives `SIGUSR1`
+#[cfg(unix)]
+fn listen_for_list_signal(active_refreshes: ActiveRefreshes) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let Ok(mut signals) = signal(SignalKind::user_defined1()) else {
+        warn!("Unable to listen for SIGUSR1. Active refreshes cannot be listed.");
+        return;
+    };
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let refreshes = active_refreshes.list();
+            if refreshes.is_empty() {
+                info!("No profiles are refreshing");
+            }
+            for (profile_id, title) in refreshes {
+                info!("Refreshing `{title}` (id {profile_id})");
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn listen_for_list_signal(_active_refreshes: ActiveRefreshes) {}
+
 pub async fn execute_run_cmd(cmd: RunCmds) -> Result<()> {
     print_title(cmd.run_loop);
     let manager = ProfileManager::new().await?;
+    let mut shutdown = listen_for_shutdown();
+    if cmd.run_loop {
+        // Requests left from before the loop started do not apply to its refreshes
+        db::profiles::take_refresh_cancellations().await?;
+        listen_for_cancellations(manager.get_active_refreshes().clone());
+        listen_for_list_signal(manager.get_active_refreshes().clone());
+    }
 
     // Initial refresh is performed irrespective of `run_loop` flag
     manager
-        .refresh_playlists_from_profiles(cmd.run_loop, false)
+        .refresh_playlists_from_profiles(cmd.run_loop, false, &mut shutdown)
         .await?;
 
     if cmd.run_loop {
-        loop {
-            sleep(Duration::from_secs(1)).await;
+        while !*shutdown.borrow() {
+            tokio::select! {
+                _ = sleep(Duration::from_secs(1)) => {}
+                _ = shutdown.changed() => break,
+            }
+
+            manager.refresh_scheduled_profiles(&mut shutdown).await?;
 
             if manager.fetch_any_profile_refresh().await? {
                 manager
-                    .refresh_playlists_from_profiles(cmd.run_loop, true)
+                    .refresh_playlists_from_profiles(cmd.run_loop, true, &mut shutdown)
                     .await?;
             }
         }
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+use itertools::Itertools;
+
+use crate::types::profiles::refresh_interval::RefreshInterval;
+use crate::utils;
+
+#[derive(Args, PartialEq)]
+pub struct CliUtil {
+    #[command(subcommand)]
+    util_cmds: UtilCmds,
+}
+
+#[derive(Subcommand, PartialEq)]
+enum UtilCmds {
+    /// Print the minutes of each hour at which a profile with the given refresh interval refreshes
+    RefreshMinutes {
+        /// The refresh interval in minutes
+        interval: u32,
+    },
+}
+
+pub async fn run_util_cmd(util: CliUtil) -> Result<()> {
+    match util.util_cmds {
+        UtilCmds::RefreshMinutes { interval } => {
+            println!("{}", format_refresh_minutes(interval)?)
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the refresh minutes of an interval, e.g. `15,30,45,60` for an interval of `15`
+fn format_refresh_minutes(interval: u32) -> Result<String> {
+    let refresh_interval = RefreshInterval::try_new(interval)
+        .map_err(|err| anyhow!("Invalid refresh interval `{interval}`: {err}"))?;
+    if interval == 0 {
+        return Err(anyhow!(
+            "Invalid refresh interval `{interval}`: must be at least 1"
+        ));
+    }
+
+    Ok(utils::build_refresh_minutes(&refresh_interval)
+        .iter()
+        .join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_format_refresh_minutes() {
+        assert_eq!("12,24,36,48,60", format_refresh_minutes(12).unwrap());
+        assert!(format_refresh_minutes(0).is_err());
+        assert!(format_refresh_minutes(61).is_err());
+    }
+}
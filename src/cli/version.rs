@@ -0,0 +1,43 @@
+use anyhow::Result;
+use clap::Args;
+use simplelog::warn;
+
+use crate::plex::PlexClient;
+use crate::{config, db};
+
+#[derive(Args, Debug, PartialEq)]
+pub struct CliVersion {
+    /// Also print the database schema version and the plex server version
+    #[arg(long, default_value_t = false)]
+    pub full: bool,
+}
+
+pub async fn run_version_cmd(version: CliVersion) -> Result<()> {
+    println!("hitomi:         {}", env!("CARGO_PKG_VERSION"));
+
+    if !version.full {
+        return Ok(());
+    }
+
+    let schema_version = match db::fetch_schema_version().await? {
+        Some(schema_version) => schema_version.to_string(),
+        None => String::from("no migrations applied"),
+    };
+    println!("Schema version: {schema_version}");
+
+    let server_version = if db::config::have_config().await? {
+        let config = config::load_config().await?;
+        match PlexClient::initialize(&config).await {
+            Ok(plex_client) => plex_client.get_server_version().to_string(),
+            Err(err) => {
+                warn!("Unable to connect to plex: {err}");
+                String::from("unavailable")
+            }
+        }
+    } else {
+        String::from("not configured")
+    };
+    println!("Plex server:    {server_version}");
+
+    Ok(())
+}
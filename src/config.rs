@@ -2,19 +2,34 @@
 
 use std::env;
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
-use anyhow::Result;
-use clap::Args;
+use anyhow::{anyhow, Result};
+use clap::{Args, ValueEnum};
 use derive_builder::Builder;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, Select};
+use itertools::Itertools;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use simplelog::{debug, info};
+use simplelog::{debug, error, info};
+use strum::{Display, EnumString};
 
 use crate::db;
+use crate::plex::models::sections::Section;
 use crate::plex::PlexClient;
 use crate::types::plex::plex_token::PlexToken;
+use crate::types::plex::plex_url::PlexUrl;
+
+/// The default time to wait for plex to respond when testing the connection in the config wizard
+const CONNECTION_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The playlist title length above which creating a profile asks for confirmation, since plex
+/// truncates long titles in its UI
+pub const DEFAULT_TITLE_LENGTH_WARNING: u32 = 80;
+
+/// The number of times a profile whose tracks could not be fetched is retried within a cycle
+pub const DEFAULT_REFRESH_RETRIES: u32 = 1;
 
 /// Represents the configuration file
 #[derive(Args, Builder, Clone, Debug, Deserialize, Serialize, PartialEq, sqlx::Type)]
@@ -25,6 +40,79 @@ pub struct Config {
     plex_url: String,
     #[arg(long)]
     primary_section_id: u32,
+    /// Text prepended to the title of every playlist created in plex
+    #[arg(long)]
+    #[builder(default)]
+    playlist_title_prefix: Option<String>,
+    /// Text appended to the title of every playlist created in plex
+    #[arg(long)]
+    #[builder(default)]
+    playlist_title_suffix: Option<String>,
+    /// Determines how the playlist summary in plex is updated after each refresh
+    #[arg(long, value_enum, default_value_t = SummaryMode::default())]
+    #[builder(default)]
+    summary_mode: SummaryMode,
+    /// The summary suggested when creating a profile. See
+    /// [`Profile::render_summary_template`](crate::profiles::profile::Profile::render_summary_template)
+    /// for the supported placeholders.
+    #[arg(long)]
+    #[builder(default)]
+    default_summary_template: Option<String>,
+    /// A label applied to every playlist created in plex, to identify playlists managed by `hitomi`
+    #[arg(long)]
+    #[builder(default)]
+    playlist_label: Option<String>,
+    /// The maximum number of requests sent to plex while refreshing a single profile. A refresh
+    /// that exceeds it is aborted.
+    #[arg(long)]
+    #[builder(default)]
+    max_requests_per_refresh: Option<u32>,
+    /// Library sections tried in order when the primary section returns an error or no tracks
+    #[arg(long, value_delimiter = ',')]
+    #[builder(default)]
+    fallback_section_ids: Vec<u32>,
+    /// If true, a skipped refresh still updates the playlist summary with the reason it was
+    /// skipped, so that a stale playlist can be spotted in plex
+    #[arg(long, default_value_t = false)]
+    #[builder(default)]
+    summarize_skipped_refreshes: bool,
+    /// If true, a refresh that would write the same tracks as the last refresh leaves the
+    /// playlist's items untouched and only updates its summary
+    #[arg(long, default_value_t = false)]
+    #[builder(default)]
+    skip_unchanged_refreshes: bool,
+    /// Creating a profile whose playlist title is longer than this many characters asks for
+    /// confirmation. Defaults to 80.
+    #[arg(long)]
+    #[builder(default)]
+    title_length_warning: Option<u32>,
+    /// The number of times a profile whose tracks could not be fetched from plex is retried
+    /// once the other profiles have refreshed. Defaults to 1.
+    #[arg(long)]
+    #[builder(default)]
+    refresh_retries: Option<u32>,
+}
+
+/// Determines how the playlist summary in plex is updated after each refresh
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    PartialEq,
+    Serialize,
+    ValueEnum,
+    sqlx::Type,
+)]
+pub enum SummaryMode {
+    /// The entire summary is replaced
+    #[default]
+    Replace,
+    /// Only the text between the `hitomi` markers is replaced, preserving any other text
+    Preserve,
 }
 
 impl Default for Config {
@@ -33,6 +121,17 @@ impl Default for Config {
             plex_url: "http://127.0.0.1:32400".to_string(),
             plex_token: "PLEX_TOKEN".to_string(),
             primary_section_id: 0,
+            playlist_title_prefix: None,
+            playlist_title_suffix: None,
+            summary_mode: SummaryMode::default(),
+            default_summary_template: None,
+            playlist_label: None,
+            max_requests_per_refresh: None,
+            fallback_section_ids: vec![],
+            summarize_skipped_refreshes: false,
+            skip_unchanged_refreshes: false,
+            title_length_warning: None,
+            refresh_retries: None,
         }
     }
 }
@@ -43,7 +142,7 @@ impl Config {
     }
 
     pub fn get_plex_url(&self) -> Result<Url> {
-        Ok(Url::parse(&self.plex_url)?)
+        Ok(Url::parse(&PlexUrl::try_new(&self.plex_url)?)?)
     }
 
     pub fn get_plex_url_str(&self) -> String {
@@ -54,62 +153,353 @@ impl Config {
         Ok(PlexToken::try_new(&self.plex_token)?)
     }
 
+    pub fn set_plex_token(&mut self, plex_token: &PlexToken) {
+        self.plex_token = plex_token.to_string();
+    }
+
     pub fn get_primary_section_id(&self) -> u32 {
         self.primary_section_id
     }
+
+    pub fn get_playlist_title_prefix(&self) -> Option<&str> {
+        self.playlist_title_prefix.as_deref()
+    }
+
+    pub fn get_playlist_title_suffix(&self) -> Option<&str> {
+        self.playlist_title_suffix.as_deref()
+    }
+
+    pub fn get_summary_mode(&self) -> SummaryMode {
+        self.summary_mode
+    }
+
+    pub fn get_default_summary_template(&self) -> Option<&str> {
+        self.default_summary_template.as_deref()
+    }
+
+    pub fn get_playlist_label(&self) -> Option<&str> {
+        self.playlist_label.as_deref()
+    }
+
+    pub fn get_max_requests_per_refresh(&self) -> Option<u32> {
+        self.max_requests_per_refresh
+    }
+
+    pub fn get_fallback_section_ids(&self) -> &[u32] {
+        &self.fallback_section_ids
+    }
+
+    pub fn get_summarize_skipped_refreshes(&self) -> bool {
+        self.summarize_skipped_refreshes
+    }
+
+    pub fn get_skip_unchanged_refreshes(&self) -> bool {
+        self.skip_unchanged_refreshes
+    }
+
+    /// The configured title length warning, if any
+    pub fn get_title_length_warning_setting(&self) -> Option<u32> {
+        self.title_length_warning
+    }
+
+    pub fn get_title_length_warning(&self) -> u32 {
+        self.title_length_warning
+            .unwrap_or(DEFAULT_TITLE_LENGTH_WARNING)
+    }
+
+    /// The configured number of refresh retries, if any
+    pub fn get_refresh_retries_setting(&self) -> Option<u32> {
+        self.refresh_retries
+    }
+
+    pub fn get_refresh_retries(&self) -> u32 {
+        self.refresh_retries.unwrap_or(DEFAULT_REFRESH_RETRIES)
+    }
+
+    /// The fallback section ids as stored in the database, e.g. `3,7`
+    pub fn get_fallback_section_ids_str(&self) -> String {
+        self.fallback_section_ids.iter().join(",")
+    }
+}
+
+/// Where a resolved config value came from
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum ConfigSource {
+    /// An environment variable
+    Environment,
+    /// The `--db` flag
+    #[strum(to_string = "--db flag")]
+    Flag,
+    /// The config table in the database
+    Database,
+    /// No source set the value
+    Default,
+}
+
+/// The settings that may be read from an environment variable by the config wizard, and the
+/// name of the variable
+const ENVIRONMENT_SETTINGS: [(&str, &str); 3] = [
+    ("plex_url", "PLEX_URL"),
+    ("plex_token", "PLEX_TOKEN"),
+    ("primary_section_id", "PRIMARY_SECTION_ID"),
+];
+
+/// Settings whose values are masked when displayed
+const SECRET_SETTINGS: [&str; 1] = ["plex_token"];
+
+/// Returns the name of every field of [`Config`], sorted by name
+fn get_setting_names() -> Vec<String> {
+    match serde_json::to_value(Config::default()) {
+        Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => vec![],
+    }
+}
+
+/// A config value and the source it was resolved from
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedValue {
+    name: String,
+    value: Option<String>,
+    source: ConfigSource,
+    /// Secret values are masked when displayed
+    secret: bool,
+}
+
+impl ResolvedValue {
+    pub fn get_value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    pub fn get_source(&self) -> ConfigSource {
+        self.source
+    }
+
+    /// Returns the value to display, masking all but the last four characters of secrets
+    fn get_display_value(&self) -> String {
+        match &self.value {
+            None => "(not set)".to_string(),
+            Some(value) if self.secret => {
+                let masked = value.chars().count().saturating_sub(4);
+                format!(
+                    "{}{}",
+                    "*".repeat(masked),
+                    value.chars().skip(masked).collect::<String>()
+                )
+            }
+            Some(value) => value.to_owned(),
+        }
+    }
+}
+
+/// Reports where each config value comes from
+///
+/// Every command loads the config from the config table in the database, so a stored value is
+/// always the effective one. Environment variables are only read by the config wizard, and are
+/// reported separately as wizard inputs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigResolver {
+    database_url: Option<ResolvedValue>,
+    values: Vec<ResolvedValue>,
+    wizard_inputs: Vec<ResolvedValue>,
+}
+
+impl ConfigResolver {
+    /// Reports the settings stored in the database and the environment variables read by the
+    /// config wizard, where `env` returns the value of an environment variable
+    ///
+    /// Settings stored in the database that are not fields of [`Config`] are kept as they are.
+    pub fn new(settings: &[(String, String)], env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut names = get_setting_names();
+        for (setting, _) in settings {
+            if !names.contains(setting) {
+                names.push(setting.to_owned());
+            }
+        }
+
+        let values = names
+            .into_iter()
+            .map(|name| {
+                let value = settings
+                    .iter()
+                    .find(|(setting, _)| *setting == name)
+                    .map(|(_, value)| value.to_owned());
+                let source = if value.is_some() {
+                    ConfigSource::Database
+                } else {
+                    ConfigSource::Default
+                };
+
+                ResolvedValue {
+                    secret: SECRET_SETTINGS.contains(&name.as_str()),
+                    name,
+                    value,
+                    source,
+                }
+            })
+            .collect();
+
+        let wizard_inputs = ENVIRONMENT_SETTINGS
+            .iter()
+            .filter_map(|(setting, env_var)| {
+                Some(ResolvedValue {
+                    name: env_var.to_string(),
+                    value: Some(env(env_var)?),
+                    source: ConfigSource::Environment,
+                    secret: SECRET_SETTINGS.contains(setting),
+                })
+            })
+            .collect();
+
+        Self {
+            database_url: None,
+            values,
+            wizard_inputs,
+        }
+    }
+
+    /// Includes the database url, resolved by [`db::resolve_database_url`], in the report
+    pub fn with_database_url(mut self, database_url: (String, ConfigSource)) -> Self {
+        self.database_url = Some(ResolvedValue {
+            name: "database_url".to_string(),
+            value: Some(database_url.0),
+            source: database_url.1,
+            secret: false,
+        });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ResolvedValue> {
+        self.values.iter().find(|value| value.name == name)
+    }
+
+    /// Returns the value of an environment variable read by the config wizard, if it is set
+    pub fn get_wizard_input(&self, env_var: &str) -> Option<&ResolvedValue> {
+        self.wizard_inputs
+            .iter()
+            .find(|value| value.name == env_var)
+    }
+}
+
+impl Display for ConfigResolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let values = self
+            .database_url
+            .iter()
+            .chain(&self.values)
+            .collect::<Vec<_>>();
+        let name_width = values
+            .iter()
+            .map(|value| value.name.len())
+            .max()
+            .unwrap_or_default();
+
+        let mut output = format!("{:<name_width$}  {:<11}  Value", "Setting", "Source");
+        for value in values {
+            output += &format!(
+                "\n{:<name_width$}  {:<11}  {}",
+                value.name,
+                value.source.to_string(),
+                value.get_display_value()
+            );
+        }
+
+        if !self.wizard_inputs.is_empty() {
+            output += "\n\nEnvironment variables (only read by the config wizard):";
+            for value in &self.wizard_inputs {
+                output += &format!("\n{}={}", value.name, value.get_display_value());
+            }
+        }
+
+        write!(f, "{output}")
+    }
+}
+
+/// Fetches the settings stored in the database and resolves them against the environment
+pub async fn resolve_config() -> Result<ConfigResolver> {
+    let settings = db::config::fetch_config_settings().await?;
+    Ok(ConfigResolver::new(&settings, |name| env::var(name).ok()))
 }
 
 /// Wizard used by user to create an initial configuration table
 pub async fn build_config_wizard() -> Result<Config> {
     info!("Config table not populated. Checking for environment variables...");
 
-    let plex_url = if let Ok(plex_url) = env::var("PLEX_URL") {
-        plex_url
-    } else {
-        Input::<String>::with_theme(&ColorfulTheme::default())
-            .with_prompt("Enter your plex URL:")
-            .interact_text()?
-            .to_string()
-    };
-    let plex_url = Url::parse(&plex_url)?;
-
-    let plex_token = if let Ok(plex_token) = env::var("PLEX_TOKEN") {
-        plex_token
-    } else {
-        Input::<String>::with_theme(&ColorfulTheme::default())
-            .with_prompt("Enter your plex token:")
-            .interact_text()?
-            .to_string()
-    };
-    let plex_token = PlexToken::try_new(plex_token)?;
+    run_config_wizard(env::var("PLEX_TOKEN").ok()).await
+}
 
-    info!("Testing connection to plex. Please wait...");
-    match PlexClient::new_for_config(&plex_url, &plex_token).await {
-        Ok(_) => {
-            info!("Successfully connected to plex!");
-        }
-        Err(err) => {
-            panic!("Could not connect to plex:\n{err}")
+/// Runs the config wizard, using `plex_token` for the first connection attempt if provided
+pub async fn run_config_wizard(plex_token: Option<String>) -> Result<Config> {
+    let timeout = get_connection_test_timeout();
+
+    // Environment variables are only used for the first attempt, so that the user can correct
+    // them if the connection fails
+    let mut env_plex_url = env::var("PLEX_URL").ok();
+    let mut env_plex_token = plex_token;
+
+    let (plex_url, plex_token, plex) = loop {
+        let plex_url = match env_plex_url.take() {
+            Some(plex_url) => plex_url,
+            None => Input::<String>::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your plex URL:")
+                .interact_text()?
+                .to_string(),
+        };
+        let plex_url = match Url::parse(&plex_url) {
+            Ok(plex_url) => plex_url,
+            Err(err) => {
+                error!("Invalid plex URL `{plex_url}`: {err}");
+                continue;
+            }
+        };
+
+        let plex_token = match env_plex_token.take() {
+            Some(plex_token) => plex_token,
+            None => Input::<String>::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your plex token:")
+                .interact_text()?
+                .to_string(),
+        };
+        let plex_token = match PlexToken::try_new(plex_token) {
+            Ok(plex_token) => plex_token,
+            Err(err) => {
+                error!("Invalid plex token: {err}");
+                continue;
+            }
+        };
+
+        info!("Testing connection to plex. Please wait...");
+        match test_plex_connection(&plex_url, &plex_token, timeout).await {
+            Ok(plex) => {
+                info!("Successfully connected to plex!");
+                break (plex_url, plex_token, plex);
+            }
+            Err(err) => {
+                error!("Could not connect to plex:\n{err}\nPlease check your plex URL and token.");
+            }
         }
     };
 
     let primary_section_id = if let Ok(id) = env::var("PRIMARY_SECTION_ID") {
         id.parse::<u32>()
+            .map_err(|_| anyhow!("`PRIMARY_SECTION_ID` must be a section id, e.g. `1`"))?
     } else {
-        let plex = PlexClient::new_for_config(&plex_url, &plex_token).await?;
         let sections = plex.get_music_sections();
-        let titles = sections
-            .iter()
-            .map(|x| x.get_title().to_owned())
-            .collect::<Vec<String>>();
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select your music library:")
-            .default(0)
-            .items(&titles)
-            .interact()?;
-        sections[selection].id().parse::<u32>()
-    }
-    .expect("Could not parse section id");
+        if let Some(section) = get_only_music_section(sections) {
+            info!("Found a single music library: `{}`", section.get_title());
+            section.get_section_id()?
+        } else {
+            let titles = sections
+                .iter()
+                .map(|x| x.get_title().to_owned())
+                .collect::<Vec<String>>();
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select your music library:")
+                .default(0)
+                .items(&titles)
+                .interact()?;
+            sections[selection].get_section_id()?
+        }
+    };
 
     let config = ConfigBuilder::default()
         .plex_url(plex_url.to_string())
@@ -122,6 +512,38 @@ pub async fn build_config_wizard() -> Result<Config> {
     Ok(config)
 }
 
+/// Returns the connection test timeout, read in seconds from `PLEX_CONNECTION_TIMEOUT`
+fn get_connection_test_timeout() -> Duration {
+    env::var("PLEX_CONNECTION_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(CONNECTION_TEST_TIMEOUT)
+}
+
+/// Connects to plex, failing if the server does not respond within the timeout
+async fn test_plex_connection(
+    plex_url: &Url,
+    plex_token: &PlexToken,
+    timeout: Duration,
+) -> Result<PlexClient> {
+    match tokio::time::timeout(timeout, PlexClient::new_for_config(plex_url, plex_token)).await {
+        Ok(plex) => plex,
+        Err(_) => Err(anyhow!(
+            "Timed out after {} waiting for a response from `{plex_url}`",
+            humantime::format_duration(timeout)
+        )),
+    }
+}
+
+/// Returns the music section if it is the only music section on the server
+fn get_only_music_section(sections: &[Section]) -> Option<&Section> {
+    match sections {
+        [section] => Some(section),
+        _ => None,
+    }
+}
+
 pub async fn load_config() -> Result<Config> {
     debug!("Loading config...");
 
@@ -139,6 +561,40 @@ impl Display for Config {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut output = String::default();
         output += &format!("Plex URL:       {}\n", self.get_plex_url_str());
+        if let Some(prefix) = self.get_playlist_title_prefix() {
+            output += &format!("Title Prefix:   `{prefix}`\n");
+        }
+        if let Some(suffix) = self.get_playlist_title_suffix() {
+            output += &format!("Title Suffix:   `{suffix}`\n");
+        }
+        output += &format!("Summary Mode:   {}\n", self.summary_mode);
+        if let Some(label) = self.get_playlist_label() {
+            output += &format!("Playlist Label: `{label}`\n");
+        }
+        if let Some(template) = self.get_default_summary_template() {
+            output += &format!("Template:       `{template}`\n");
+        }
+        if let Some(max_requests) = self.get_max_requests_per_refresh() {
+            output += &format!("Max Requests:   {max_requests} per refresh\n");
+        }
+        if !self.fallback_section_ids.is_empty() {
+            output += &format!(
+                "Fallbacks:      {}\n",
+                self.fallback_section_ids.iter().join(", ")
+            );
+        }
+        if self.summarize_skipped_refreshes {
+            output += "Skip Summary:   enabled\n";
+        }
+        if self.skip_unchanged_refreshes {
+            output += "Skip Unchanged: enabled\n";
+        }
+        if let Some(length) = self.title_length_warning {
+            output += &format!("Title Warning:  {length} characters\n");
+        }
+        if let Some(retries) = self.refresh_retries {
+            output += &format!("Retries:        {retries}\n");
+        }
 
         write!(f, "{}", output)
     }
@@ -152,6 +608,26 @@ mod tests {
     const VALID_TOKEN: &str = "RWtuIcHBY-hq6HbSq3GY";
     const VALID_URL: &str = "http://127.0.0.1:32400";
 
+    #[tokio::test]
+    async fn test_plex_connection_timeout() {
+        // The server accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let plex_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                connections.push(stream);
+            }
+        });
+
+        let plex_token = PlexToken::try_new(VALID_TOKEN).unwrap();
+        let result = test_plex_connection(&plex_url, &plex_token, Duration::from_millis(100)).await;
+
+        let err = result.err().unwrap().to_string();
+        assert!(err.starts_with("Timed out after 100ms"), "{err}");
+    }
+
     #[test]
     fn test_valid_config() {
         let config = ConfigBuilder::default()
@@ -168,6 +644,32 @@ mod tests {
         assert_eq!(config.get_plex_url().unwrap(), valid_url);
     }
 
+    fn build_section(title: &str, key: &str) -> Section {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "type": "artist",
+            "key": key,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_single_music_section_auto_selected() {
+        let sections = vec![build_section("Music", "5")];
+        let section = get_only_music_section(&sections).unwrap();
+        assert_eq!(5, section.get_section_id().unwrap());
+    }
+
+    #[test]
+    fn test_multiple_music_sections_not_auto_selected() {
+        let sections = vec![
+            build_section("Music", "5"),
+            build_section("Audiobooks", "6"),
+        ];
+        assert!(get_only_music_section(&sections).is_none());
+        assert!(get_only_music_section(&[]).is_none());
+    }
+
     #[test]
     #[should_panic]
     fn test_invalid_config_token() {
@@ -193,4 +695,76 @@ mod tests {
 
         config.get_plex_url().unwrap();
     }
+
+    #[test]
+    fn test_config_resolver_reports_database_values() {
+        let settings = vec![
+            ("plex_url".to_string(), "http://10.0.0.1:32400".to_string()),
+            ("plex_token".to_string(), VALID_TOKEN.to_string()),
+            ("primary_section_id".to_string(), "1".to_string()),
+        ];
+        let env = |name: &str| match name {
+            "PLEX_URL" => Some(VALID_URL.to_string()),
+            "PLEX_TOKEN" => Some(VALID_TOKEN.to_string()),
+            _ => None,
+        };
+
+        let resolver = ConfigResolver::new(&settings, env);
+
+        // The stored value is used, even though the environment sets another one
+        let plex_url = resolver.get("plex_url").unwrap();
+        assert_eq!(ConfigSource::Database, plex_url.get_source());
+        assert_eq!(Some("http://10.0.0.1:32400"), plex_url.get_value());
+        assert_eq!(
+            Some(VALID_URL),
+            resolver.get_wizard_input("PLEX_URL").unwrap().get_value()
+        );
+        assert_eq!(
+            "****************q3GY",
+            resolver
+                .get_wizard_input("PLEX_TOKEN")
+                .unwrap()
+                .get_display_value()
+        );
+        assert_eq!(None, resolver.get_wizard_input("PRIMARY_SECTION_ID"));
+
+        let plex_token = resolver.get("plex_token").unwrap();
+        assert_eq!(ConfigSource::Database, plex_token.get_source());
+        assert_eq!("****************q3GY", plex_token.get_display_value());
+
+        let label = resolver.get("playlist_label").unwrap();
+        assert_eq!(ConfigSource::Default, label.get_source());
+        assert_eq!(None, label.get_value());
+    }
+
+    #[test]
+    fn test_config_resolver_keeps_every_setting() {
+        let settings = vec![("refresh_retries".to_string(), "3".to_string())];
+        let resolver = ConfigResolver::new(&settings, |_| None);
+
+        // Every field of the config is reported, whether or not it is set
+        assert_eq!(
+            get_setting_names(),
+            resolver
+                .values
+                .iter()
+                .map(|value| value.name.to_owned())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("3"),
+            resolver.get("refresh_retries").unwrap().get_value()
+        );
+    }
+
+    #[test]
+    fn test_secret_display_value_masks_characters() {
+        let value = ResolvedValue {
+            name: "plex_token".to_string(),
+            value: Some("ÄÖÜßabcd".to_string()),
+            source: ConfigSource::Database,
+            secret: true,
+        };
+        assert_eq!("****abcd", value.get_display_value());
+    }
 }
@@ -0,0 +1,305 @@
+//! Exporting and importing every profile and the config as a single portable JSON bundle
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use simplelog::warn;
+use sqlx::SqlitePool;
+
+use crate::db;
+use crate::db::config::{build_config, select_config_settings};
+use crate::db::profiles::{insert_new_profile, select_profile, select_profile_sections};
+use crate::profiles::profile::Profile;
+use crate::profiles::profile_section::ProfileSection;
+
+/// The version of the bundle format written by this version of hitomi
+///
+/// Bundles written by older versions can still be imported, since fields they are missing fall
+/// back to their defaults. Bundles written by newer versions are rejected.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// The name of the config setting holding the plex token
+const PLEX_TOKEN: &str = "plex_token";
+
+/// Every profile, its sections, and the config settings stored in the database
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Bundle {
+    version: u32,
+    /// Each config setting by name. The plex token is only included if requested.
+    config: BTreeMap<String, String>,
+    profiles: Vec<BundledProfile>,
+}
+
+/// A profile and its sections within a bundle
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct BundledProfile {
+    profile: Profile,
+    #[serde(default)]
+    sections: Vec<ProfileSection>,
+}
+
+impl Bundle {
+    /// Parses a bundle, rejecting bundles written by a newer version of hitomi
+    ///
+    /// Titles, playlist ids, and the other newtypes are validated as the bundle is parsed.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let bundle: Bundle = serde_json::from_str(contents)?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(anyhow!(
+                "The bundle was written with version {} of the bundle format, but only versions up to {BUNDLE_VERSION} are supported. Update hitomi and try again.",
+                bundle.version
+            ));
+        }
+
+        Ok(bundle)
+    }
+
+    pub fn get_num_profiles(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn has_plex_token(&self) -> bool {
+        self.config.contains_key(PLEX_TOKEN)
+    }
+}
+
+/// Collects every profile, section, and config setting into a bundle
+///
+/// The plex token is left out unless `include_token` is set.
+pub async fn export_bundle(include_token: bool) -> Result<Bundle> {
+    select_bundle(db::get_pool()?, include_token).await
+}
+
+async fn select_bundle(pool: &SqlitePool, include_token: bool) -> Result<Bundle> {
+    let mut config = select_config_settings(pool)
+        .await?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    if !include_token {
+        config.remove(PLEX_TOKEN);
+    }
+
+    let ids: Vec<(i32,)> = sqlx::query_as("select profile_id from profile order by profile_title")
+        .fetch_all(pool)
+        .await?;
+    let sections = select_profile_sections(pool).await?;
+
+    let mut profiles = vec![];
+    for (profile_id,) in ids {
+        profiles.push(BundledProfile {
+            profile: select_profile(pool, profile_id).await?,
+            sections: sections
+                .iter()
+                .filter(|section| section.get_profile_id() == profile_id)
+                .cloned()
+                .collect(),
+        });
+    }
+
+    Ok(Bundle {
+        version: BUNDLE_VERSION,
+        config,
+        profiles,
+    })
+}
+
+/// Replaces every profile and config setting with those in the bundle
+///
+/// The refresh history and playlist snapshots of replaced profiles are deleted along with them.
+/// If the bundle does not include a plex token, the token already stored is kept.
+pub async fn import_bundle(bundle: &Bundle) -> Result<()> {
+    insert_bundle(db::get_pool()?, bundle).await
+}
+
+async fn insert_bundle(pool: &SqlitePool, bundle: &Bundle) -> Result<()> {
+    let mut settings = bundle.config.clone();
+    if !bundle.has_plex_token() {
+        if let Some((_, token)) = select_config_settings(pool)
+            .await?
+            .into_iter()
+            .find(|(name, _)| name == PLEX_TOKEN)
+        {
+            settings.insert(PLEX_TOKEN.to_string(), token);
+        }
+    }
+
+    if settings.contains_key(PLEX_TOKEN) {
+        let settings = settings
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect_vec();
+        build_config(&settings)?.get_plex_token()?;
+    } else if !settings.is_empty() {
+        warn!("The bundle does not include a plex token and none is stored. Run `hitomi config login` after importing.");
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("delete from profile").execute(&mut *tx).await?;
+    sqlx::query("delete from config").execute(&mut *tx).await?;
+
+    for (name, value) in &settings {
+        sqlx::query("insert into config values (?, ?)")
+            .bind(name)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for bundled in &bundle.profiles {
+        let profile = &bundled.profile;
+        let profile_id = insert_new_profile(
+            &mut tx,
+            profile.get_playlist_id().as_str(),
+            profile,
+            &bundled.sections,
+        )
+        .await?;
+
+        // New profiles and sections are always saved as enabled
+        sqlx::query(
+            r#"
+            update profile
+            set enabled = ?1,
+                disabled_at = case when ?1 then null else current_timestamp end
+            where profile_id = ?2
+            "#,
+        )
+        .bind(profile.get_enabled())
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let section_ids: Vec<(i32,)> = sqlx::query_as(
+            "select profile_section_id from profile_section where profile_id = ? order by profile_section_id",
+        )
+        .bind(profile_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for ((section_id,), section) in section_ids.into_iter().zip(&bundled.sections) {
+            sqlx::query("update profile_section set enabled = ? where profile_section_id = ?")
+                .bind(section.is_enabled())
+                .bind(section_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::profiles::profile::ProfileBuilder;
+    use crate::profiles::profile_section::ProfileSectionBuilder;
+    use crate::profiles::SectionType;
+    use crate::types::plex::plex_id::PlexId;
+    use crate::types::Title;
+
+    use super::*;
+
+    const TOKEN: &str = "NJlYINZmB-Hdy78xubjR";
+
+    async fn insert_settings(pool: &SqlitePool, settings: &[(&str, &str)]) {
+        for (name, value) in settings {
+            sqlx::query("insert into config values (?, ?)")
+                .bind(name)
+                .bind(value)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    fn build_section(section_type: SectionType) -> ProfileSection {
+        ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(0)
+            .randomize_tracks(false)
+            .section_type(section_type)
+            .sorting("random".to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let source = db::build_test_pool().await;
+        insert_settings(
+            &source,
+            &[
+                (PLEX_TOKEN, TOKEN),
+                ("plex_url", "http://127.0.0.1:32400"),
+                ("primary_section_id", "1"),
+            ],
+        )
+        .await;
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Daily Mix").unwrap())
+            .playlist_id(PlexId::try_new("111111").unwrap())
+            .time_limit(6)
+            .build()
+            .unwrap();
+        let sections = [
+            build_section(SectionType::Unplayed),
+            build_section(SectionType::Oldest),
+        ];
+        let mut conn = source.acquire().await.unwrap();
+        insert_new_profile(&mut conn, "111111", &profile, &sections)
+            .await
+            .unwrap();
+        drop(conn);
+        sqlx::query("update profile_section set enabled = 0 where section_type = 'Oldest'")
+            .execute(&source)
+            .await
+            .unwrap();
+
+        let exported = select_bundle(&source, false).await.unwrap();
+        assert!(!exported.has_plex_token());
+        let contents = serde_json::to_string_pretty(&exported).unwrap();
+
+        // The destination already has a token, which is kept since the bundle does not include one
+        let destination = db::build_test_pool().await;
+        insert_settings(&destination, &[(PLEX_TOKEN, TOKEN)]).await;
+        insert_bundle(&destination, &Bundle::parse(&contents).unwrap())
+            .await
+            .unwrap();
+
+        let source_bundle = select_bundle(&source, true).await.unwrap();
+        let destination_bundle = select_bundle(&destination, true).await.unwrap();
+        assert_eq!(source_bundle.config, destination_bundle.config);
+        assert_eq!(1, destination_bundle.get_num_profiles());
+        let imported = &destination_bundle.profiles[0];
+        assert_eq!("Daily Mix", imported.profile.get_title());
+        assert_eq!("111111", imported.profile.get_playlist_id().as_str());
+        assert_eq!(6, imported.profile.get_time_limit());
+        assert_eq!(source_bundle.profiles[0].sections, imported.sections);
+        assert_eq!(
+            vec![true, false],
+            imported
+                .sections
+                .iter()
+                .map(|section| section.is_enabled())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_newer_versions() {
+        let contents = format!(
+            r#"{{"version": {}, "config": {{}}, "profiles": []}}"#,
+            BUNDLE_VERSION + 1
+        );
+
+        assert!(Bundle::parse(&contents).is_err());
+    }
+}
@@ -1,7 +1,9 @@
+use std::str::FromStr;
+
 use anyhow::Result;
-use sqlx::{Encode, Sqlite};
+use sqlx::{Encode, Sqlite, SqliteConnection, SqlitePool};
 
-use crate::config::{Config as AppConfig, ConfigBuilder};
+use crate::config::{Config as AppConfig, ConfigBuilder, SummaryMode};
 use crate::db;
 
 #[derive(sqlx::FromRow)]
@@ -25,6 +27,7 @@ pub async fn have_config() -> Result<bool> {
 }
 
 async fn add_config_setting<'q, T: 'q + Send + Encode<'q, Sqlite> + sqlx::Type<Sqlite>>(
+    conn: &mut SqliteConnection,
     name: &'q str,
     value: T,
 ) -> Result<()> {
@@ -36,43 +39,173 @@ async fn add_config_setting<'q, T: 'q + Send + Encode<'q, Sqlite> + sqlx::Type<S
     )
     .bind(name)
     .bind(value)
-    .execute(db::get_pool()?)
+    .execute(conn)
     .await?;
 
     Ok(())
 }
 
+/// Saves the config, replacing any existing settings
+///
+/// The settings are replaced in a single transaction, so a failed insert leaves the stored config
+/// untouched.
 pub async fn save_config(config: &AppConfig) -> Result<()> {
-    add_config_setting("plex_token", config.get_plex_token()?.as_str()).await?;
-    add_config_setting("plex_url", config.get_plex_url_str()).await?;
-    add_config_setting("primary_section_id", config.get_primary_section_id()).await?;
+    let mut tx = db::get_pool()?.begin().await?;
+
+    sqlx::query("delete from config").execute(&mut *tx).await?;
+
+    add_config_setting(&mut tx, "plex_token", config.get_plex_token()?.as_str()).await?;
+    add_config_setting(&mut tx, "plex_url", config.get_plex_url_str()).await?;
+    add_config_setting(
+        &mut tx,
+        "primary_section_id",
+        config.get_primary_section_id(),
+    )
+    .await?;
+    if let Some(prefix) = config.get_playlist_title_prefix() {
+        add_config_setting(&mut tx, "playlist_title_prefix", prefix).await?;
+    }
+    if let Some(suffix) = config.get_playlist_title_suffix() {
+        add_config_setting(&mut tx, "playlist_title_suffix", suffix).await?;
+    }
+    add_config_setting(
+        &mut tx,
+        "summary_mode",
+        config.get_summary_mode().to_string(),
+    )
+    .await?;
+    if let Some(template) = config.get_default_summary_template() {
+        add_config_setting(&mut tx, "default_summary_template", template).await?;
+    }
+    if let Some(label) = config.get_playlist_label() {
+        add_config_setting(&mut tx, "playlist_label", label).await?;
+    }
+    if let Some(max_requests) = config.get_max_requests_per_refresh() {
+        add_config_setting(&mut tx, "max_requests_per_refresh", max_requests).await?;
+    }
+    if !config.get_fallback_section_ids().is_empty() {
+        add_config_setting(
+            &mut tx,
+            "fallback_section_ids",
+            config.get_fallback_section_ids_str(),
+        )
+        .await?;
+    }
+    if config.get_summarize_skipped_refreshes() {
+        add_config_setting(&mut tx, "summarize_skipped_refreshes", "true").await?;
+    }
+    if config.get_skip_unchanged_refreshes() {
+        add_config_setting(&mut tx, "skip_unchanged_refreshes", "true").await?;
+    }
+    if let Some(length) = config.get_title_length_warning_setting() {
+        add_config_setting(&mut tx, "title_length_warning", length).await?;
+    }
+    if let Some(retries) = config.get_refresh_retries_setting() {
+        add_config_setting(&mut tx, "refresh_retries", retries).await?;
+    }
+
+    tx.commit().await?;
 
     Ok(())
 }
 
 pub async fn fetch_config() -> Result<AppConfig> {
+    build_config(&fetch_config_settings().await?)
+}
+
+/// Returns the name and value of each setting stored in the config table
+pub async fn fetch_config_settings() -> Result<Vec<(String, String)>> {
+    select_config_settings(db::get_pool()?).await
+}
+
+pub(super) async fn select_config_settings(pool: &SqlitePool) -> Result<Vec<(String, String)>> {
     let rows = sqlx::query_as::<_, DbConfig>(
         r#"
         select * from config
     "#,
     )
-    .fetch_all(db::get_pool()?)
+    .fetch_all(pool)
     .await?;
 
+    Ok(rows.into_iter().map(|row| (row.name, row.value)).collect())
+}
+
+/// Builds a config from the name and value of each setting
+pub fn build_config(settings: &[(String, String)]) -> Result<AppConfig> {
     let mut config = ConfigBuilder::default();
-    for row in rows {
-        if row.name == "plex_token" {
-            config.plex_token(row.value);
+    for (name, value) in settings {
+        if name == "plex_token" {
+            config.plex_token(value.to_owned());
+            continue;
+        }
+
+        if name == "plex_url" {
+            config.plex_url(value.to_owned());
+            continue;
+        }
+
+        if name == "primary_section_id" {
+            config.primary_section_id(value.parse()?);
+            continue;
+        }
+
+        if name == "playlist_title_prefix" {
+            config.playlist_title_prefix(Some(value.to_owned()));
+            continue;
+        }
+
+        if name == "playlist_title_suffix" {
+            config.playlist_title_suffix(Some(value.to_owned()));
+            continue;
+        }
+
+        if name == "summary_mode" {
+            config.summary_mode(SummaryMode::from_str(value)?);
+            continue;
+        }
+
+        if name == "default_summary_template" {
+            config.default_summary_template(Some(value.to_owned()));
+            continue;
+        }
+
+        if name == "playlist_label" {
+            config.playlist_label(Some(value.to_owned()));
+            continue;
+        }
+
+        if name == "max_requests_per_refresh" {
+            config.max_requests_per_refresh(Some(value.parse()?));
+            continue;
+        }
+
+        if name == "fallback_section_ids" {
+            config.fallback_section_ids(
+                value
+                    .split(',')
+                    .map(|id| id.trim().parse())
+                    .collect::<Result<Vec<u32>, _>>()?,
+            );
+            continue;
+        }
+
+        if name == "summarize_skipped_refreshes" {
+            config.summarize_skipped_refreshes(value.parse()?);
+            continue;
+        }
+
+        if name == "skip_unchanged_refreshes" {
+            config.skip_unchanged_refreshes(value.parse()?);
             continue;
         }
 
-        if row.name == "plex_url" {
-            config.plex_url(row.value);
+        if name == "title_length_warning" {
+            config.title_length_warning(Some(value.parse()?));
             continue;
         }
 
-        if row.name == "primary_section_id" {
-            config.primary_section_id(row.value.parse()?);
+        if name == "refresh_retries" {
+            config.refresh_retries(Some(value.parse()?));
             continue;
         }
     }
@@ -9,6 +9,9 @@ use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
 use sqlx::SqlitePool;
 use tokio::sync::OnceCell;
 
+use crate::config::ConfigSource;
+
+pub mod bundle;
 pub mod config;
 pub mod profiles;
 
@@ -21,15 +24,26 @@ fn get_pool() -> Result<&'static SqlitePool> {
     }
 }
 
-pub async fn initialize_pool(database_url: Option<&str>) -> Result<()> {
-    let database_url = if let Ok(database_url) = env::var("DATABASE_URL") {
-        database_url
+/// The database used if neither `DATABASE_URL` nor the `--db` flag is set
+const DEFAULT_DATABASE_URL: &str = "sqlite:./data/hitomi.db";
+
+/// Resolves the database url from the `DATABASE_URL` environment variable, then the `--db`
+/// flag, then the default url
+pub fn resolve_database_url(database_url: Option<&str>) -> (String, ConfigSource) {
+    if let Ok(database_url) = env::var("DATABASE_URL") {
+        (database_url, ConfigSource::Environment)
     } else if let Some(database_url) = database_url {
-        database_url.to_string()
+        (database_url.to_string(), ConfigSource::Flag)
     } else {
+        (DEFAULT_DATABASE_URL.to_string(), ConfigSource::Default)
+    }
+}
+
+pub async fn initialize_pool(database_url: Option<&str>) -> Result<()> {
+    let (database_url, source) = resolve_database_url(database_url);
+    if source == ConfigSource::Default {
         warn!("Environment variable `DATABASE_URL` not set and --db flag not provided. Using default URL.");
-        String::from("sqlite:./data/hitomi.db")
-    };
+    }
 
     let database_url = if database_url.contains("sqlite:") {
         database_url
@@ -46,3 +60,49 @@ pub async fn initialize_pool(database_url: Option<&str>) -> Result<()> {
 
     Ok(())
 }
+
+/// Returns the version of the latest applied sqlx migration, if any migrations have been run
+pub async fn fetch_schema_version() -> Result<Option<i64>> {
+    let pool = get_pool()?;
+
+    let has_migrations: (i32,) = sqlx::query_as(
+        "select count(*) from sqlite_master where type = 'table' and name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if has_migrations.0 == 0 {
+        return Ok(None);
+    }
+
+    let version: (Option<i64>,) =
+        sqlx::query_as("select max(version) from _sqlx_migrations where success = 1")
+            .fetch_one(pool)
+            .await?;
+
+    Ok(version.0)
+}
+
+/// Builds an in-memory database with every migration applied
+#[cfg(test)]
+pub async fn build_test_pool() -> SqlitePool {
+    // Each connection to an in-memory database is a separate database
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+    let mut migrations = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    migrations.sort();
+    for migration in migrations {
+        let sql = std::fs::read_to_string(migration).unwrap();
+        sqlx::raw_sql(&sql).execute(&pool).await.unwrap();
+    }
+
+    pool
+}
@@ -1,13 +1,16 @@
+use std::collections::HashSet;
 use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use simplelog::debug;
-use sqlx::Row;
+use sqlx::{Row, SqliteConnection, SqlitePool};
 
 use crate::db;
+use crate::profiles::derived::ProfileViewRecord;
 use crate::profiles::profile::{Profile, ProfileBuilder};
 use crate::profiles::profile_section::ProfileSection;
-use crate::profiles::{ProfileSource, SectionType};
+use crate::profiles::{BalanceBy, EmptySectionBehavior, MergeStrategy, ProfileSource, SectionType};
+use crate::types::plex::hub_identifier::HubIdentifier;
 use crate::types::plex::plex_id::PlexId;
 use crate::types::profiles::profile_source_id::ProfileSourceId;
 use crate::types::profiles::refresh_interval::RefreshInterval;
@@ -20,6 +23,19 @@ pub async fn create_profile(
     new_profile: &Profile,
     sections: &[ProfileSection],
 ) -> Result<()> {
+    let mut conn = db::get_pool()?.acquire().await?;
+    insert_new_profile(&mut conn, playlist_id, new_profile, sections).await?;
+
+    Ok(())
+}
+
+/// Saves a new profile and its sections, returning the id of the new profile
+pub(super) async fn insert_new_profile(
+    conn: &mut SqliteConnection,
+    playlist_id: &str,
+    new_profile: &Profile,
+    sections: &[ProfileSection],
+) -> Result<i32> {
     let result = sqlx::query(
         r#"
         insert into profile (playlist_id,
@@ -30,8 +46,23 @@ pub async fn create_profile(
                      profile_source_id,
                      refresh_interval,
                      time_limit,
-                     track_limit)
-        values (?,?,?,?,?,?,?,?,?)
+                     track_limit,
+                     active_hours_start,
+                     active_hours_end,
+                     hub_identifier,
+                     empty_section_behavior,
+                     merge_order,
+                     avoid_recent_repeats_cycles,
+                     merge_strategy,
+                     balance_by,
+                     max_tracks_by_artist_global,
+                     summary_only,
+                     snapshot_count,
+                     minimum_playlist_size,
+                     fail_below_minimum_size,
+                     seeded_shuffle,
+                     minimum_valid_sections)
+        values (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)
         returning profile_id
     "#,
     )
@@ -44,19 +75,38 @@ pub async fn create_profile(
     .bind(new_profile.get_refresh_interval())
     .bind(new_profile.get_time_limit())
     .bind(new_profile.get_track_limit())
-    .fetch_one(db::get_pool()?)
+    .bind(new_profile.get_active_hours_start())
+    .bind(new_profile.get_active_hours_end())
+    .bind(new_profile.get_hub_identifier_str())
+    .bind(new_profile.get_empty_section_behavior().to_string())
+    .bind(new_profile.get_merge_order_json()?)
+    .bind(new_profile.get_avoid_recent_repeats_cycles())
+    .bind(new_profile.get_merge_strategy().to_string())
+    .bind(new_profile.get_balance_by().to_string())
+    .bind(new_profile.get_max_tracks_by_artist_global())
+    .bind(new_profile.is_summary_only())
+    .bind(new_profile.get_snapshot_count())
+    .bind(new_profile.get_minimum_playlist_size())
+    .bind(new_profile.get_fail_below_minimum_size())
+    .bind(new_profile.get_seeded_shuffle())
+    .bind(new_profile.get_minimum_valid_sections())
+    .fetch_one(&mut *conn)
     .await?;
 
     let profile_id = result.get(0);
 
     for section in sections {
-        create_profile_section(profile_id, section).await?;
+        create_profile_section(conn, profile_id, section).await?;
     }
 
-    Ok(())
+    Ok(profile_id)
 }
 
-async fn create_profile_section(profile_id: i32, section: &ProfileSection) -> Result<()> {
+async fn create_profile_section(
+    conn: &mut SqliteConnection,
+    profile_id: i32,
+    section: &ProfileSection,
+) -> Result<()> {
     sqlx::query(
         r#"
         insert into profile_section (profile_id,
@@ -66,9 +116,28 @@ async fn create_profile_section(profile_id: i32, section: &ProfileSection) -> Re
                              deduplicate_tracks_by_title_and_artist,
                              maximum_tracks_by_artist,
                              minimum_track_rating,
+                             minimum_album_completeness,
                              randomize_tracks,
+                             randomization_bias,
+                             oldest_bucket,
+                             exclude_never_played,
+                             exclude_genres,
+                             min_duration_seconds,
+                             max_duration_seconds,
+                             normalize_featured_artists,
+                             content_rating_preference,
+                             exclude_recently_played,
+                             least_played_includes_unplayed,
+                             use_net_plays,
+                             keep_top_n_fixed,
+                             dedup_key,
+                             boost_recently_added,
+                             missing_duration_policy,
+                             server_random,
+                             extra_filters,
+                             minimum_plays,
                              sorting)
-        VALUES(?,?,?,?,?,?,?,?,?)
+        VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)
     "#,
     )
     .bind(profile_id)
@@ -78,9 +147,28 @@ async fn create_profile_section(profile_id: i32, section: &ProfileSection) -> Re
     .bind(section.get_deduplicate_tracks_by_title_and_artist())
     .bind(section.get_maximum_tracks_by_artist())
     .bind(section.get_minimum_track_rating())
+    .bind(section.get_minimum_album_completeness())
     .bind(section.get_randomize_tracks())
+    .bind(section.get_randomization_bias())
+    .bind(section.get_oldest_bucket())
+    .bind(section.get_exclude_never_played())
+    .bind(section.get_exclude_genres_json()?)
+    .bind(section.get_min_duration_seconds())
+    .bind(section.get_max_duration_seconds())
+    .bind(section.get_normalize_featured_artists())
+    .bind(section.get_content_rating_preference())
+    .bind(section.get_exclude_recently_played())
+    .bind(section.get_least_played_includes_unplayed())
+    .bind(section.get_use_net_plays())
+    .bind(section.get_keep_top_n_fixed())
+    .bind(section.get_dedup_key())
+    .bind(section.get_boost_recently_added())
+    .bind(section.get_missing_duration_policy())
+    .bind(section.get_server_random())
+    .bind(section.get_extra_filters_json()?)
+    .bind(section.get_minimum_plays())
     .bind(section.get_sorting())
-    .execute(db::get_pool()?)
+    .execute(conn)
     .await?;
 
     Ok(())
@@ -108,22 +196,55 @@ pub async fn update_profile(profile: &Profile, sections: &[ProfileSection]) -> R
         set profile_title = ?,
             profile_summary = ?,
             enabled = ?,
+            disabled_at = case when ? then null else coalesce(disabled_at, current_timestamp) end,
+            last_used_at = current_timestamp,
             profile_source = ?,
             profile_source_id = ?,
             refresh_interval = ?,
             time_limit = ?,
-            track_limit = ?
+            track_limit = ?,
+            active_hours_start = ?,
+            active_hours_end = ?,
+            hub_identifier = ?,
+            empty_section_behavior = ?,
+            merge_order = ?,
+            avoid_recent_repeats_cycles = ?,
+            merge_strategy = ?,
+            balance_by = ?,
+            max_tracks_by_artist_global = ?,
+            summary_only = ?,
+            snapshot_count = ?,
+            minimum_playlist_size = ?,
+            fail_below_minimum_size = ?,
+            seeded_shuffle = ?,
+            minimum_valid_sections = ?
         where profile_id = ?
     "#,
     )
     .bind(profile.get_title())
     .bind(profile.get_summary())
     .bind(profile.get_enabled())
+    .bind(profile.get_enabled())
     .bind(profile.get_profile_source().to_string())
     .bind(profile.get_profile_source_id_str())
     .bind(profile.get_refresh_interval())
     .bind(profile.get_time_limit())
     .bind(profile.get_track_limit())
+    .bind(profile.get_active_hours_start())
+    .bind(profile.get_active_hours_end())
+    .bind(profile.get_hub_identifier_str())
+    .bind(profile.get_empty_section_behavior().to_string())
+    .bind(profile.get_merge_order_json()?)
+    .bind(profile.get_avoid_recent_repeats_cycles())
+    .bind(profile.get_merge_strategy().to_string())
+    .bind(profile.get_balance_by().to_string())
+    .bind(profile.get_max_tracks_by_artist_global())
+    .bind(profile.is_summary_only())
+    .bind(profile.get_snapshot_count())
+    .bind(profile.get_minimum_playlist_size())
+    .bind(profile.get_fail_below_minimum_size())
+    .bind(profile.get_seeded_shuffle())
+    .bind(profile.get_minimum_valid_sections())
     .bind(profile_id)
     .execute(db::get_pool()?)
     .await?;
@@ -135,6 +256,144 @@ pub async fn update_profile(profile: &Profile, sections: &[ProfileSection]) -> R
     Ok(())
 }
 
+/// Points a profile at a new playlist and forgets the hash of the old playlist's tracks
+pub async fn update_playlist_id(profile_id: i32, playlist_id: &str) -> Result<()> {
+    set_playlist_id(db::get_pool()?, profile_id, playlist_id).await
+}
+
+async fn set_playlist_id(pool: &SqlitePool, profile_id: i32, playlist_id: &str) -> Result<()> {
+    sqlx::query("update profile set playlist_id = ?, track_ids_hash = null where profile_id = ?")
+        .bind(playlist_id)
+        .bind(profile_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Records that a profile was just previewed, refreshed, or edited
+pub async fn mark_profile_used(profile_id: i32) -> Result<()> {
+    sqlx::query("update profile set last_used_at = current_timestamp where profile_id = ?")
+        .bind(profile_id)
+        .execute(db::get_pool()?)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the hash of the track ids written by the profile's last refresh, if any
+pub async fn fetch_track_ids_hash(profile_id: i32) -> Result<Option<String>> {
+    let hash: Option<(Option<String>,)> =
+        sqlx::query_as("select track_ids_hash from profile where profile_id = ?")
+            .bind(profile_id)
+            .fetch_optional(db::get_pool()?)
+            .await?;
+
+    Ok(hash.and_then(|hash| hash.0))
+}
+
+/// Stores the hash of the track ids written by the profile's latest refresh
+pub async fn save_track_ids_hash(profile_id: i32, hash: &str) -> Result<()> {
+    sqlx::query("update profile set track_ids_hash = ? where profile_id = ?")
+        .bind(hash)
+        .bind(profile_id)
+        .execute(db::get_pool()?)
+        .await?;
+
+    Ok(())
+}
+
+/// Forgets the hash of the track ids written by the profile's last refresh, so that the next
+/// refresh rewrites the playlist's items even if its tracks are unchanged
+///
+/// Called whenever `hitomi` writes a playlist's items outside of a refresh.
+pub async fn clear_track_ids_hash(profile_id: i32) -> Result<()> {
+    reset_track_ids_hash(db::get_pool()?, profile_id).await
+}
+
+async fn reset_track_ids_hash(pool: &SqlitePool, profile_id: i32) -> Result<()> {
+    sqlx::query("update profile set track_ids_hash = null where profile_id = ?")
+        .bind(profile_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Records the track guids used by a refresh, keeping only the latest `cycles` refreshes
+pub async fn save_refresh_history(
+    profile_id: i32,
+    track_guids: &[&str],
+    cycles: u32,
+) -> Result<()> {
+    sqlx::query("insert into profile_refresh_history (profile_id, track_guids) values (?, ?)")
+        .bind(profile_id)
+        .bind(serde_json::to_string(track_guids)?)
+        .execute(db::get_pool()?)
+        .await?;
+
+    sqlx::query(
+        r#"
+        delete from profile_refresh_history
+        where profile_id = ?
+          and profile_refresh_history_id not in (select profile_refresh_history_id
+                                                 from profile_refresh_history
+                                                 where profile_id = ?
+                                                 order by profile_refresh_history_id desc
+                                                 limit ?)
+    "#,
+    )
+    .bind(profile_id)
+    .bind(profile_id)
+    .bind(cycles)
+    .execute(db::get_pool()?)
+    .await?;
+
+    Ok(())
+}
+
+/// Saves the track ids of a playlist before it is refreshed, keeping only the latest `keep`
+/// snapshots
+pub async fn save_playlist_snapshot(
+    profile_id: i32,
+    track_ids: &[String],
+    keep: u32,
+) -> Result<()> {
+    insert_playlist_snapshot(db::get_pool()?, profile_id, track_ids, keep).await
+}
+
+async fn insert_playlist_snapshot(
+    pool: &SqlitePool,
+    profile_id: i32,
+    track_ids: &[String],
+    keep: u32,
+) -> Result<()> {
+    sqlx::query("insert into playlist_snapshot (profile_id, track_ids) values (?, ?)")
+        .bind(profile_id)
+        .bind(serde_json::to_string(track_ids)?)
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        delete from playlist_snapshot
+        where profile_id = ?
+          and playlist_snapshot_id not in (select playlist_snapshot_id
+                                           from playlist_snapshot
+                                           where profile_id = ?
+                                           order by playlist_snapshot_id desc
+                                           limit ?)
+    "#,
+    )
+    .bind(profile_id)
+    .bind(profile_id)
+    .bind(keep)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 async fn update_profile_section(profile_id: i32, section: &ProfileSection) -> Result<()> {
     let profile_section_id = fetch_profile_section_id(profile_id, section.get_section_type())
         .await?
@@ -148,7 +407,26 @@ async fn update_profile_section(profile_id: i32, section: &ProfileSection) -> Re
            deduplicate_tracks_by_title_and_artist = ?,
            maximum_tracks_by_artist = ?,
            minimum_track_rating = ?,
+           minimum_album_completeness = ?,
            randomize_tracks = ?,
+           randomization_bias = ?,
+           oldest_bucket = ?,
+           exclude_never_played = ?,
+           exclude_genres = ?,
+           min_duration_seconds = ?,
+           max_duration_seconds = ?,
+           normalize_featured_artists = ?,
+           content_rating_preference = ?,
+           exclude_recently_played = ?,
+           least_played_includes_unplayed = ?,
+           use_net_plays = ?,
+           keep_top_n_fixed = ?,
+           dedup_key = ?,
+           boost_recently_added = ?,
+           missing_duration_policy = ?,
+           server_random = ?,
+           extra_filters = ?,
+           minimum_plays = ?,
            sorting = ?
         where profile_id = ? and profile_section_id = ?
     "#,
@@ -158,7 +436,26 @@ async fn update_profile_section(profile_id: i32, section: &ProfileSection) -> Re
     .bind(section.get_deduplicate_tracks_by_title_and_artist())
     .bind(section.get_maximum_tracks_by_artist())
     .bind(section.get_minimum_track_rating_adjusted())
+    .bind(section.get_minimum_album_completeness())
     .bind(section.get_randomize_tracks())
+    .bind(section.get_randomization_bias())
+    .bind(section.get_oldest_bucket())
+    .bind(section.get_exclude_never_played())
+    .bind(section.get_exclude_genres_json()?)
+    .bind(section.get_min_duration_seconds())
+    .bind(section.get_max_duration_seconds())
+    .bind(section.get_normalize_featured_artists())
+    .bind(section.get_content_rating_preference())
+    .bind(section.get_exclude_recently_played())
+    .bind(section.get_least_played_includes_unplayed())
+    .bind(section.get_use_net_plays())
+    .bind(section.get_keep_top_n_fixed())
+    .bind(section.get_dedup_key())
+    .bind(section.get_boost_recently_added())
+    .bind(section.get_missing_duration_policy())
+    .bind(section.get_server_random())
+    .bind(section.get_extra_filters_json()?)
+    .bind(section.get_minimum_plays())
     .bind(section.get_sorting())
     .bind(profile_id)
     .bind(profile_section_id)
@@ -170,7 +467,11 @@ async fn update_profile_section(profile_id: i32, section: &ProfileSection) -> Re
 
 // FETCH ######################################################################
 
-async fn fetch_profile(profile_id: i32) -> Result<Profile> {
+pub async fn fetch_profile(profile_id: i32) -> Result<Profile> {
+    select_profile(db::get_pool()?, profile_id).await
+}
+
+pub(super) async fn select_profile(pool: &SqlitePool, profile_id: i32) -> Result<Profile> {
     let row = sqlx::query(
         r#"
         select profile_id,
@@ -183,6 +484,21 @@ async fn fetch_profile(profile_id: i32) -> Result<Profile> {
                refresh_interval,
                time_limit,
                track_limit,
+               active_hours_start,
+               active_hours_end,
+               hub_identifier,
+               empty_section_behavior,
+               merge_order,
+               avoid_recent_repeats_cycles,
+               merge_strategy,
+               balance_by,
+               max_tracks_by_artist_global,
+               summary_only,
+               snapshot_count,
+               minimum_playlist_size,
+               fail_below_minimum_size,
+               seeded_shuffle,
+               minimum_valid_sections,
                num_sections,
                has_max_sections,
                section_time_limit,
@@ -195,7 +511,7 @@ async fn fetch_profile(profile_id: i32) -> Result<Profile> {
     "#,
     )
     .bind(profile_id)
-    .fetch_one(db::get_pool()?)
+    .fetch_one(pool)
     .await?;
 
     let playlist_id = PlexId::try_new(row.try_get::<&str, &str>("playlist_id")?).unwrap();
@@ -211,6 +527,18 @@ async fn fetch_profile(profile_id: i32) -> Result<Profile> {
         } else {
             None
         };
+    let hub_identifier = match row.try_get::<Option<&str>, &str>("hub_identifier")? {
+        Some(id) => Some(HubIdentifier::try_new(id)?),
+        None => None,
+    };
+    let empty_section_behavior =
+        EmptySectionBehavior::from_str(row.try_get::<&str, &str>("empty_section_behavior")?)?;
+    let merge_strategy = MergeStrategy::from_str(row.try_get::<&str, &str>("merge_strategy")?)?;
+    let balance_by = BalanceBy::from_str(row.try_get::<&str, &str>("balance_by")?)?;
+    let merge_order = match row.try_get::<Option<&str>, &str>("merge_order")? {
+        Some(merge_order) => serde_json::from_str(merge_order)?,
+        None => vec![],
+    };
     let refresh_interval =
         RefreshInterval::try_new(row.try_get::<u32, &str>("refresh_interval")?).unwrap();
 
@@ -222,9 +550,24 @@ async fn fetch_profile(profile_id: i32) -> Result<Profile> {
         .enabled(row.try_get("enabled")?)
         .profile_source(profile_source)
         .profile_source_id(profile_source_id)
+        .hub_identifier(hub_identifier)
         .refresh_interval(refresh_interval)
         .time_limit(row.try_get("time_limit")?)
         .track_limit(row.try_get("track_limit")?)
+        .empty_section_behavior(empty_section_behavior)
+        .merge_order(merge_order)
+        .merge_strategy(merge_strategy)
+        .balance_by(balance_by)
+        .max_tracks_by_artist_global(row.try_get("max_tracks_by_artist_global")?)
+        .summary_only(row.try_get("summary_only")?)
+        .avoid_recent_repeats_cycles(row.try_get("avoid_recent_repeats_cycles")?)
+        .snapshot_count(row.try_get("snapshot_count")?)
+        .minimum_playlist_size(row.try_get("minimum_playlist_size")?)
+        .fail_below_minimum_size(row.try_get("fail_below_minimum_size")?)
+        .seeded_shuffle(row.try_get("seeded_shuffle")?)
+        .minimum_valid_sections(row.try_get("minimum_valid_sections")?)
+        .active_hours_start(row.try_get("active_hours_start")?)
+        .active_hours_end(row.try_get("active_hours_end")?)
         .num_sections(row.try_get("num_sections")?)
         .section_time_limit(row.try_get("section_time_limit")?)
         .refreshes_per_hour(row.try_get("refreshes_per_hour")?)
@@ -293,13 +636,50 @@ pub async fn fetch_profiles(enabled: bool) -> Result<Vec<Profile>> {
 }
 
 pub async fn fetch_profile_sections() -> Result<Vec<ProfileSection>> {
+    select_profile_sections(db::get_pool()?).await
+}
+
+pub(super) async fn select_profile_sections(pool: &SqlitePool) -> Result<Vec<ProfileSection>> {
     let sections = sqlx::query_as::<_, ProfileSection>("select * from profile_section")
-        .fetch_all(db::get_pool()?)
+        .fetch_all(pool)
         .await?;
 
     Ok(sections)
 }
 
+/// Returns the computed fields of every profile in `v_profile`, along with the inputs needed to
+/// derive them and the timestamp the view was evaluated at
+pub async fn fetch_profile_view_records() -> Result<Vec<ProfileViewRecord>> {
+    select_profile_view_records(db::get_pool()?).await
+}
+
+async fn select_profile_view_records(pool: &SqlitePool) -> Result<Vec<ProfileViewRecord>> {
+    let records = sqlx::query_as::<_, ProfileViewRecord>(
+        r#"
+        select v.profile_id,
+               v.profile_title,
+               v.enabled,
+               p.time_limit                                        raw_time_limit,
+               v.refresh_interval,
+               cast(strftime('%s', current_timestamp) as integer) evaluated_at,
+               v.num_sections,
+               v.has_max_sections,
+               v.section_time_limit,
+               v.refreshes_per_hour,
+               v.current_refresh,
+               v.next_refresh_at,
+               v.eligible_for_refresh
+        from v_profile v
+                 join profile p on p.profile_id = v.profile_id
+        order by v.profile_title
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records)
+}
+
 pub async fn fetch_profile_sections_for_profile(profile_id: i32) -> Result<Vec<ProfileSection>> {
     let sections =
         sqlx::query_as::<_, ProfileSection>("select * from profile_section where profile_id = ?")
@@ -312,6 +692,232 @@ pub async fn fetch_profile_sections_for_profile(profile_id: i32) -> Result<Vec<P
     Ok(sections)
 }
 
+/// Returns the track guids used by the latest `cycles` refreshes of a profile
+pub async fn fetch_recent_track_guids(profile_id: i32, cycles: u32) -> Result<HashSet<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        select track_guids
+        from profile_refresh_history
+        where profile_id = ?
+        order by profile_refresh_history_id desc
+        limit ?
+    "#,
+    )
+    .bind(profile_id)
+    .bind(cycles)
+    .fetch_all(db::get_pool()?)
+    .await?;
+
+    let mut guids = HashSet::new();
+    for row in rows {
+        guids.extend(serde_json::from_str::<Vec<String>>(&row.0)?);
+    }
+
+    Ok(guids)
+}
+
+/// Returns the track ids of a playlist snapshot, where `1` is the most recent snapshot
+pub async fn fetch_playlist_snapshot(
+    profile_id: i32,
+    snapshot: u32,
+) -> Result<Option<Vec<String>>> {
+    select_playlist_snapshot(db::get_pool()?, profile_id, snapshot).await
+}
+
+async fn select_playlist_snapshot(
+    pool: &SqlitePool,
+    profile_id: i32,
+    snapshot: u32,
+) -> Result<Option<Vec<String>>> {
+    if snapshot == 0 {
+        return Ok(None);
+    }
+
+    let row: Option<(String,)> = sqlx::query_as(
+        r#"
+        select track_ids
+        from playlist_snapshot
+        where profile_id = ?
+        order by playlist_snapshot_id desc
+        limit 1 offset ?
+    "#,
+    )
+    .bind(profile_id)
+    .bind(snapshot - 1)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some((track_ids,)) => Ok(Some(serde_json::from_str(&track_ids)?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the disabled profiles, optionally only those disabled more than `older_than_days`
+/// days ago
+pub async fn fetch_prunable_profiles(older_than_days: Option<u32>) -> Result<Vec<Profile>> {
+    let ids = select_prunable_profile_ids(db::get_pool()?, older_than_days).await?;
+
+    let mut profiles = vec![];
+    for id in ids {
+        profiles.push(fetch_profile(id).await?);
+    }
+
+    Ok(profiles)
+}
+
+async fn select_prunable_profile_ids(
+    pool: &SqlitePool,
+    older_than_days: Option<u32>,
+) -> Result<Vec<i32>> {
+    let ids: Vec<(i32,)> = sqlx::query_as(
+        r#"
+        select profile_id
+        from profile
+        where enabled = 0
+          and (? is null or disabled_at <= datetime('now', ?))
+        order by profile_title
+    "#,
+    )
+    .bind(older_than_days)
+    .bind(older_than_days.map(|days| format!("-{days} days")))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|id| id.0).collect())
+}
+
+/// Schedules a single refresh of a profile at `refresh_at`, in seconds since the epoch
+pub async fn schedule_refresh(profile_id: i32, refresh_at: i64) -> Result<()> {
+    insert_scheduled_refresh(db::get_pool()?, profile_id, refresh_at).await
+}
+
+async fn insert_scheduled_refresh(
+    pool: &SqlitePool,
+    profile_id: i32,
+    refresh_at: i64,
+) -> Result<()> {
+    sqlx::query("insert into scheduled_refresh (profile_id, refresh_at) values (?, ?)")
+        .bind(profile_id)
+        .bind(refresh_at)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Removes every scheduled refresh due at or before `now`, in seconds since the epoch, and
+/// returns the ids of their profiles
+pub async fn take_due_scheduled_refreshes(now: i64) -> Result<Vec<i32>> {
+    delete_due_scheduled_refreshes(db::get_pool()?, now).await
+}
+
+async fn delete_due_scheduled_refreshes(pool: &SqlitePool, now: i64) -> Result<Vec<i32>> {
+    let ids: Vec<(i32,)> = sqlx::query_as(
+        r#"
+        delete
+        from scheduled_refresh
+        where refresh_at <= ?
+        returning profile_id
+        "#,
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    // A profile scheduled more than once for the same minute is only refreshed once
+    let mut ids = ids.into_iter().map(|id| id.0).collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+
+    Ok(ids)
+}
+
+/// Records the id of a playlist created for a shadow of the profile, replacing the id of the
+/// profile's earlier shadow
+pub async fn save_shadow_playlist(profile_id: i32, playlist_id: &str) -> Result<()> {
+    insert_shadow_playlist(db::get_pool()?, profile_id, playlist_id).await
+}
+
+async fn insert_shadow_playlist(
+    pool: &SqlitePool,
+    profile_id: i32,
+    playlist_id: &str,
+) -> Result<()> {
+    sqlx::query("delete from shadow_playlist where profile_id = ?")
+        .bind(profile_id)
+        .execute(pool)
+        .await?;
+
+    sqlx::query("insert into shadow_playlist (playlist_id, profile_id) values (?, ?)")
+        .bind(playlist_id)
+        .bind(profile_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the ids of every playlist created for a shadow profile
+pub async fn fetch_shadow_playlist_ids() -> Result<HashSet<String>> {
+    select_shadow_playlist_ids(db::get_pool()?).await
+}
+
+async fn select_shadow_playlist_ids(pool: &SqlitePool) -> Result<HashSet<String>> {
+    let ids: Vec<(String,)> = sqlx::query_as("select playlist_id from shadow_playlist")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(ids.into_iter().map(|id| id.0).collect())
+}
+
+/// Returns the ids of the playlists recorded for shadows of the profile
+pub async fn fetch_profile_shadow_playlist_ids(profile_id: i32) -> Result<HashSet<String>> {
+    select_profile_shadow_playlist_ids(db::get_pool()?, profile_id).await
+}
+
+async fn select_profile_shadow_playlist_ids(
+    pool: &SqlitePool,
+    profile_id: i32,
+) -> Result<HashSet<String>> {
+    let ids: Vec<(String,)> =
+        sqlx::query_as("select playlist_id from shadow_playlist where profile_id = ?")
+            .bind(profile_id)
+            .fetch_all(pool)
+            .await?;
+
+    Ok(ids.into_iter().map(|id| id.0).collect())
+}
+
+/// Forgets a shadow playlist after it is deleted from plex
+pub async fn delete_shadow_playlist(playlist_id: &str) -> Result<()> {
+    sqlx::query("delete from shadow_playlist where playlist_id = ?")
+        .bind(playlist_id)
+        .execute(db::get_pool()?)
+        .await?;
+
+    Ok(())
+}
+
+/// Requests that the refresh loop cancel the profile's refresh, if one is underway
+pub async fn request_refresh_cancellation(profile_id: i32) -> Result<()> {
+    sqlx::query("insert or replace into refresh_cancellation (profile_id) values (?)")
+        .bind(profile_id)
+        .execute(db::get_pool()?)
+        .await?;
+
+    Ok(())
+}
+
+/// Removes every requested cancellation and returns the ids of their profiles
+pub async fn take_refresh_cancellations() -> Result<Vec<i32>> {
+    let ids: Vec<(i32,)> = sqlx::query_as("delete from refresh_cancellation returning profile_id")
+        .fetch_all(db::get_pool()?)
+        .await?;
+
+    Ok(ids.into_iter().map(|id| id.0).collect())
+}
+
 pub async fn fetch_any_eligible_for_refresh() -> Result<bool> {
     let result: (i32,) = sqlx::query_as(
         r#"
@@ -376,16 +982,383 @@ pub async fn fetch_profile_by_title(title: &str) -> Result<Option<Profile>> {
     Ok(profile)
 }
 
-pub async fn fetch_profile_titles() -> Result<Vec<String>> {
-    let titles: Vec<(String,)> = sqlx::query_as(
+/// Returns the profile whose id or exact title matches `arg`
+///
+/// Fails if no profile matches, or if `arg` is the id of one profile and the title of another.
+pub async fn resolve_profile(arg: &str) -> Result<Profile> {
+    find_profile(db::get_pool()?, arg).await
+}
+
+async fn find_profile(pool: &SqlitePool, arg: &str) -> Result<Profile> {
+    let by_title: Option<(i32,)> =
+        sqlx::query_as("select profile_id from profile where profile_title = ?")
+            .bind(arg)
+            .fetch_optional(pool)
+            .await?;
+
+    let by_id: Option<(i32,)> = match arg.trim().parse::<i32>() {
+        Ok(id) => {
+            sqlx::query_as("select profile_id from profile where profile_id = ?")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?
+        }
+        Err(_) => None,
+    };
+
+    let profile_id = match (by_id, by_title) {
+        (Some((id,)), Some((title_id,))) if id != title_id => {
+            return Err(anyhow!(
+                "`{arg}` is ambiguous: it is the id of profile {id} and the title of profile {title_id}"
+            ))
+        }
+        (Some((id,)), _) | (None, Some((id,))) => id,
+        (None, None) => return Err(anyhow!("No profile found with the id or title `{arg}`")),
+    };
+
+    select_profile(pool, profile_id).await
+}
+
+/// Returns the title of every profile, most recently used first unless `alphabetical` is set
+///
+/// Profiles that were never used are listed after used profiles, ordered by title.
+pub async fn fetch_profile_titles(alphabetical: bool) -> Result<Vec<String>> {
+    select_profile_titles(db::get_pool()?, alphabetical).await
+}
+
+async fn select_profile_titles(pool: &SqlitePool, alphabetical: bool) -> Result<Vec<String>> {
+    let sql = if alphabetical {
+        "select profile_title from profile order by profile_title"
+    } else {
         r#"
-        select profile_title from v_profile order by profile_title
-    "#,
-    )
-    .fetch_all(db::get_pool()?)
-    .await?;
+        select profile_title
+        from profile
+        order by last_used_at is null, last_used_at desc, profile_title
+        "#
+    };
+
+    let titles: Vec<(String,)> = sqlx::query_as(sql).fetch_all(pool).await?;
 
     let titles = titles.into_iter().map(|x| x.0).collect::<Vec<_>>();
 
     Ok(titles)
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    async fn insert_profile(pool: &SqlitePool) -> i32 {
+        let row = sqlx::query(
+            "insert into profile (playlist_id, profile_title, profile_source) values ('123456', 'Test', 'Library') returning profile_id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        row.get(0)
+    }
+
+    fn build_track_ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_playlist_snapshots_pruned() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+
+        for i in 1..=4 {
+            insert_playlist_snapshot(&pool, profile_id, &build_track_ids(&[&i.to_string()]), 2)
+                .await
+                .unwrap();
+        }
+
+        let (count,): (i32,) = sqlx::query_as("select count(*) from playlist_snapshot")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(2, count);
+        assert_eq!(
+            Some(build_track_ids(&["4"])),
+            select_playlist_snapshot(&pool, profile_id, 1)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            Some(build_track_ids(&["3"])),
+            select_playlist_snapshot(&pool, profile_id, 2)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            None,
+            select_playlist_snapshot(&pool, profile_id, 3)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_new_profile() {
+        let pool = db::build_test_pool().await;
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("New Profile").unwrap())
+            .build()
+            .unwrap();
+        let section = crate::profiles::profile_section::ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(0)
+            .randomize_tracks(false)
+            .section_type(SectionType::Unplayed)
+            .sorting("random".to_string())
+            .build()
+            .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        let profile_id = insert_new_profile(&mut conn, "123456", &profile, &[section])
+            .await
+            .unwrap();
+        drop(conn);
+
+        let (title,): (String,) =
+            sqlx::query_as("select profile_title from profile where profile_id = ?")
+                .bind(profile_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!("New Profile", title);
+        let sections = select_profile_sections(&pool).await.unwrap();
+        assert_eq!(1, sections.len());
+        assert_eq!(profile_id, sections[0].get_profile_id());
+    }
+
+    #[tokio::test]
+    async fn test_profile_view_records_match_derived_fields() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+        for section_type in ["Unplayed", "LeastPlayed", "Oldest"] {
+            sqlx::query(
+                "insert into profile_section (profile_id, section_type, sorting) values (?, ?, 'random')",
+            )
+            .bind(profile_id)
+            .bind(section_type)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "insert into profile (playlist_id, profile_title, profile_source, refresh_interval, time_limit) values ('654321', 'Unlimited', 'Library', 10, 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        insert_disabled_profile(&pool, "Disabled", 1).await;
+
+        let records = select_profile_view_records(&pool).await.unwrap();
+        let sections = select_profile_sections(&pool).await.unwrap();
+
+        assert_eq!(3, records.len());
+        assert_eq!(
+            Vec::<String>::new(),
+            crate::profiles::derived::find_mismatches(&records, &sections)
+                .iter()
+                .map(|mismatch| mismatch.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_profile_by_id_or_title() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+
+        let by_id = find_profile(&pool, &profile_id.to_string()).await.unwrap();
+        let by_title = find_profile(&pool, "Test").await.unwrap();
+
+        assert_eq!(profile_id, by_id.get_profile_id());
+        assert_eq!(profile_id, by_title.get_profile_id());
+        assert_eq!(
+            "No profile found with the id or title `Missing`",
+            find_profile(&pool, "Missing")
+                .await
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_profile_ambiguous() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+        sqlx::query(
+            "insert into profile (playlist_id, profile_title, profile_source) values ('123456', ?, 'Library')",
+        )
+        .bind(profile_id.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let err = find_profile(&pool, &profile_id.to_string())
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("is ambiguous"));
+    }
+
+    #[tokio::test]
+    async fn test_shadow_playlists_recorded() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+        insert_shadow_playlist(&pool, profile_id, "200001")
+            .await
+            .unwrap();
+        // A new shadow of the same profile replaces the earlier one
+        insert_shadow_playlist(&pool, profile_id, "200002")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            HashSet::from(["200002".to_string()]),
+            select_shadow_playlist_ids(&pool).await.unwrap()
+        );
+        assert_eq!(
+            HashSet::from(["200002".to_string()]),
+            select_profile_shadow_playlist_ids(&pool, profile_id)
+                .await
+                .unwrap()
+        );
+        assert!(select_profile_shadow_playlist_ids(&pool, profile_id + 1)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_track_ids_hash_cleared() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+        let select_hash = || async {
+            let hash: (Option<String>,) =
+                sqlx::query_as("select track_ids_hash from profile where profile_id = ?")
+                    .bind(profile_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            hash.0
+        };
+        let set_hash = || async {
+            sqlx::query("update profile set track_ids_hash = 'abc' where profile_id = ?")
+                .bind(profile_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        };
+
+        set_hash().await;
+        reset_track_ids_hash(&pool, profile_id).await.unwrap();
+        assert_eq!(None, select_hash().await);
+
+        set_hash().await;
+        set_playlist_id(&pool, profile_id, "654321").await.unwrap();
+        assert_eq!(None, select_hash().await);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_refresh_fires_once() {
+        let pool = db::build_test_pool().await;
+        let profile_id = insert_profile(&pool).await;
+        insert_scheduled_refresh(&pool, profile_id, 1_700_000_000)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Vec::<i32>::new(),
+            delete_due_scheduled_refreshes(&pool, 1_699_999_999)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![profile_id],
+            delete_due_scheduled_refreshes(&pool, 1_700_000_000)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            Vec::<i32>::new(),
+            delete_due_scheduled_refreshes(&pool, 1_700_000_060)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_titles_ordered_by_recency() {
+        let pool = db::build_test_pool().await;
+        for (title, last_used_at) in [
+            ("Alpha", None),
+            ("Bravo", Some("2024-01-01 08:00:00")),
+            ("Charlie", None),
+            ("Delta", Some("2024-03-01 08:00:00")),
+        ] {
+            sqlx::query(
+                "insert into profile (playlist_id, profile_title, profile_source, last_used_at) values ('123456', ?, 'Library', ?)",
+            )
+            .bind(title)
+            .bind(last_used_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            vec!["Delta", "Bravo", "Alpha", "Charlie"],
+            select_profile_titles(&pool, false).await.unwrap()
+        );
+        assert_eq!(
+            vec!["Alpha", "Bravo", "Charlie", "Delta"],
+            select_profile_titles(&pool, true).await.unwrap()
+        );
+    }
+
+    async fn insert_disabled_profile(pool: &SqlitePool, title: &str, disabled_days_ago: u32) {
+        sqlx::query(
+            "insert into profile (playlist_id, profile_title, profile_source, enabled, disabled_at) values ('123456', ?, 'Library', 0, datetime('now', ?))",
+        )
+        .bind(title)
+        .bind(format!("-{disabled_days_ago} days"))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prunable_profiles_older_than() {
+        let pool = db::build_test_pool().await;
+        let enabled_id = insert_profile(&pool).await;
+        insert_disabled_profile(&pool, "Recent", 5).await;
+        insert_disabled_profile(&pool, "Old", 60).await;
+
+        let all = select_prunable_profile_ids(&pool, None).await.unwrap();
+        assert_eq!(2, all.len());
+        assert!(!all.contains(&enabled_id));
+
+        let old = select_prunable_profile_ids(&pool, Some(30)).await.unwrap();
+        let (title,): (String,) =
+            sqlx::query_as("select profile_title from profile where profile_id = ?")
+                .bind(old[0])
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(1, old.len());
+        assert_eq!("Old", title);
+
+        let none = select_prunable_profile_ids(&pool, Some(90)).await.unwrap();
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! Exports generated playlists to other file formats
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::plex::models::tracks::Track;
+use crate::plex::PlexClient;
+
+/// The file formats a playlist can be exported to
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values, suitable for spreadsheets
+    #[default]
+    Csv,
+}
+
+/// A single row in a CSV export
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    title: &'a str,
+    artist: &'a str,
+    album: &'a str,
+    plays: i32,
+    last_played: String,
+    rating: i32,
+    duration_seconds: i64,
+    guid: &'a str,
+    /// The artwork urls are only included when requested. Tracks without artwork have an empty
+    /// url so that every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    art_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    album_art_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artist_art_url: Option<String>,
+}
+
+impl<'a> CsvRow<'a> {
+    /// Builds a row for a track, including its artwork urls if a plex client is provided
+    fn new(track: &'a Track, art_client: Option<&PlexClient>) -> Result<Self> {
+        let build_art_url = |thumb: Option<&str>| -> Result<Option<String>> {
+            let Some(client) = art_client else {
+                return Ok(None);
+            };
+
+            Ok(Some(match thumb {
+                Some(thumb) => client.build_art_url(thumb)?.to_string(),
+                None => String::new(),
+            }))
+        };
+
+        Ok(Self {
+            title: track.get_track_title(),
+            artist: track.get_track_artist(),
+            album: track.get_track_album(),
+            plays: track.get_plays(),
+            last_played: track.get_last_played_str(),
+            rating: track.get_rating(),
+            duration_seconds: track.get_track_duration() / 1000,
+            guid: track.get_guid(),
+            art_url: build_art_url(track.get_thumb())?,
+            album_art_url: build_art_url(track.get_parent_thumb())?,
+            artist_art_url: build_art_url(track.get_grandparent_thumb())?,
+        })
+    }
+}
+
+/// Exports a list of tracks to the given format
+///
+/// If `art_client` is provided, the absolute urls of each track's artwork and its album and
+/// artist artwork are included. The urls contain the plex token.
+pub fn export_tracks(
+    tracks: &[Track],
+    format: ExportFormat,
+    art_client: Option<&PlexClient>,
+) -> Result<String> {
+    match format {
+        ExportFormat::Csv => tracks_to_csv(tracks, art_client),
+    }
+}
+
+/// Serializes a list of tracks to CSV, including a header row
+fn tracks_to_csv(tracks: &[Track], art_client: Option<&PlexClient>) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for track in tracks {
+        writer.serialize(CsvRow::new(track, art_client)?)?;
+    }
+
+    let csv = String::from_utf8(writer.into_inner()?)?;
+    Ok(csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::mock_server::MockServer;
+    use crate::plex::models::tracks::TrackBuilder;
+
+    use super::*;
+
+    fn build_track(title: &str, artist: &str, plays: i32) -> Track {
+        build_track_with(title, artist, plays).build()
+    }
+
+    fn build_track_with(title: &str, artist: &str, plays: i32) -> TrackBuilder {
+        TrackBuilder::default()
+            .title(title)
+            .artist(artist)
+            .album("Moving Pictures")
+            .plays(plays)
+            .rating(8.0)
+            .duration(276_000)
+            .guid("plex://track/608bcb5f0f0b9c002cf4cd16")
+    }
+
+    #[test]
+    fn test_tracks_to_csv() {
+        let tracks = vec![
+            build_track("Tom Sawyer", "Rush", 3),
+            build_track("Red Barchetta, \"Live\"", "Rush", 0),
+        ];
+
+        let expected = "\
+title,artist,album,plays,last_played,rating,duration_seconds,guid
+Tom Sawyer,Rush,Moving Pictures,3,1970-01-01,4,276,plex://track/608bcb5f0f0b9c002cf4cd16
+\"Red Barchetta, \"\"Live\"\"\",Rush,Moving Pictures,0,1970-01-01,4,276,plex://track/608bcb5f0f0b9c002cf4cd16
+";
+        let csv = export_tracks(&tracks, ExportFormat::Csv, None).unwrap();
+        assert_eq!(expected, csv);
+    }
+
+    #[tokio::test]
+    async fn test_tracks_to_csv_with_art() {
+        let server = MockServer::start(|_| String::new()).await;
+        let client = server.client();
+        let track = build_track_with("Tom Sawyer", "Rush", 3)
+            .thumb("/library/metadata/1001/thumb/1700000000")
+            .parent_thumb("/library/metadata/1000/thumb/1700000000")
+            .build();
+
+        let csv = export_tracks(&[track], ExportFormat::Csv, Some(&client)).unwrap();
+
+        let url = server.get_url();
+        let expected = format!(
+            "\
+title,artist,album,plays,last_played,rating,duration_seconds,guid,art_url,album_art_url,artist_art_url
+Tom Sawyer,Rush,Moving Pictures,3,1970-01-01,4,276,plex://track/608bcb5f0f0b9c002cf4cd16,{url}library/metadata/1001/thumb/1700000000?X-Plex-Token=token,{url}library/metadata/1000/thumb/1700000000?X-Plex-Token=token,
+"
+        );
+        assert_eq!(expected, csv);
+    }
+}
@@ -4,14 +4,26 @@
 //! The original source for this code is from: <https://github.com/seanmonstar/reqwest/issues/988#issuecomment-1475364352>
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
-use reqwest::{header, Url};
+use reqwest::{header, Response, Url};
 use serde::Deserialize;
-use simplelog::debug;
+use simplelog::{debug, info};
 
+use crate::types::plex::plex_url::PlexUrl;
 use crate::utils;
 
+/// When set, every request and its response status are logged
+static TRACE_HTTP: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables request tracing for every [`HttpClient`] created afterward
+pub fn set_trace_http(trace_http: bool) {
+    TRACE_HTTP.store(trace_http, Ordering::Relaxed);
+}
+
 /// A custom [`Client`](reqwest::Client), with a base url and headers set during creation.
 #[derive(Clone, Default, Debug)]
 pub struct HttpClient {
@@ -23,6 +35,12 @@ pub struct HttpClient {
     plex_token: String,
     /// The resulting custom client
     client: reqwest::Client,
+    /// The maximum number of requests the client may send, if any
+    max_requests: Option<u32>,
+    /// The number of requests sent, shared between clones of the client
+    request_count: Arc<AtomicU32>,
+    /// If true, each request is logged with its response status and elapsed time
+    trace_http: bool,
 }
 
 /// Shorthand for headers parameter type
@@ -44,29 +62,94 @@ impl HttpClient {
         let client = reqwest::Client::builder().build()?;
 
         Ok(Self {
-            base_url: base_url.to_owned(),
+            base_url: PlexUrl::try_new(base_url)?.into_inner(),
             plex_token: plex_token.to_owned(),
             headers,
             client,
+            max_requests: None,
+            request_count: Arc::default(),
+            trace_http: TRACE_HTTP.load(Ordering::Relaxed),
         })
     }
 
+    /// Returns a copy of the client that may send at most `max_requests` requests, counted
+    /// separately from this client
+    pub fn with_request_limit(&self, max_requests: Option<u32>) -> Self {
+        Self {
+            max_requests,
+            request_count: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Counts a request about to be sent, failing if the client's request limit is exceeded
+    fn count_request(&self) -> Result<()> {
+        let count = self.request_count.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.max_requests {
+            Some(max_requests) if count > max_requests => Err(anyhow!(
+                "Exceeded the maximum of {max_requests} requests per refresh. Check the profile for runaway pagination or raise `max_requests_per_refresh`."
+            )),
+            _ => Ok(()),
+        }
+    }
+
     /// Perform a `GET` request with the custom ['Client'](reqwest::Client)
     pub async fn get<T>(&self, path: &str, params: Params, max_results: Option<i32>) -> Result<T>
     where
         T: for<'de> Deserialize<'de> + Default,
     {
-        let url = self.build_final_url(path, params)?;
+        self.get_page(path, params, 0, max_results).await
+    }
 
-        let req = self.client.get(url).headers(self.headers.clone());
-        let req = if let Some(max_results) = max_results {
-            req.header("X-Plex-Container-Size", max_results.to_string())
-                .header("X-Plex-Container-Start", "0")
-        } else {
-            req
-        };
+    /// Perform a `GET` request for a page of results, starting at the `start` offset
+    ///
+    /// If `size` is `None`, every result after `start` is returned. The container headers are
+    /// only sent when a page is requested.
+    pub async fn get_page<T>(
+        &self,
+        path: &str,
+        params: Params,
+        start: i32,
+        size: Option<i32>,
+    ) -> Result<T>
+    where
+        T: for<'de> Deserialize<'de> + Default,
+    {
+        self.get_filtered_page(path, params, HashMap::new(), start, size)
+            .await
+    }
+
+    /// Perform a `GET` request for a page of results matching plex filters
+    ///
+    /// Unlike `params`, the values of `filters` may hold several values joined with
+    /// [`join_filter_values`], which plex matches if any value matches.
+    pub async fn get_filtered_page<T>(
+        &self,
+        path: &str,
+        params: Params,
+        filters: HashMap<String, String>,
+        start: i32,
+        size: Option<i32>,
+    ) -> Result<T>
+    where
+        T: for<'de> Deserialize<'de> + Default,
+    {
+        self.count_request()?;
+        let url = self.build_filtered_url(path, params, filters)?;
+
+        let mut req = self.client.get(url.clone()).headers(self.headers.clone());
+        if let Some(size) = size {
+            req = req.header("X-Plex-Container-Size", size.to_string());
+        }
+        if size.is_some() || start > 0 {
+            req = req.header("X-Plex-Container-Start", start.to_string());
+        }
 
-        match req.send().await {
+        let start = Instant::now();
+        let resp = req.send().await;
+        self.trace("GET", &url, &resp, start.elapsed());
+
+        match resp {
             Ok(resp) => {
                 let url = resp.url().to_owned();
                 let contents = resp.text().await?;
@@ -87,8 +170,14 @@ impl HttpClient {
 
     /// Perform a `DELETE` request with the custom ['Client'](reqwest::Client)
     pub async fn delete(&self, path: &str, params: Params) -> Result<()> {
+        self.count_request()?;
         let url = self.build_final_url(path, params)?;
-        self.client.delete(url).send().await?;
+
+        let start = Instant::now();
+        let resp = self.client.delete(url.clone()).send().await;
+        self.trace("DELETE", &url, &resp, start.elapsed());
+
+        resp?;
         Ok(())
     }
 
@@ -97,15 +186,19 @@ impl HttpClient {
     where
         T: for<'de> Deserialize<'de> + Default,
     {
+        self.count_request()?;
         let url = self.build_final_url(path, params)?;
 
-        match self
+        let start = Instant::now();
+        let resp = self
             .client
-            .post(url)
+            .post(url.clone())
             .headers(self.headers.clone())
             .send()
-            .await
-        {
+            .await;
+        self.trace("POST", &url, &resp, start.elapsed());
+
+        match resp {
             Ok(resp) => {
                 let contents = resp.text().await?;
                 if contents.is_empty() {
@@ -125,14 +218,18 @@ impl HttpClient {
     where
         T: for<'de> Deserialize<'de> + Default,
     {
+        self.count_request()?;
         let url = self.build_final_url(path, params)?;
-        match self
+        let start = Instant::now();
+        let resp = self
             .client
-            .put(url)
+            .put(url.clone())
             .headers(self.headers.clone())
             .send()
-            .await
-        {
+            .await;
+        self.trace("PUT", &url, &resp, start.elapsed());
+
+        match resp {
             Ok(resp) => {
                 let contents = resp.text().await?;
                 if contents.is_empty() {
@@ -147,21 +244,254 @@ impl HttpClient {
         }
     }
 
+    /// Logs a request's method, path, and query along with its response status and elapsed time,
+    /// if request tracing is enabled
+    fn trace(&self, method: &str, url: &Url, resp: &reqwest::Result<Response>, elapsed: Duration) {
+        if !self.trace_http {
+            return;
+        }
+
+        let status = match resp {
+            Ok(resp) => resp.status().to_string(),
+            Err(err) => format!("failed ({err})"),
+        };
+        info!(
+            "{method} {} -> {status} in {}",
+            redact_token(url),
+            humantime::format_duration(Duration::from_millis(elapsed.as_millis() as u64))
+        );
+    }
+
+    /// Builds an absolute url to a path on the plex server, authenticated with the plex token
+    pub fn build_url(&self, path: &str) -> Result<Url> {
+        self.build_final_url(path, None)
+    }
+
     /// Constructs the final URL passed to the respective request
     ///
-    /// Merges the base url, the path, and any parameters together
+    /// Merges the base url, the path, and any parameters together. Paths are always resolved
+    /// relative to the base url, whether or not they start with a slash.
     fn build_final_url(&self, path: &str, params: Params) -> Result<Url> {
-        let mut url = Url::parse(&self.base_url)?.join(path)?;
+        self.build_filtered_url(path, params, HashMap::new())
+    }
 
-        url.query_pairs_mut()
-            .append_pair("X-Plex-Token", &self.plex_token);
+    /// Constructs the final URL passed to a request with plex filters
+    ///
+    /// Keys and values are percent-encoded, except that the commas separating the values of a
+    /// filter are kept, so that plex matches any of the values.
+    fn build_filtered_url(
+        &self,
+        path: &str,
+        params: Params,
+        filters: HashMap<String, String>,
+    ) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url)?.join(path.trim_start_matches('/'))?;
 
-        if let Some(params) = params {
-            for (k, v) in params {
-                url.query_pairs_mut().append_pair(&k, &v);
-            }
+        let mut query = format!("X-Plex-Token={}", percent_encode(&self.plex_token));
+        for (k, v) in params.unwrap_or_default() {
+            query += &format!("&{}={}", percent_encode(&k), percent_encode(&v));
+        }
+        for (k, v) in filters {
+            let values = v.split(',').map(percent_encode).collect::<Vec<_>>();
+            query += &format!("&{}={}", percent_encode(&k), values.join(","));
         }
+        url.set_query(Some(&query));
 
         Ok(url)
     }
 }
+
+/// Joins the values of a multi-value filter, which plex matches if any value matches
+///
+/// Plex splits filter values on every comma, so a value containing a comma cannot be matched
+/// exactly.
+pub fn join_filter_values<S: AsRef<str>>(values: &[S]) -> String {
+    values
+        .iter()
+        .map(|value| value.as_ref())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Percent-encodes every byte except the unreserved characters of RFC 3986
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Returns the path and query of a url with the plex token redacted
+fn redact_token(url: &Url) -> String {
+    let mut redacted = url.clone();
+    let pairs = url
+        .query_pairs()
+        .map(|(key, value)| {
+            if key == "X-Plex-Token" {
+                (key.into_owned(), "REDACTED".to_string())
+            } else {
+                (key.into_owned(), value.into_owned())
+            }
+        })
+        .collect::<Vec<_>>();
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
+
+    match redacted.query() {
+        Some(query) => format!("{}?{query}", redacted.path()),
+        None => redacted.path().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, Once};
+
+    use log::{Log, Metadata, Record};
+
+    use crate::plex::mock_server::MockServer;
+
+    use super::*;
+
+    /// Captures every log message so that tests can inspect them
+    struct CaptureLogger;
+
+    static CAPTURED: Mutex<Vec<String>> = Mutex::new(vec![]);
+    static INIT_LOGGER: Once = Once::new();
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            CAPTURED.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn build_filter_query(key: &str, value: &str) -> String {
+        let client = HttpClient::new("http://127.0.0.1:32400", "token").unwrap();
+        let filters = HashMap::from([(key.to_string(), value.to_string())]);
+        let url = client
+            .build_filtered_url("library/sections/1/all", None, filters)
+            .unwrap();
+
+        url.query().unwrap().to_string()
+    }
+
+    fn build_param_query(key: &str, value: &str) -> String {
+        let client = HttpClient::new("http://127.0.0.1:32400", "token").unwrap();
+        let params = HashMap::from([(key.to_string(), value.to_string())]);
+        let url = client
+            .build_final_url("library/sections/1/all", Some(params))
+            .unwrap();
+
+        url.query().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_artist_filter_single_id() {
+        assert_eq!(
+            "X-Plex-Token=token&artist.id=1041",
+            build_filter_query("artist.id", &join_filter_values(&["1041"]))
+        );
+    }
+
+    #[test]
+    fn test_artist_filter_multiple_ids() {
+        assert_eq!(
+            "X-Plex-Token=token&artist.id=1041,2002,3003",
+            build_filter_query("artist.id", &join_filter_values(&["1041", "2002", "3003"]))
+        );
+    }
+
+    #[test]
+    fn test_multi_value_filter_values_are_encoded() {
+        assert_eq!(
+            "X-Plex-Token=token&genre%21=Rock%20%26%20Roll,R%26B",
+            build_filter_query("genre!", &join_filter_values(&["Rock & Roll", "R&B"]))
+        );
+        assert_eq!(
+            "X-Plex-Token=token&userRating%3E%3E=6",
+            build_filter_query("userRating>>", "6")
+        );
+    }
+
+    #[test]
+    fn test_any_filter_keeps_commas_between_values() {
+        assert_eq!(
+            "X-Plex-Token=token&mood=Happy,Sad",
+            build_filter_query("mood", &join_filter_values(&["Happy", "Sad"]))
+        );
+    }
+
+    #[test]
+    fn test_other_values_are_fully_encoded() {
+        assert_eq!(
+            "X-Plex-Token=token&title=AC%5CDC%2C%20Live",
+            build_param_query("title", "AC\\DC, Live")
+        );
+    }
+
+    #[test]
+    fn test_final_url_ignores_trailing_slash() {
+        for (base_url, expected) in [
+            (
+                "http://127.0.0.1:32400",
+                "http://127.0.0.1:32400/library/sections?X-Plex-Token=token",
+            ),
+            (
+                "http://127.0.0.1:32400/",
+                "http://127.0.0.1:32400/library/sections?X-Plex-Token=token",
+            ),
+            (
+                "http://127.0.0.1/plex",
+                "http://127.0.0.1/plex/library/sections?X-Plex-Token=token",
+            ),
+            (
+                "http://127.0.0.1/plex/",
+                "http://127.0.0.1/plex/library/sections?X-Plex-Token=token",
+            ),
+        ] {
+            let client = HttpClient::new(base_url, "token").unwrap();
+            for path in ["library/sections", "/library/sections"] {
+                let url = client.build_final_url(path, None).unwrap();
+                assert_eq!(expected, url.as_str(), "{base_url} joined with {path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_http_logs_get() {
+        INIT_LOGGER.call_once(|| {
+            log::set_logger(&CaptureLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Info);
+        });
+
+        let server = MockServer::start(|_| "{}".to_string()).await;
+        let mut client = HttpClient::new(server.get_url(), "secret-token").unwrap();
+        client.trace_http = true;
+
+        let params = HashMap::from([("type".to_string(), "10".to_string())]);
+        client
+            .get::<serde_json::Value>("library/sections/1/all", Some(params), None)
+            .await
+            .unwrap();
+
+        let captured = CAPTURED.lock().unwrap();
+        let line = captured
+            .iter()
+            .find(|line| line.contains("/library/sections/1/all"))
+            .unwrap();
+        assert!(line.starts_with(
+            "GET /library/sections/1/all?X-Plex-Token=REDACTED&type=10 -> 200 OK in "
+        ));
+        assert!(!line.contains("secret-token"));
+    }
+}
@@ -5,8 +5,10 @@
 pub mod cli;
 pub mod config;
 pub mod db;
+pub mod export;
 pub mod http_client;
 pub mod logger;
+pub mod output;
 pub mod plex;
 pub mod profiles;
 pub mod types;
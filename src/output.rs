@@ -0,0 +1,62 @@
+//! Formats command output for humans or for other programs
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// The formats in which a command can print its output
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON
+    Json,
+}
+
+/// Determines how JSON output is serialized
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum JsonStyle {
+    /// Indented across multiple lines, for reading
+    #[default]
+    Pretty,
+    /// A single line without whitespace, for piping to other programs
+    Compact,
+}
+
+/// Serializes a value to JSON in the given style
+pub fn to_json<T: Serialize>(value: &T, style: JsonStyle) -> Result<String> {
+    let json = match style {
+        JsonStyle::Pretty => serde_json::to_string_pretty(value)?,
+        JsonStyle::Compact => serde_json::to_string(value)?,
+    };
+
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::profiles::profile::{Profile, ProfileBuilder};
+    use crate::types::Title;
+
+    use super::*;
+
+    #[test]
+    fn test_to_json_round_trip() {
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Morning Mix").unwrap())
+            .time_limit(4)
+            .build()
+            .unwrap();
+
+        let pretty = to_json(&profile, JsonStyle::Pretty).unwrap();
+        let compact = to_json(&profile, JsonStyle::Compact).unwrap();
+
+        assert!(pretty.contains('\n'));
+        assert!(!compact.contains('\n'));
+        assert_eq!(profile, serde_json::from_str::<Profile>(&pretty).unwrap());
+        assert_eq!(profile, serde_json::from_str::<Profile>(&compact).unwrap());
+    }
+}
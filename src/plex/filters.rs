@@ -0,0 +1,25 @@
+//! Filters for plex library requests that are shared by profile sections and ad-hoc commands
+
+/// The key of a filter matching tracks added after a point in time
+const ADDED_AT_AFTER: &str = "addedAt>>";
+
+/// Builds a filter matching tracks added within the last `days` days
+///
+/// Plex joins the key and value with `=`, so the filter is sent as `addedAt>>=-{days}d`.
+pub fn added_within_days(days: u32) -> (String, String) {
+    (ADDED_AT_AFTER.to_string(), format!("-{days}d"))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_added_within_days() {
+        let (key, value) = added_within_days(30);
+
+        assert_eq!("addedAt>>=-30d", format!("{key}={value}"));
+    }
+}
@@ -0,0 +1,91 @@
+//! A minimal HTTP server used by tests in place of a plex server
+
+use std::sync::{Arc, Mutex};
+
+use reqwest::Url;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::http_client::HttpClient;
+use crate::plex::{PlexClient, PlexClientBuilder};
+use crate::types::plex::plex_token::PlexToken;
+
+pub struct MockServer {
+    url: String,
+    /// The method and path of each request received, e.g. `PUT /playlists/123456`
+    requests: Arc<Mutex<Vec<String>>>,
+    /// The full text of each request received, including headers
+    raw_requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Starts a server that answers every request with the body returned by `respond`, which
+    /// receives the request path
+    pub async fn start(respond: fn(&str) -> String) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let raw_requests = Arc::new(Mutex::new(vec![]));
+
+        let received = requests.clone();
+        let raw_received = raw_requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut buf = [0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let mut request_line = request.split_whitespace();
+                let method = request_line.next().unwrap_or_default();
+                let path = request_line.next().unwrap_or_default();
+                received.lock().unwrap().push(format!("{method} {path}"));
+                raw_received.lock().unwrap().push(request.to_string());
+
+                let body = respond(path);
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        Self {
+            url,
+            requests,
+            raw_requests,
+        }
+    }
+
+    /// Returns a [`PlexClient`] that sends its requests to this server
+    pub fn client(&self) -> PlexClient {
+        self.client_builder().build().unwrap()
+    }
+
+    /// Returns a builder for a [`PlexClient`] that sends its requests to this server, for tests
+    /// that need to set other fields of the client
+    pub fn client_builder(&self) -> PlexClientBuilder {
+        let mut builder = PlexClientBuilder::default();
+        builder
+            .client(HttpClient::new(&self.url, "token").unwrap())
+            .plex_token(PlexToken::default())
+            .plex_url(Url::parse(&self.url).unwrap());
+        builder
+    }
+
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn get_requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn get_raw_requests(&self) -> Vec<String> {
+        self.raw_requests.lock().unwrap().clone()
+    }
+
+    pub fn get_request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
@@ -1,28 +1,81 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
 use derive_builder::Builder;
 use itertools;
 use itertools::Itertools;
-use log::{error, info};
+use log::{error, info, warn};
 use reqwest::Url;
 use serde::Deserialize;
 use simplelog::debug;
+use tokio::sync::OnceCell;
 
-use crate::config::Config;
+use crate::config::{Config, SummaryMode};
 use crate::http_client::HttpClient;
 use crate::plex::models::artists::Artist;
 use crate::plex::models::collections::{Collection, SubType};
+use crate::plex::models::genres::GenreContainer;
+use crate::plex::models::hubs::{Hub, HubContainer, HubItem};
 use crate::plex::models::new_playlist::NewPlaylist;
 use crate::plex::models::playlists::Playlist;
-use crate::plex::models::sections::Section;
-use crate::plex::models::tracks::Track;
+use crate::plex::models::sections::{section_path, Section, MUSIC_SECTION_TYPE};
+use crate::plex::models::tracks::{Track, TrackPage};
 use crate::plex::models::{MediaContainerWrapper, PlexResponse, SectionResponse};
-use crate::profiles::profile::Profile;
+use crate::profiles::profile::{Profile, SHADOW_SUFFIX};
+use crate::types::plex::hub_identifier::HubIdentifier;
 use crate::types::plex::plex_id::PlexId;
 use crate::types::plex::plex_token::PlexToken;
 
+pub mod filters;
+#[cfg(test)]
+pub mod mock_server;
 pub mod models;
+pub mod schema;
+pub mod stats;
+pub mod tv;
+
+/// Marks the start of the block in a playlist summary written by `hitomi`
+static SUMMARY_BLOCK_START: &str = "<!-- hitomi -->";
+/// Marks the end of the block in a playlist summary written by `hitomi`
+static SUMMARY_BLOCK_END: &str = "<!-- /hitomi -->";
+
+/// The type of every playlist created by `hitomi`
+static PLAYLIST_TYPE: &str = "audio";
+/// The library section type whose items can be added to a playlist of [`PLAYLIST_TYPE`]
+static PLAYLIST_SECTION_TYPE: &str = MUSIC_SECTION_TYPE;
+
+/// When set, all mutating requests made by [`PlexClient`] are skipped
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables read-only mode for every [`PlexClient`] created afterward
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+/// The API paths that may be requested with [`PlexClient::fetch_raw`]
+static RAW_PATH_PREFIXES: [&str; 6] = [
+    "library/",
+    "playlists/",
+    "hubs/",
+    "identity",
+    "status/",
+    "activities",
+];
+
+/// The response from the plex `identity` endpoint
+#[derive(Default, Deserialize)]
+struct Identity {
+    #[serde(alias = "machineIdentifier")]
+    machine_identifier: String,
+    #[serde(default)]
+    version: String,
+}
+
+/// The artist ids of each collection, keyed by collection id and shared between clones of a
+/// [`PlexClient`]
+type CollectionArtistsCache = Arc<Mutex<HashMap<String, Arc<OnceCell<Vec<String>>>>>>;
 
 /// Plex API wrapper
 ///
@@ -37,13 +90,35 @@ pub struct PlexClient {
     #[builder(default)]
     machine_identifier: String,
     #[builder(default)]
+    server_version: String,
+    #[builder(default)]
     primary_section_id: u32,
+    /// Library sections tried in order when the primary section returns an error or no tracks
+    #[builder(default)]
+    fallback_section_ids: Vec<u32>,
+    #[builder(default)]
+    playlist_title_prefix: String,
+    #[builder(default)]
+    playlist_title_suffix: String,
+    #[builder(default)]
+    summary_mode: SummaryMode,
+    /// The label applied to every playlist created by `hitomi`, if any
+    #[builder(default)]
+    playlist_label: Option<String>,
+    /// The maximum number of requests sent while refreshing a single profile, if any
+    #[builder(default)]
+    max_requests_per_refresh: Option<u32>,
+    /// If true, mutating requests are logged and skipped instead of being sent to plex
+    #[builder(default)]
+    read_only: bool,
     #[builder(default)]
     playlists: Vec<Playlist>,
     #[builder(default)]
     collections: Vec<Collection>,
     #[builder(default)]
     sections: Vec<Section>,
+    #[builder(default)]
+    collection_artists_cache: CollectionArtistsCache,
 }
 
 impl PlexClient {
@@ -60,6 +135,23 @@ impl PlexClient {
             .plex_token(plex_token)
             .plex_url(plex_url)
             .primary_section_id(config.get_primary_section_id())
+            .fallback_section_ids(config.get_fallback_section_ids().to_vec())
+            .playlist_title_prefix(
+                config
+                    .get_playlist_title_prefix()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+            .playlist_title_suffix(
+                config
+                    .get_playlist_title_suffix()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+            .summary_mode(config.get_summary_mode())
+            .playlist_label(config.get_playlist_label().map(str::to_string))
+            .max_requests_per_refresh(config.get_max_requests_per_refresh())
+            .read_only(READ_ONLY.load(Ordering::Relaxed))
             .build()?;
 
         plex.fetch_machine_identifier().await?;
@@ -77,6 +169,7 @@ impl PlexClient {
             .client(client)
             .plex_token(plex_token.to_owned())
             .plex_url(plex_url.to_owned())
+            .read_only(READ_ONLY.load(Ordering::Relaxed))
             .build()?;
 
         plex.fetch_music_sections().await?;
@@ -88,7 +181,7 @@ impl PlexClient {
         let resp: PlexResponse<Vec<Collection>> = self
             .client
             .get(
-                &format!("library/sections/{}/collections", self.primary_section_id),
+                &section_path(self.primary_section_id, "collections"),
                 None,
                 None,
             )
@@ -113,6 +206,77 @@ impl PlexClient {
         self.collections.clone()
     }
 
+    /// Fetches the hubs available for the primary music section
+    ///
+    /// Returns an empty list if the server does not expose hubs
+    pub async fn fetch_hubs(&self) -> Result<Vec<Hub>> {
+        let resp: Result<MediaContainerWrapper<HubContainer>> = self
+            .client
+            .get(
+                &format!("hubs/sections/{}", self.primary_section_id),
+                None,
+                None,
+            )
+            .await;
+
+        match resp {
+            Ok(resp) => Ok(resp.media_container.hub),
+            Err(err) => {
+                warn!("Unable to fetch hubs from plex:\n{err}");
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Fetches the names of the track genres in the primary music section
+    pub async fn fetch_genres(&self) -> Result<Vec<String>> {
+        let params = HashMap::from([("type".to_string(), "10".to_string())]);
+        let resp: MediaContainerWrapper<GenreContainer> = self
+            .client
+            .get(
+                &section_path(self.primary_section_id, "genre"),
+                Some(params),
+                None,
+            )
+            .await?;
+
+        let genres = resp
+            .media_container
+            .directory
+            .iter()
+            .map(|genre| genre.get_title().to_owned())
+            .sorted()
+            .collect_vec();
+
+        Ok(genres)
+    }
+
+    /// Fetches a single hub, including all of its items
+    ///
+    /// Returns `None` if the hub could not be found on the server
+    pub async fn fetch_hub(&self, hub_identifier: &HubIdentifier) -> Result<Option<Hub>> {
+        let hub = self
+            .fetch_hubs()
+            .await?
+            .into_iter()
+            .find(|hub| hub.get_identifier() == hub_identifier);
+
+        let Some(mut hub) = hub else {
+            return Ok(None);
+        };
+
+        // The hubs list only includes a sample of items, so the full list is fetched if possible
+        if let Some(key) = hub.get_key() {
+            let resp: Result<PlexResponse<Vec<HubItem>>> = self.client.get(key, None, None).await;
+            match resp {
+                Ok(resp) => hub.set_items(resp.media_container.metadata),
+                Err(err) => debug!("Unable to fetch all items for hub `{hub_identifier}`:\n{err}"),
+            }
+        }
+
+        Ok(Some(hub))
+    }
+
     pub async fn fetch_music_sections(&mut self) -> Result<()> {
         let resp: SectionResponse = self.client.get("library/sections", None, None).await?;
 
@@ -125,6 +289,42 @@ impl PlexClient {
         Ok(())
     }
 
+    /// Returns a copy of the client for refreshing a single profile, whose requests are counted
+    /// against the configured maximum requests per refresh
+    pub fn new_refresh_cycle(&self) -> Self {
+        Self {
+            client: self
+                .client
+                .with_request_limit(self.max_requests_per_refresh),
+            ..self.clone()
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Logs a mutating request that was skipped due to read-only mode.
+    /// Returns `true` if the request should be skipped.
+    fn skip_in_read_only(&self, action: &str) -> bool {
+        if self.read_only {
+            info!("[READ-ONLY] Would have {action}");
+        }
+
+        self.read_only
+    }
+
+    /// Returns the version of the connected plex server
+    pub fn get_server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    /// Builds an absolute url to an image such as a track's `thumb`, which plex only serves to
+    /// requests that include the plex token
+    pub fn build_art_url(&self, thumb: &str) -> Result<Url> {
+        self.client.build_url(thumb)
+    }
+
     pub fn get_music_sections(&self) -> &[Section] {
         &self.sections
     }
@@ -136,6 +336,25 @@ impl PlexClient {
         Ok(())
     }
 
+    /// Applies the configured prefix and suffix to a profile title
+    ///
+    /// The result is only used for the playlist in plex. The profile title is stored without them.
+    pub fn get_playlist_title(&self, profile_title: &str) -> String {
+        format!(
+            "{}{profile_title}{}",
+            self.playlist_title_prefix, self.playlist_title_suffix
+        )
+    }
+
+    /// Returns the temporary playlists created for shadow profiles
+    pub fn get_shadow_playlists(&self) -> Vec<&Playlist> {
+        let shadow_suffix = format!("{SHADOW_SUFFIX}{}", self.playlist_title_suffix);
+        self.playlists
+            .iter()
+            .filter(|playlist| playlist.get_title().ends_with(&shadow_suffix))
+            .collect()
+    }
+
     pub fn get_playlists(&self) -> &[Playlist] {
         &self.playlists
     }
@@ -148,38 +367,120 @@ impl PlexClient {
     }
 
     pub async fn fetch_playlist_items(&self, playlist_id: &PlexId) -> Result<Vec<Track>> {
+        Ok(self.fetch_playlist_items_page(playlist_id).await?.tracks)
+    }
+
+    /// Fetches the items of a playlist along with the number of items available
+    pub async fn fetch_playlist_items_page(&self, playlist_id: &PlexId) -> Result<TrackPage> {
         let resp: PlexResponse<Vec<Track>> = self
             .client
             .get(&format!("playlists/{playlist_id}/items"), None, None)
             .await?;
-        Ok(resp.media_container.metadata)
+        Ok(TrackPage::from(resp))
     }
 
+    /// Fetches tracks from the library
+    ///
+    /// If `max_results` is `None`, no container size is sent and plex returns every matching
+    /// track. Otherwise, at most `max_results` tracks are returned.
     pub async fn fetch_music(
         &self,
         filters: HashMap<String, String>,
         sort: Vec<&str>,
         max_results: Option<i32>,
     ) -> Result<Vec<Track>> {
+        Ok(self
+            .fetch_music_page(filters, sort, 0, max_results)
+            .await?
+            .tracks)
+    }
+
+    /// Fetches a page of tracks from the library along with the number of tracks available
+    ///
+    /// The page begins `start` tracks into the results and holds at most `size` tracks, so a
+    /// large fetch can be resumed from the offset after the last page. If `size` is `None`, every
+    /// track after `start` is returned.
+    ///
+    /// The first page is fetched from the primary library section, or from the first fallback
+    /// section with tracks if the primary returns an error or no tracks. Offsets differ between
+    /// sections, so later pages are only fetched from the primary section. Use
+    /// [`fetch_section_music_page`](Self::fetch_section_music_page) with the first page's
+    /// `section_id` to resume a fetch from a fallback section.
+    pub async fn fetch_music_page(
+        &self,
+        filters: HashMap<String, String>,
+        sort: Vec<&str>,
+        start: i32,
+        size: Option<i32>,
+    ) -> Result<TrackPage> {
+        let primary = self
+            .fetch_section_music_page(
+                self.primary_section_id,
+                filters.clone(),
+                sort.clone(),
+                start,
+                size,
+            )
+            .await;
+        match &primary {
+            Ok(page) if !page.tracks.is_empty() => return primary,
+            Err(err) => error!("An error occurred while attempting to fetch tracks from library section `{}`:\n{err}", self.primary_section_id),
+            _ => {}
+        }
+        if start > 0 {
+            return primary;
+        }
+
+        for section_id in &self.fallback_section_ids {
+            info!("Trying fallback library section `{section_id}`...");
+            match self
+                .fetch_section_music_page(*section_id, filters.clone(), sort.clone(), start, size)
+                .await
+            {
+                Ok(page) if !page.tracks.is_empty() => {
+                    info!("Fetched tracks from fallback library section `{section_id}`");
+                    return Ok(page);
+                }
+                Ok(_) => {}
+                Err(err) => error!("An error occurred while attempting to fetch tracks from library section `{section_id}`:\n{err}"),
+            }
+        }
+
+        primary
+    }
+
+    /// Fetches a page of tracks from a single library section, without falling back to any other
+    /// section
+    pub async fn fetch_section_music_page(
+        &self,
+        section_id: u32,
+        filters: HashMap<String, String>,
+        sort: Vec<&str>,
+        start: i32,
+        size: Option<i32>,
+    ) -> Result<TrackPage> {
         let sort = &sort.join(",");
 
         let mut params = HashMap::new();
         params.insert("type".to_string(), "10".to_string());
         params.insert("sort".to_string(), sort.to_string());
-        params.extend(filters);
 
-        let resp: Result<PlexResponse<Vec<Track>>> = self
+        let resp: PlexResponse<Vec<Track>> = self
             .client
-            .get("library/sections/5/all", Some(params), max_results)
-            .await;
+            .get_filtered_page(
+                &section_path(section_id, "all"),
+                Some(params),
+                filters,
+                start,
+                size,
+            )
+            .await?;
 
-        match resp {
-            Ok(resp) => Ok(resp.media_container.metadata),
-            Err(err) => {
-                error!("An error occurred while attempting to fetch tracks:\n{err}");
-                Err(err)
-            }
-        }
+        Ok(TrackPage {
+            start: start.max(0) as usize,
+            section_id,
+            ..TrackPage::from(resp)
+        })
     }
 
     pub async fn update_playlist(
@@ -205,8 +506,63 @@ impl PlexClient {
         Ok(())
     }
 
+    /// Fetches a single playlist directly from plex
+    pub async fn fetch_playlist(&self, playlist_id: &PlexId) -> Result<Playlist> {
+        let resp: PlexResponse<Vec<Playlist>> = self
+            .client
+            .get(&format!("playlists/{playlist_id}"), None, None)
+            .await?;
+
+        resp.media_container
+            .metadata
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Playlist `{playlist_id}` not found"))
+    }
+
+    /// Updates the summary of a playlist according to the configured [`SummaryMode`]
     pub async fn update_summary(&self, playlist_id: &PlexId, summary: &str) -> Result<()> {
-        let params = HashMap::from([("summary".to_string(), summary.to_string())]);
+        if self.skip_in_read_only(&format!("updated the summary of playlist `{playlist_id}`")) {
+            return Ok(());
+        }
+
+        let summary = match self.summary_mode {
+            SummaryMode::Replace => summary.to_string(),
+            SummaryMode::Preserve => {
+                let playlist = self.fetch_playlist(playlist_id).await?;
+                splice_summary_block(playlist.get_summary(), summary)
+            }
+        };
+        let params = HashMap::from([("summary".to_string(), summary)]);
+
+        let _: () = self
+            .client
+            .put(&format!("playlists/{}", playlist_id), Some(params))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Renames a playlist, applying the configured prefix and suffix to the profile title
+    ///
+    /// Nothing is sent if the playlist already has that title in plex.
+    pub async fn rename_playlist(&self, playlist_id: &PlexId, profile_title: &str) -> Result<()> {
+        let playlist_title = self.get_playlist_title(profile_title);
+        if self
+            .playlists
+            .iter()
+            .any(|p| p.get_id() == playlist_id.as_str() && p.get_title() == playlist_title)
+        {
+            return Ok(());
+        }
+
+        if self.skip_in_read_only(&format!(
+            "renamed playlist `{playlist_id}` to `{playlist_title}`"
+        )) {
+            return Ok(());
+        }
+
+        let params = HashMap::from([("title".to_string(), playlist_title)]);
 
         let _: () = self
             .client
@@ -216,25 +572,92 @@ impl PlexClient {
         Ok(())
     }
 
+    /// Creates a new playlist for a profile and returns its id.
+    ///
+    /// In read-only mode, no playlist is created and a placeholder id is returned.
     pub async fn create_playlist(&self, profile: &Profile) -> Result<String> {
+        self.validate_source_section().await?;
+
+        if self.skip_in_read_only(&format!(
+            "created playlist `{}`",
+            self.get_playlist_title(profile.get_title())
+        )) {
+            return Ok(PlexId::default().to_string());
+        }
+
         let params = HashMap::from([
             (
                 "uri".to_string(),
                 format!("{}/library/metadata", self.uri_root(),),
             ),
-            ("title".to_string(), profile.get_title().to_string()),
+            (
+                "title".to_string(),
+                self.get_playlist_title(profile.get_title()),
+            ),
             // ("summary".to_string(), urlencoding::encode(profile.get_summary()).to_string()),
             ("smart".to_string(), "0".to_string()),
-            ("type".to_string(), "audio".to_string()),
+            ("type".to_string(), PLAYLIST_TYPE.to_string()),
         ]);
 
         let playlist: PlexResponse<Vec<NewPlaylist>> =
             self.client.post("playlists", Some(params)).await?;
         let playlist = playlist.media_container.metadata.first().unwrap();
 
+        // The playlist already exists, so a missing label should not orphan it
+        if let Some(label) = &self.playlist_label {
+            if let Err(err) = self.add_label(&playlist.rating_key, label).await {
+                warn!(
+                    "Unable to add label `{label}` to playlist `{}`: {err}",
+                    playlist.rating_key
+                );
+            }
+        }
+
         Ok(playlist.rating_key.to_string())
     }
 
+    /// Adds a label to a playlist
+    pub async fn add_label(&self, rating_key: &PlexId, label: &str) -> Result<()> {
+        if self.skip_in_read_only(&format!("added label `{label}` to playlist `{rating_key}`")) {
+            return Ok(());
+        }
+
+        let params = HashMap::from([
+            ("label[0].tag.tag".to_string(), label.to_string()),
+            ("label.locked".to_string(), "1".to_string()),
+        ]);
+
+        let _: () = self
+            .client
+            .put(&format!("playlists/{}", rating_key), Some(params))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Checks that the primary section can supply items for a playlist of [`PLAYLIST_TYPE`]
+    async fn validate_source_section(&self) -> Result<()> {
+        let resp: SectionResponse = self.client.get("library/sections", None, None).await?;
+        let section_id = self.primary_section_id;
+
+        let section = resp
+            .media_container
+            .directory
+            .into_iter()
+            .find(|section| section.get_section_id().ok() == Some(section_id))
+            .ok_or_else(|| anyhow!("Library section `{section_id}` was not found in plex"))?;
+
+        if section.get_section_type() != PLAYLIST_SECTION_TYPE {
+            return Err(anyhow!(
+                "Library section `{}` ({section_id}) is a `{}` library. Playlists can only be created from music libraries.",
+                section.get_title(),
+                section.get_section_type()
+            ));
+        }
+
+        Ok(())
+    }
+
     pub async fn add_items_to_playlist(
         &self,
         playlist_id: &PlexId,
@@ -244,6 +667,13 @@ impl PlexClient {
             return Err(anyhow!("There are no items to add to the playlist"));
         }
 
+        if self.skip_in_read_only(&format!(
+            "added {} items to playlist `{playlist_id}`",
+            items.len()
+        )) {
+            return Ok(());
+        }
+
         for chunk in items.chunks(200) {
             let params = HashMap::from([(
                 "uri".to_string(),
@@ -259,6 +689,56 @@ impl PlexClient {
         Ok(())
     }
 
+    /// Fetches the total number of tracks on an album
+    pub async fn fetch_album_track_count(&self, album_id: &str) -> Result<u32> {
+        #[derive(Clone, Default, Deserialize)]
+        struct Album {
+            #[serde(alias = "leafCount")]
+            leaf_count: u32,
+        }
+
+        let resp: PlexResponse<Vec<Album>> = self
+            .client
+            .get(&format!("library/metadata/{album_id}"), None, None)
+            .await?;
+
+        let count = resp
+            .media_container
+            .metadata
+            .first()
+            .map(|album| album.leaf_count)
+            .unwrap_or_default();
+
+        Ok(count)
+    }
+
+    /// Fetches the ids of every artist in a collection
+    ///
+    /// Results are cached until [`PlexClient::clear_collection_artists_cache`] is called, so
+    /// profiles sharing a collection only fetch its artists once per refresh cycle.
+    pub async fn fetch_collection_artists(&self, collection_id: &str) -> Result<Vec<String>> {
+        let cell = self
+            .collection_artists_cache
+            .lock()
+            .unwrap()
+            .entry(collection_id.to_string())
+            .or_default()
+            .clone();
+
+        let artists = cell
+            .get_or_try_init(|| async {
+                let collection = self.fetch_collection(collection_id).await?;
+                self.fetch_artists_from_collection(&collection).await
+            })
+            .await?;
+
+        Ok(artists.clone())
+    }
+
+    pub fn clear_collection_artists_cache(&self) {
+        self.collection_artists_cache.lock().unwrap().clear();
+    }
+
     pub async fn fetch_artists_from_collection(
         &self,
         collection: &Collection,
@@ -305,13 +785,50 @@ impl PlexClient {
         Ok(artists)
     }
 
+    /// Fetches every track in a library section, or the primary music section if not provided
+    pub async fn fetch_all_tracks(&self, section_id: Option<u32>) -> Result<Vec<Track>> {
+        let section_id = section_id.unwrap_or(self.primary_section_id);
+        let params = HashMap::from([("type".to_string(), "10".to_string())]);
+
+        let resp: PlexResponse<Vec<Track>> = self
+            .client
+            .get(&section_path(section_id, "all"), Some(params), None)
+            .await?;
+
+        Ok(resp.media_container.metadata)
+    }
+
+    /// Fetches the tracks added to a library section within the last `days` days, newest first
+    ///
+    /// Uses the primary music section if `section_id` is not provided
+    pub async fn fetch_recently_added_tracks(
+        &self,
+        days: u32,
+        section_id: Option<u32>,
+    ) -> Result<Vec<Track>> {
+        let section_id = section_id.unwrap_or(self.primary_section_id);
+        let mut params = HashMap::from([
+            ("type".to_string(), "10".to_string()),
+            ("sort".to_string(), "addedAt:desc".to_string()),
+        ]);
+        let (key, value) = filters::added_within_days(days);
+        params.insert(key, value);
+
+        let resp: PlexResponse<Vec<Track>> = self
+            .client
+            .get(&section_path(section_id, "all"), Some(params), None)
+            .await?;
+
+        Ok(resp.media_container.metadata)
+    }
+
     pub async fn search_for_artist(&self, artist: &str) -> Result<Vec<Artist>> {
         let params = HashMap::from([("title".to_string(), artist.to_string())]);
 
         let resp: PlexResponse<Vec<Artist>> = self
             .client
             .get(
-                &format!("/library/sections/{}/all", self.primary_section_id),
+                &section_path(self.primary_section_id, "all"),
                 Some(params),
                 Some(10),
             )
@@ -321,27 +838,70 @@ impl PlexClient {
     }
 
     pub async fn clear_playlist(&self, playlist_id: &PlexId) -> Result<()> {
+        if self.skip_in_read_only(&format!("cleared playlist `{playlist_id}`")) {
+            return Ok(());
+        }
+
         self.client
             .delete(&format!("playlists/{playlist_id}/items"), None)
             .await?;
         Ok(())
     }
 
+    pub async fn delete_playlist(&self, playlist_id: &PlexId) -> Result<()> {
+        if self.skip_in_read_only(&format!("deleted playlist `{playlist_id}`")) {
+            return Ok(());
+        }
+
+        self.client
+            .delete(&format!("playlists/{playlist_id}"), None)
+            .await?;
+        Ok(())
+    }
+
     async fn fetch_machine_identifier(&mut self) -> Result<()> {
         debug!("Fetching machine identifier...");
 
-        #[derive(Default, Deserialize)]
-        struct Identity {
-            #[serde(alias = "machineIdentifier")]
-            machine_identifier: String,
-        }
-
         let resp: MediaContainerWrapper<Identity> = self.client.get("identity", None, None).await?;
         self.machine_identifier = resp.media_container.machine_identifier;
+        self.server_version = resp.media_container.version;
 
         Ok(())
     }
 
+    /// Fetches a page of tracks from a music section as raw JSON, without deserializing them
+    /// into [`Track`]
+    pub async fn fetch_raw_tracks(
+        &self,
+        section_id: Option<u32>,
+        max_results: i32,
+    ) -> Result<Vec<serde_json::Value>> {
+        let section_id = section_id.unwrap_or(self.primary_section_id);
+        let params = HashMap::from([("type".to_string(), "10".to_string())]);
+
+        let mut json: serde_json::Value = self
+            .client
+            .get(
+                &section_path(section_id, "all"),
+                Some(params),
+                Some(max_results),
+            )
+            .await?;
+
+        match json["MediaContainer"]["Metadata"].take() {
+            serde_json::Value::Array(tracks) => Ok(tracks),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Performs a `GET` request against an arbitrary plex API path and returns the raw JSON
+    ///
+    /// Only paths beginning with one of [`RAW_PATH_PREFIXES`] are allowed
+    pub async fn fetch_raw(&self, path: &str) -> Result<serde_json::Value> {
+        let path = validate_raw_path(path)?;
+        self.client.get(path, None, None).await
+    }
+
     fn uri_root(&self) -> String {
         format!(
             "server://{}/com.plexapp.plugins.library",
@@ -349,3 +909,465 @@ impl PlexClient {
         )
     }
 }
+
+/// Writes the generated summary between the `hitomi` markers, preserving any surrounding text
+///
+/// If the markers are not found, the block is placed before the existing summary
+fn splice_summary_block(current: &str, generated: &str) -> String {
+    let block = format!("{SUMMARY_BLOCK_START}\n{generated}\n{SUMMARY_BLOCK_END}");
+
+    let start = current.find(SUMMARY_BLOCK_START);
+    let end = current.find(SUMMARY_BLOCK_END);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => format!(
+            "{}{block}{}",
+            &current[..start],
+            &current[end + SUMMARY_BLOCK_END.len()..]
+        ),
+        _ if current.trim().is_empty() => block,
+        _ => format!("{block}\n\n{current}"),
+    }
+}
+
+/// Ensures a path starts with a known prefix, returning the path without any leading slashes
+///
+/// Paths with `.` or `..` segments are rejected, since they are resolved when the path is joined
+/// to the server url and could reach an endpoint outside the allowed prefixes.
+fn validate_raw_path(path: &str) -> Result<&str> {
+    let path = path.trim().trim_start_matches('/');
+
+    let has_dot_segment = path.split(['/', '\\']).any(|segment| {
+        matches!(
+            segment.to_lowercase().replace("%2e", ".").as_str(),
+            "." | ".."
+        )
+    });
+    if has_dot_segment {
+        return Err(anyhow!("Path must not contain `.` or `..` segments"));
+    }
+
+    if RAW_PATH_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix) || path == prefix.trim_end_matches('/'))
+    {
+        Ok(path)
+    } else {
+        Err(anyhow!(
+            "Path must start with one of the following: {}",
+            RAW_PATH_PREFIXES.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::mock_server::MockServer;
+    use crate::plex::models::tracks::TrackBuilder;
+    use crate::profiles::profile::ProfileBuilder;
+    use crate::types::Title;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collection_artists_are_cached() {
+        let server = MockServer::start(|path| {
+            if path.contains("/children") {
+                r#"{"MediaContainer": {"Metadata": [
+                    {"ratingKey": "2001", "key": "/library/metadata/2001/children", "title": "Artist"}
+                ]}}"#
+                    .to_string()
+            } else {
+                r#"{"MediaContainer": {"Metadata": [
+                    {"ratingKey": "1001", "title": "Collection", "subtype": "artist"}
+                ]}}"#
+                    .to_string()
+            }
+        })
+        .await;
+
+        let plex = server.client();
+
+        // Two profiles sharing a collection refresh concurrently with clones of the client
+        let (first_client, second_client) = (plex.clone(), plex.clone());
+        let (first, second) = tokio::join!(
+            first_client.fetch_collection_artists("1001"),
+            second_client.fetch_collection_artists("1001")
+        );
+        assert_eq!(vec!["2001"], first.unwrap());
+        assert_eq!(vec!["2001"], second.unwrap());
+        // One request for the collection and one for its children
+        assert_eq!(2, server.get_request_count());
+
+        plex.clear_collection_artists_cache();
+        plex.fetch_collection_artists("1001").await.unwrap();
+        assert_eq!(4, server.get_request_count());
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_adds_label() {
+        let server = MockServer::start(|path| {
+            if path.starts_with("/library/sections") {
+                r#"{"MediaContainer": {"Directory": [{"key": "1", "title": "Music", "type": "artist"}]}}"#
+                    .to_string()
+            } else if path.starts_with("/playlists?") {
+                r#"{"MediaContainer": {"Metadata": [{"ratingKey": "654321"}]}}"#.to_string()
+            } else {
+                String::new()
+            }
+        })
+        .await;
+
+        let mut plex = server.client();
+        plex.primary_section_id = 1;
+        plex.playlist_label = Some("hitomi".to_string());
+
+        let playlist_id = plex.create_playlist(&Profile::default()).await.unwrap();
+        assert_eq!("654321", playlist_id);
+
+        let requests = server.get_requests();
+        assert_eq!(3, requests.len());
+        assert!(requests[2].starts_with("PUT /playlists/654321?"));
+        assert!(requests[2].contains("label%5B0%5D.tag.tag=hitomi"));
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_non_audio_section() {
+        let server = MockServer::start(|_| {
+            r#"{"MediaContainer": {"Directory": [
+                {"key": "1", "title": "Music", "type": "artist"},
+                {"key": "2", "title": "Movies", "type": "movie"}
+            ]}}"#
+                .to_string()
+        })
+        .await;
+
+        let mut plex = server.client();
+        plex.primary_section_id = 2;
+
+        let result = plex.create_playlist(&Profile::default()).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("is a `movie` library"));
+        // The playlist is never created
+        assert_eq!(
+            vec!["GET /library/sections?X-Plex-Token=token"],
+            server.get_requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_music_uses_fallback_section() {
+        let server = MockServer::start(|path| {
+            if path.starts_with("/library/sections/2/all") {
+                let track = TrackBuilder::default().id("100001").build();
+                serde_json::json!({"MediaContainer": {"size": 1, "totalSize": 1, "Metadata": [track]}})
+                    .to_string()
+            } else {
+                "Internal Server Error".to_string()
+            }
+        })
+        .await;
+        let mut plex = server.client();
+        plex.primary_section_id = 1;
+        plex.fallback_section_ids = vec![2, 3];
+
+        let tracks = plex
+            .fetch_music(HashMap::new(), vec!["viewCount"], None)
+            .await
+            .unwrap();
+
+        assert_eq!(1, tracks.len());
+        assert_eq!("100001", tracks[0].get_id());
+        let requests = server.get_requests();
+        assert_eq!(2, requests.len());
+        assert!(requests[0].starts_with("GET /library/sections/1/all?"));
+        assert!(requests[1].starts_with("GET /library/sections/2/all?"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_music_keeps_commas_between_filter_values() {
+        let server =
+            MockServer::start(|_| r#"{"MediaContainer": {"Metadata": []}}"#.to_string()).await;
+        let plex = server.client();
+        let filters = HashMap::from([(
+            "mood".to_string(),
+            crate::http_client::join_filter_values(&["Happy", "Rock & Roll"]),
+        )]);
+
+        plex.fetch_music(filters, vec!["viewCount"], None)
+            .await
+            .unwrap();
+
+        let requests = server.get_requests();
+        assert!(requests[0].contains("&mood=Happy,Rock%20%26%20Roll"));
+        assert!(requests[0].contains("&sort=viewCount"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_music_returns_primary_error_without_fallback_tracks() {
+        let server = MockServer::start(|path| {
+            if path.starts_with("/library/sections/1/all") {
+                "Internal Server Error".to_string()
+            } else {
+                r#"{"MediaContainer": {"Metadata": []}}"#.to_string()
+            }
+        })
+        .await;
+        let mut plex = server.client();
+        plex.primary_section_id = 1;
+        plex.fallback_section_ids = vec![2];
+
+        assert!(plex
+            .fetch_music(HashMap::new(), vec!["viewCount"], None)
+            .await
+            .is_err());
+
+        // Resumed pages are never fetched from a fallback section
+        assert!(plex
+            .fetch_music_page(HashMap::new(), vec!["viewCount"], 100, Some(50))
+            .await
+            .is_err());
+        let requests = server.get_requests();
+        assert_eq!(3, requests.len());
+        assert!(requests[0].starts_with("GET /library/sections/1/all?"));
+        assert!(requests[1].starts_with("GET /library/sections/2/all?"));
+        assert!(requests[2].starts_with("GET /library/sections/1/all?"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cycle_request_limit() {
+        let server =
+            MockServer::start(|_| r#"{"MediaContainer": {"Metadata": []}}"#.to_string()).await;
+        let mut plex = server.client();
+        plex.max_requests_per_refresh = Some(2);
+
+        let refresh = plex.new_refresh_cycle();
+        for _ in 0..2 {
+            refresh
+                .fetch_music(HashMap::new(), vec!["viewCount"], None)
+                .await
+                .unwrap();
+        }
+        let err = refresh
+            .fetch_music(HashMap::new(), vec!["viewCount"], None)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Exceeded the maximum of 2 requests per refresh"));
+        assert_eq!(2, server.get_request_count());
+
+        // Each refresh cycle starts a new count, and the original client is not limited
+        plex.new_refresh_cycle()
+            .fetch_music(HashMap::new(), vec!["viewCount"], None)
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            plex.fetch_music(HashMap::new(), vec!["viewCount"], None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(6, server.get_request_count());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_music_container_size() {
+        let server =
+            MockServer::start(|_| r#"{"MediaContainer": {"Metadata": []}}"#.to_string()).await;
+        let plex = server.client();
+
+        plex.fetch_music(HashMap::new(), vec!["viewCount"], None)
+            .await
+            .unwrap();
+        plex.fetch_music(HashMap::new(), vec!["viewCount"], Some(50))
+            .await
+            .unwrap();
+
+        let requests = server
+            .get_raw_requests()
+            .into_iter()
+            .map(|request| request.to_lowercase())
+            .collect_vec();
+        assert!(!requests[0].contains("x-plex-container-size"));
+        assert!(requests[1].contains("x-plex-container-size: 50"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_music_page_headers() {
+        let server = MockServer::start(|_| {
+            format!(
+                r#"{{"MediaContainer": {{"totalSize": 250, "Metadata": [{}]}}}}"#,
+                serde_json::to_string(&Track::default()).unwrap()
+            )
+        })
+        .await;
+        let plex = server.client();
+
+        let page = plex
+            .fetch_music_page(HashMap::new(), vec!["viewCount"], 100, Some(50))
+            .await
+            .unwrap();
+
+        let requests = server
+            .get_raw_requests()
+            .into_iter()
+            .map(|request| request.to_lowercase())
+            .collect_vec();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].contains("x-plex-container-start: 100"));
+        assert!(requests[0].contains("x-plex-container-size: 50"));
+        assert_eq!(100, page.start);
+        assert_eq!(250, page.total_size);
+        assert!(page.has_more());
+    }
+
+    #[tokio::test]
+    async fn test_read_only_makes_no_requests() {
+        // Nothing listens on this port, so any request that is sent fails
+        let build_client = |read_only: bool| {
+            PlexClientBuilder::default()
+                .client(HttpClient::new("http://127.0.0.1:9", "token").unwrap())
+                .plex_token(PlexToken::default())
+                .plex_url(Url::parse("http://127.0.0.1:9").unwrap())
+                .read_only(read_only)
+                .build()
+                .unwrap()
+        };
+
+        let plex = build_client(true);
+        let playlist_id = PlexId::default();
+
+        assert!(plex.clear_playlist(&playlist_id).await.is_ok());
+        assert!(plex
+            .add_items_to_playlist(&playlist_id, &["1234".to_string()])
+            .await
+            .is_ok());
+        assert!(plex.update_summary(&playlist_id, "summary").await.is_ok());
+
+        let plex = build_client(false);
+        assert!(plex.clear_playlist(&playlist_id).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_identity() {
+        let json = r#"{
+            "MediaContainer": {
+                "size": 0,
+                "claimed": true,
+                "machineIdentifier": "abc123",
+                "version": "1.40.2.8395-c67dce28e"
+            }
+        }"#;
+
+        let identity: MediaContainerWrapper<Identity> = serde_json::from_str(json).unwrap();
+        assert_eq!("abc123", identity.media_container.machine_identifier);
+        assert_eq!("1.40.2.8395-c67dce28e", identity.media_container.version);
+    }
+
+    #[test]
+    fn test_splice_summary_block_first_write() {
+        assert_eq!(
+            "<!-- hitomi -->\nNEXT UPDATE: 12:00\n<!-- /hitomi -->",
+            splice_summary_block("", "NEXT UPDATE: 12:00")
+        );
+        assert_eq!(
+            "<!-- hitomi -->\nNEXT UPDATE: 12:00\n<!-- /hitomi -->\n\nMy notes",
+            splice_summary_block("My notes", "NEXT UPDATE: 12:00")
+        );
+    }
+
+    #[test]
+    fn test_splice_summary_block_update() {
+        let current = "<!-- hitomi -->\nNEXT UPDATE: 12:00\n<!-- /hitomi -->";
+        assert_eq!(
+            "<!-- hitomi -->\nNEXT UPDATE: 12:05\n<!-- /hitomi -->",
+            splice_summary_block(current, "NEXT UPDATE: 12:05")
+        );
+    }
+
+    #[test]
+    fn test_splice_summary_block_preserves_surrounding_text() {
+        let current =
+            "Notes above\n<!-- hitomi -->\nNEXT UPDATE: 12:00\n<!-- /hitomi -->\nNotes below";
+        assert_eq!(
+            "Notes above\n<!-- hitomi -->\nNEXT UPDATE: 12:05\n<!-- /hitomi -->\nNotes below",
+            splice_summary_block(current, "NEXT UPDATE: 12:05")
+        );
+    }
+
+    #[test]
+    fn test_validate_raw_path() {
+        assert_eq!(
+            "library/metadata/12345",
+            validate_raw_path("/library/metadata/12345").unwrap()
+        );
+        assert_eq!(
+            "playlists/54321/items",
+            validate_raw_path("playlists/54321/items").unwrap()
+        );
+        assert!(validate_raw_path("").is_err());
+        assert!(validate_raw_path("/:/prefs").is_err());
+        assert!(validate_raw_path("https://example.com/library/").is_err());
+        assert_eq!("playlists", validate_raw_path("playlists").unwrap());
+        assert!(validate_raw_path("playlistsfoo").is_err());
+        assert!(validate_raw_path("library/../:/prefs").is_err());
+        assert!(validate_raw_path("library/%2E%2E/:/prefs").is_err());
+        assert!(validate_raw_path("library\\..\\:/prefs").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rename_playlist_only_when_title_differs() {
+        let server = MockServer::start(|_| String::new()).await;
+        let playlist: Playlist = serde_json::from_value(serde_json::json!({
+            "ratingKey": "123456",
+            "key": "/playlists/123456/items",
+            "title": "Daily Mix",
+            "summary": "",
+            "leafCount": 0,
+        }))
+        .unwrap();
+        let plex = server
+            .client_builder()
+            .playlists(vec![playlist])
+            .build()
+            .unwrap();
+        let playlist_id = PlexId::try_new("123456").unwrap();
+
+        plex.rename_playlist(&playlist_id, "Daily Mix")
+            .await
+            .unwrap();
+        assert_eq!(0, server.get_request_count());
+
+        plex.rename_playlist(&playlist_id, "Weekly Mix")
+            .await
+            .unwrap();
+        assert_eq!(1, server.get_request_count());
+    }
+
+    #[test]
+    fn test_playlist_title_affixes() {
+        let plex_url = Url::parse("http://127.0.0.1:32400").unwrap();
+        let plex_token = PlexToken::try_new("NJlYINZmB-Hdy78xubjR").unwrap();
+        let plex = PlexClientBuilder::default()
+            .client(HttpClient::new(plex_url.as_str(), plex_token.as_str()).unwrap())
+            .plex_token(plex_token)
+            .plex_url(plex_url)
+            .playlist_title_prefix("🎵 ".to_string())
+            .playlist_title_suffix(" [hitomi]".to_string())
+            .build()
+            .unwrap();
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Daily Mix").unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            "🎵 Daily Mix [hitomi]",
+            plex.get_playlist_title(profile.get_title())
+        );
+        assert_eq!("Daily Mix", profile.get_title());
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct GenreContainer {
+    #[serde(alias = "Directory", default)]
+    pub directory: Vec<Genre>,
+}
+
+/// A genre available in a library section
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Genre {
+    title: String,
+}
+
+impl Genre {
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+}
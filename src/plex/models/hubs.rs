@@ -0,0 +1,133 @@
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::types::plex::hub_identifier::HubIdentifier;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct HubContainer {
+    #[serde(alias = "Hub", default)]
+    pub hub: Vec<Hub>,
+}
+
+/// A curated list of items from plex (e.g., "Recently Played Artists")
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hub {
+    hub_identifier: HubIdentifier,
+    title: String,
+    key: Option<String>,
+    #[serde(alias = "Metadata", default)]
+    metadata: Vec<HubItem>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HubItem {
+    rating_key: String,
+    #[serde(alias = "type")]
+    item_type: String,
+    parent_rating_key: Option<String>,
+    grandparent_rating_key: Option<String>,
+}
+
+impl Hub {
+    pub fn get_identifier(&self) -> &HubIdentifier {
+        &self.hub_identifier
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn get_key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    pub fn set_items(&mut self, items: Vec<HubItem>) {
+        self.metadata = items
+    }
+
+    /// Returns the unique artist ids of every item in the hub
+    ///
+    /// Albums and tracks are resolved to the ids of their artists
+    pub fn get_artist_ids(&self) -> Vec<String> {
+        self.metadata
+            .iter()
+            .filter_map(HubItem::get_artist_id)
+            .map(|id| id.to_owned())
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+}
+
+impl HubItem {
+    pub fn get_artist_id(&self) -> Option<&str> {
+        match self.item_type.as_str() {
+            "artist" => Some(&self.rating_key),
+            "album" => self.parent_rating_key.as_deref(),
+            "track" => self.grandparent_rating_key.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::models::MediaContainerWrapper;
+
+    use super::*;
+
+    const HUB_RESPONSE: &str = r#"{
+        "MediaContainer": {
+            "size": 2,
+            "Hub": [
+                {
+                    "hubIdentifier": "music.recent.played.artists",
+                    "title": "Recently Played Artists",
+                    "key": "/hubs/sections/5/recent/played/artists",
+                    "type": "artist",
+                    "Metadata": [
+                        { "ratingKey": "1041", "type": "artist" },
+                        { "ratingKey": "2002", "type": "artist" }
+                    ]
+                },
+                {
+                    "hubIdentifier": "music.recent.played",
+                    "title": "Recently Played",
+                    "type": "mixed",
+                    "Metadata": [
+                        { "ratingKey": "5001", "type": "track", "parentRatingKey": "4001", "grandparentRatingKey": "2002" },
+                        { "ratingKey": "4002", "type": "album", "parentRatingKey": "1041" },
+                        { "ratingKey": "9001", "type": "playlist" }
+                    ]
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_hub_response() {
+        let resp: MediaContainerWrapper<HubContainer> = serde_json::from_str(HUB_RESPONSE).unwrap();
+        let hubs = resp.media_container.hub;
+
+        assert_eq!(2, hubs.len());
+        assert_eq!(
+            "music.recent.played.artists",
+            hubs[0].get_identifier().as_str()
+        );
+        assert_eq!("Recently Played Artists", hubs[0].get_title());
+        assert_eq!(vec!["1041", "2002"], hubs[0].get_artist_ids());
+        assert_eq!(vec!["1041", "2002"], hubs[1].get_artist_ids());
+        assert_eq!(None, hubs[1].get_key());
+    }
+
+    #[test]
+    fn test_empty_hub_response() {
+        let resp: MediaContainerWrapper<HubContainer> =
+            serde_json::from_str(r#"{ "MediaContainer": { "size": 0 } }"#).unwrap();
+        assert!(resp.media_container.hub.is_empty());
+    }
+}
@@ -4,7 +4,10 @@ use crate::plex::models::sections::SectionContainer;
 
 pub mod artists;
 pub mod collections;
+pub mod genres;
+pub mod hubs;
 pub mod new_playlist;
+pub mod numeric;
 pub mod playlists;
 pub mod sections;
 pub mod tracks;
@@ -22,7 +25,10 @@ pub struct MediaContainerWrapper<T> {
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MediaContainer<T> {
+    /// The number of items in this response
     pub size: Option<i32>,
+    /// The number of items available, which is only sent when a response is paginated
+    pub total_size: Option<i32>,
     #[serde(alias = "Metadata")]
     pub metadata: T,
 }
@@ -0,0 +1,85 @@
+//! Deserializers for numeric fields that plex may send as either numbers or strings
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    Number(T),
+    String(String),
+}
+
+/// Deserializes an optional number from either a number or a numeric string
+///
+/// Empty strings and `null` are treated as missing. Fields using this deserializer must also be
+/// marked `#[serde(default)]` so that absent fields are still accepted.
+pub fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    match Option::<NumberOrString<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(number)) => Ok(Some(number)),
+        Some(NumberOrString::String(str)) if str.trim().is_empty() => Ok(None),
+        Some(NumberOrString::String(str)) => str
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|err| de::Error::custom(format!("invalid number `{str}`: {err}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Numbers {
+        #[serde(default, deserialize_with = "optional_number")]
+        count: Option<i32>,
+        #[serde(default, deserialize_with = "optional_number")]
+        rating: Option<f32>,
+    }
+
+    #[test]
+    fn test_optional_number_from_number_or_string() {
+        let expected = Numbers {
+            count: Some(3),
+            rating: Some(7.5),
+        };
+
+        let numbers: Numbers = serde_json::from_str(r#"{"count": 3, "rating": 7.5}"#).unwrap();
+        assert_eq!(expected, numbers);
+
+        let numbers: Numbers =
+            serde_json::from_str(r#"{"count": "3", "rating": " 7.5 "}"#).unwrap();
+        assert_eq!(expected, numbers);
+    }
+
+    #[test]
+    fn test_optional_number_missing() {
+        let expected = Numbers {
+            count: None,
+            rating: None,
+        };
+
+        let numbers: Numbers = serde_json::from_str(r#"{}"#).unwrap();
+        assert_eq!(expected, numbers);
+
+        let numbers: Numbers = serde_json::from_str(r#"{"count": null, "rating": ""}"#).unwrap();
+        assert_eq!(expected, numbers);
+    }
+
+    #[test]
+    fn test_optional_number_invalid_string() {
+        let result = serde_json::from_str::<Numbers>(r#"{"count": "three"}"#);
+        assert!(result.is_err());
+    }
+}
@@ -1,8 +1,12 @@
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
 
 use crate::types::plex::plex_key::PlexKey;
 use crate::types::Title;
 
+/// The plex section type of music libraries
+pub static MUSIC_SECTION_TYPE: &str = "artist";
+
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename = "MediaContainer")]
 pub struct SectionContainer {
@@ -18,16 +22,62 @@ pub struct Section {
     key: PlexKey,
 }
 
+/// Builds the path of an endpoint within a library section, e.g. `library/sections/1/all`
+pub fn section_path(section_id: u32, endpoint: &str) -> String {
+    format!("library/sections/{section_id}/{endpoint}")
+}
+
 impl Section {
-    pub fn id(&self) -> &str {
-        &self.key
+    /// The id of the section, which plex sends as its key
+    pub fn get_section_id(&self) -> Result<u32> {
+        self.key.parse().map_err(|_| {
+            anyhow!(
+                "Library section `{}` has an invalid id `{}`",
+                self.title,
+                self.key.as_str()
+            )
+        })
     }
 
     pub fn get_title(&self) -> &str {
         &self.title
     }
 
+    pub fn get_section_type(&self) -> &str {
+        &self.plex_section_type
+    }
+
     pub fn is_type_music(&self) -> bool {
-        self.plex_section_type == "artist"
+        self.plex_section_type == MUSIC_SECTION_TYPE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn build_section(key: &str) -> Section {
+        serde_json::from_value(serde_json::json!({
+            "title": "Music",
+            "type": "artist",
+            "key": key,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_section_id() {
+        assert_eq!(5, build_section("5").get_section_id().unwrap());
+        assert!(build_section("/library/sections/5")
+            .get_section_id()
+            .is_err());
+    }
+
+    #[test]
+    fn test_section_path() {
+        assert_eq!("library/sections/5/all", section_path(5, "all"));
+        assert_eq!("library/sections/12/genre", section_path(12, "genre"));
     }
 }
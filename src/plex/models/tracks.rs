@@ -1,15 +1,23 @@
 use std::fmt::{Display, Formatter};
+use std::sync::LazyLock;
+use std::time::Duration;
 
 use jiff::tz::TimeZone;
 use jiff::{Timestamp, ToSpan, Zoned};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use crate::plex::models::{numeric, PlexResponse};
 use crate::types::plex::guid::Guid;
 use crate::types::plex::plex_id::PlexId;
 use crate::types::plex::plex_key::PlexKey;
 use crate::types::Title;
 use crate::utils;
 
+/// Matches a featuring credit and everything after it, e.g. ` feat. X` or ` (ft. X & Y)`
+static FEATURING_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s*[(\[]?\b(feat\.?|ft\.?|featuring)\s.*$").unwrap());
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Track {
@@ -29,15 +37,39 @@ pub struct Track {
     parent_title: Title,
     index: Option<u32>,
     parent_index: u32,
+    #[serde(default, deserialize_with = "numeric::optional_number")]
     user_rating: Option<f32>,
+    #[serde(default, deserialize_with = "numeric::optional_number")]
     view_count: Option<i32>,
+    #[serde(default, deserialize_with = "numeric::optional_number")]
+    skip_count: Option<i32>,
+    #[serde(default, deserialize_with = "numeric::optional_number")]
     last_viewed_at: Option<i64>,
     parent_year: Option<i32>,
+    /// The timestamp (in seconds) when the track was added to the library
+    added_at: Option<i64>,
     /// Duration is in milliseconds
+    #[serde(default, deserialize_with = "numeric::optional_number")]
     duration: Option<i64>,
     original_title: Option<Title>,
+    /// The content rating of the track, such as `explicit` or `clean`
+    content_rating: Option<String>,
+    /// The path to the track's artwork, relative to the plex server
+    thumb: Option<String>,
+    /// The path to the album's artwork, relative to the plex server
+    parent_thumb: Option<String>,
+    /// The path to the artist's artwork, relative to the plex server
+    grandparent_thumb: Option<String>,
     #[serde(alias = "Media")]
     pub media: Vec<Media>,
+    #[serde(alias = "Genre", default)]
+    genres: Vec<Tag>,
+}
+
+/// A tag attached to an item in plex, such as a genre
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Tag {
+    tag: String,
 }
 
 impl Track {
@@ -45,6 +77,11 @@ impl Track {
         &self.rating_key
     }
 
+    /// Returns the release year of the track's album
+    pub fn get_year(&self) -> Option<i32> {
+        self.parent_year
+    }
+
     pub fn get_guid(&self) -> &str {
         self.guid.as_str()
     }
@@ -57,6 +94,18 @@ impl Track {
         self.parent_title.as_ref()
     }
 
+    pub fn get_thumb(&self) -> Option<&str> {
+        self.thumb.as_deref()
+    }
+
+    pub fn get_parent_thumb(&self) -> Option<&str> {
+        self.parent_thumb.as_deref()
+    }
+
+    pub fn get_grandparent_thumb(&self) -> Option<&str> {
+        self.grandparent_thumb.as_deref()
+    }
+
     pub fn get_track_artist(&self) -> &str {
         match &self.original_title {
             Some(artist) => artist.as_ref(),
@@ -65,6 +114,15 @@ impl Track {
         .trim()
     }
 
+    /// Returns the artist the track is filed under, ignoring any track artist credit
+    pub fn get_artist_name(&self) -> &str {
+        self.grandparent_title.trim()
+    }
+
+    pub fn get_album_id(&self) -> &str {
+        self.parent_rating_key.as_str()
+    }
+
     pub fn get_artist_id(&self) -> &str {
         self.grandparent_rating_key.as_str()
     }
@@ -73,11 +131,29 @@ impl Track {
         self.grandparent_guid.as_str()
     }
 
+    /// Returns the track artist without any featuring credit, in lowercase
+    /// (e.g., `Artist feat. X` becomes `artist`)
+    pub fn get_primary_artist(&self) -> String {
+        FEATURING_REGEX
+            .replace(self.get_track_artist(), "")
+            .trim()
+            .to_lowercase()
+    }
+
+    /// Returns true if the track artist includes a featuring credit
+    pub fn has_featured_artists(&self) -> bool {
+        FEATURING_REGEX.is_match(self.get_track_artist())
+    }
+
     /// Duration is in milliseconds
     pub fn get_track_duration(&self) -> i64 {
         self.duration.unwrap_or(0)
     }
 
+    pub fn has_duration(&self) -> bool {
+        self.duration.is_some()
+    }
+
     /// In milliseconds
     pub fn get_last_played(&self) -> Timestamp {
         if let Some(last_viewed_at) = self.last_viewed_at {
@@ -95,10 +171,33 @@ impl Track {
         self.get_last_played().strftime("%F").to_string()
     }
 
+    /// Returns how long ago the track was last played (e.g., `3 days ago`), or `never`
+    pub fn get_last_played_relative(&self) -> String {
+        if self.last_viewed_at.is_none() {
+            return "never".to_string();
+        }
+
+        utils::humanize_since(self.get_last_played(), Timestamp::now())
+    }
+
+    pub fn get_last_played_year(&self) -> String {
+        self.get_last_played().strftime("%Y").to_string()
+    }
+
     pub fn get_last_played_year_and_month(&self) -> String {
         self.get_last_played().strftime("%Y-%m").to_string()
     }
 
+    /// Returns the ISO 8601 year and week the track was last played (e.g., `2024-W27`)
+    pub fn get_last_played_year_and_week(&self) -> String {
+        let week_date = self
+            .get_last_played()
+            .to_zoned(TimeZone::UTC)
+            .date()
+            .to_iso_week_date();
+        format!("{:04}-W{:02}", week_date.year(), week_date.week())
+    }
+
     pub fn get_played_within_last_day(&self) -> bool {
         let last_played = self.get_last_played_datetime();
         let now = utils::get_current_datetime();
@@ -111,14 +210,51 @@ impl Track {
         }
     }
 
+    pub fn get_added_at(&self) -> Timestamp {
+        if let Some(added_at) = self.added_at {
+            Timestamp::from_second(added_at).unwrap_or_default()
+        } else {
+            Timestamp::default()
+        }
+    }
+
+    pub fn get_added_at_str(&self) -> String {
+        self.get_added_at().strftime("%F").to_string()
+    }
+
     pub fn get_plays(&self) -> i32 {
         self.view_count.unwrap_or(0)
     }
 
+    pub fn get_skips(&self) -> i32 {
+        self.skip_count.unwrap_or(0)
+    }
+
+    /// The number of times the track was played minus the number of times it was skipped
+    pub fn get_net_plays(&self) -> i32 {
+        self.get_plays() - self.get_skips()
+    }
+
+    pub fn get_genres(&self) -> Vec<&str> {
+        self.genres.iter().map(|genre| genre.tag.as_str()).collect()
+    }
+
+    /// Returns true if the track has any of the given genres, ignoring case
+    pub fn has_any_genre(&self, genres: &[String]) -> bool {
+        self.genres
+            .iter()
+            .any(|genre| genres.iter().any(|g| g.eq_ignore_ascii_case(&genre.tag)))
+    }
+
     pub fn get_has_never_been_played(&self) -> bool {
         self.get_plays() == 0 || self.get_last_played() == Timestamp::default()
     }
 
+    /// The rating on plex's scale of `0` to `10`, where each star is worth `2`
+    pub fn get_user_rating(&self) -> f32 {
+        self.user_rating.unwrap_or_default()
+    }
+
     pub fn get_rating(&self) -> i32 {
         let rating = self.user_rating.unwrap_or_default();
 
@@ -132,6 +268,20 @@ impl Track {
         }
     }
 
+    /// Returns true if plex marks the track as explicit
+    pub fn is_explicit(&self) -> bool {
+        self.content_rating
+            .as_deref()
+            .is_some_and(|rating| rating.eq_ignore_ascii_case("explicit"))
+    }
+
+    /// Returns true if plex marks the track as a clean version
+    pub fn is_clean(&self) -> bool {
+        self.content_rating
+            .as_deref()
+            .is_some_and(|rating| rating.eq_ignore_ascii_case("clean"))
+    }
+
     pub fn get_title_and_artist_sort_key(&self) -> (String, String) {
         (
             self.get_track_title().to_string(),
@@ -140,17 +290,204 @@ impl Track {
     }
 }
 
-impl Display for Track {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+/// Tracks fetched from plex along with the number of tracks available
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackPage {
+    pub tracks: Vec<Track>,
+    /// The offset of the first track in the page
+    pub start: usize,
+    /// The total number of tracks available, which may exceed the number of tracks fetched
+    pub total_size: usize,
+    /// The library section the tracks were fetched from
+    pub section_id: u32,
+}
+
+impl TrackPage {
+    /// Returns true if more tracks are available than were fetched
+    pub fn has_more(&self) -> bool {
+        self.total_size > self.start + self.tracks.len()
+    }
+}
+
+impl From<PlexResponse<Vec<Track>>> for TrackPage {
+    fn from(resp: PlexResponse<Vec<Track>>) -> Self {
+        let container = resp.media_container;
+        let total_size = container
+            .total_size
+            .or(container.size)
+            .map(|size| size.max(0) as usize)
+            .unwrap_or(container.metadata.len());
+
+        Self {
+            tracks: container.metadata,
+            start: 0,
+            total_size,
+            section_id: 0,
+        }
+    }
+}
+
+/// Returns the combined duration of the tracks, counting tracks without a duration as zero
+pub fn total_duration(tracks: &[Track]) -> Duration {
+    let total: i64 = tracks.iter().map(Track::get_track_duration).sum();
+    Duration::from_millis(total.max(0) as u64)
+}
+
+impl Track {
+    /// Formats the track like its [`Display`] implementation, but with a relative last played date
+    pub fn to_string_relative(&self) -> String {
+        self.format_with_last_played(&self.get_last_played_relative())
+    }
+
+    fn format_with_last_played(&self, last_played: &str) -> String {
         let mut str = String::default();
 
         str += &format!("{} ", self.title);
         str += &format!("{} ", self.get_track_artist());
         str += &format!("{} ", self.get_track_album());
         str += &format!("{} ", self.get_plays());
-        str += &format!("{} ", self.get_last_played_str());
+        str += &format!("{} ", last_played);
 
-        write!(f, "{str}")
+        str
+    }
+}
+
+impl Display for Track {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.format_with_last_played(&self.get_last_played_str())
+        )
+    }
+}
+
+/// Builds a [`Track`] for tests, leaving every field that is not set at its default
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub struct TrackBuilder {
+    track: Track,
+}
+
+#[cfg(test)]
+impl TrackBuilder {
+    pub fn id(mut self, id: &str) -> Self {
+        self.track.rating_key = PlexId::try_new(id).unwrap();
+        self
+    }
+
+    pub fn guid(mut self, guid: &str) -> Self {
+        self.track.guid = Guid::try_new(guid).unwrap();
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.track.title = Title::try_new(title).unwrap();
+        self
+    }
+
+    pub fn album(mut self, album: &str) -> Self {
+        self.track.parent_title = Title::try_new(album).unwrap();
+        self
+    }
+
+    pub fn album_id(mut self, album_id: &str) -> Self {
+        self.track.parent_rating_key = PlexId::try_new(album_id).unwrap();
+        self
+    }
+
+    pub fn artist(mut self, artist: &str) -> Self {
+        self.track.grandparent_title = Title::try_new(artist).unwrap();
+        self
+    }
+
+    pub fn artist_id(mut self, artist_id: &str) -> Self {
+        self.track.grandparent_rating_key = PlexId::try_new(artist_id).unwrap();
+        self
+    }
+
+    pub fn artist_guid(mut self, artist_guid: &str) -> Self {
+        self.track.grandparent_guid = Guid::try_new(artist_guid).unwrap();
+        self
+    }
+
+    /// The rating out of ten, as stored by plex
+    pub fn rating(mut self, rating: f32) -> Self {
+        self.track.user_rating = Some(rating);
+        self
+    }
+
+    pub fn plays(mut self, plays: i32) -> Self {
+        self.track.view_count = Some(plays);
+        self
+    }
+
+    pub fn skips(mut self, skips: i32) -> Self {
+        self.track.skip_count = Some(skips);
+        self
+    }
+
+    /// In milliseconds
+    pub fn last_viewed_at(mut self, last_viewed_at: i64) -> Self {
+        self.track.last_viewed_at = Some(last_viewed_at);
+        self
+    }
+
+    /// In seconds
+    pub fn added_at(mut self, added_at: i64) -> Self {
+        self.track.added_at = Some(added_at);
+        self
+    }
+
+    pub fn year(mut self, year: i32) -> Self {
+        self.track.parent_year = Some(year);
+        self
+    }
+
+    /// In milliseconds
+    pub fn duration(mut self, duration: i64) -> Self {
+        self.track.duration = Some(duration);
+        self
+    }
+
+    pub fn content_rating(mut self, content_rating: &str) -> Self {
+        self.track.content_rating = Some(content_rating.to_string());
+        self
+    }
+
+    pub fn genres(mut self, genres: &[&str]) -> Self {
+        self.track.genres = genres
+            .iter()
+            .map(|genre| Tag {
+                tag: genre.to_string(),
+            })
+            .collect();
+        self
+    }
+
+    pub fn thumb(mut self, thumb: &str) -> Self {
+        self.track.thumb = Some(thumb.to_string());
+        self
+    }
+
+    pub fn parent_thumb(mut self, parent_thumb: &str) -> Self {
+        self.track.parent_thumb = Some(parent_thumb.to_string());
+        self
+    }
+
+    pub fn bitrate(mut self, bitrate: i64) -> Self {
+        self.track.media = vec![Media {
+            id: 0,
+            bitrate: Some(bitrate),
+            duration: self.track.duration,
+            audio_channels: 2,
+            audio_codec: "flac".to_string(),
+        }];
+        self
+    }
+
+    pub fn build(self) -> Track {
+        self.track
     }
 }
 
@@ -163,3 +500,90 @@ pub struct Media {
     audio_channels: i64,
     audio_codec: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn build_response(container: serde_json::Value) -> PlexResponse<Vec<Track>> {
+        let track = serde_json::to_value(Track::default()).unwrap();
+        let mut container = container;
+        container["Metadata"] = serde_json::json!([track.clone(), track]);
+        serde_json::from_value(serde_json::json!({ "MediaContainer": container })).unwrap()
+    }
+
+    #[test]
+    fn test_total_duration() {
+        let tracks = [180_000, 240_500, 95_250]
+            .into_iter()
+            .map(|duration| TrackBuilder::default().duration(duration).build())
+            .chain([Track::default()])
+            .collect::<Vec<_>>();
+
+        assert_eq!(Duration::from_millis(515_750), total_duration(&tracks));
+        assert_eq!(Duration::ZERO, total_duration(&[]));
+    }
+
+    #[test]
+    fn test_track_page_total_size() {
+        let page = TrackPage::from(build_response(serde_json::json!({ "size": 2 })));
+        assert_eq!(2, page.tracks.len());
+        assert_eq!(2, page.total_size);
+        assert!(!page.has_more());
+
+        let page = TrackPage::from(build_response(
+            serde_json::json!({ "size": 2, "totalSize": 150 }),
+        ));
+        assert_eq!(2, page.tracks.len());
+        assert_eq!(150, page.total_size);
+        assert!(page.has_more());
+
+        let page = TrackPage::from(build_response(serde_json::json!({})));
+        assert_eq!(2, page.total_size);
+    }
+
+    fn build_played_track(last_viewed_at: Option<i64>) -> Track {
+        match last_viewed_at {
+            Some(last_viewed_at) => TrackBuilder::default()
+                .last_viewed_at(last_viewed_at)
+                .build(),
+            None => Track::default(),
+        }
+    }
+
+    #[test]
+    fn test_last_played_relative() {
+        let now = Timestamp::now().as_millisecond();
+        let three_weeks = 21 * 24 * 60 * 60 * 1000;
+
+        assert_eq!("never", build_played_track(None).get_last_played_relative());
+        assert_eq!(
+            "today",
+            build_played_track(Some(now)).get_last_played_relative()
+        );
+        assert_eq!(
+            "3 weeks ago",
+            build_played_track(Some(now - three_weeks)).get_last_played_relative()
+        );
+    }
+
+    #[test]
+    fn test_numeric_fields_as_strings() {
+        let mut track = serde_json::to_value(Track::default()).unwrap();
+        track["viewCount"] = serde_json::json!(4);
+        track["userRating"] = serde_json::json!(8.0);
+        track["duration"] = serde_json::json!(240_000);
+        let from_numbers = serde_json::from_value::<Track>(track.clone()).unwrap();
+
+        track["viewCount"] = serde_json::json!("4");
+        track["userRating"] = serde_json::json!("8.0");
+        track["duration"] = serde_json::json!("240000");
+        let from_strings = serde_json::from_value::<Track>(track).unwrap();
+
+        assert_eq!(from_numbers, from_strings);
+        assert_eq!(4, from_strings.get_plays());
+        assert_eq!(240_000, from_strings.get_track_duration());
+    }
+}
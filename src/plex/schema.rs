@@ -0,0 +1,102 @@
+//! Checks that the tracks returned by plex still deserialize into [`Track`], to catch changes to
+//! plex's API before they break fetching music
+
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+use crate::plex::models::tracks::Track;
+use crate::utils;
+
+/// The number of lines before the offending line included in the snippet
+const SNIPPET_CONTEXT_LINES: usize = 3;
+
+/// The maximum number of characters in the snippet
+const SNIPPET_MAX_CHARS: usize = 1000;
+
+/// The first track that failed to deserialize into a [`Track`]
+#[derive(Debug, PartialEq)]
+pub struct SchemaError {
+    /// The position of the track in the fetched page
+    index: usize,
+    /// The `ratingKey` of the track, if present
+    rating_key: Option<String>,
+    /// The error reported while deserializing, which names the offending field
+    message: String,
+    /// The pretty-printed JSON leading up to the offending field
+    snippet: String,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Track #{} (ratingKey {}) failed to deserialize: {}\n{}",
+            self.index + 1,
+            self.rating_key.as_deref().unwrap_or("unknown"),
+            self.message,
+            self.snippet
+        )
+    }
+}
+
+/// Deserializes each track into a [`Track`], returning the first that fails
+///
+/// Each track is pretty-printed before deserializing, so that the line reported by `serde_json`
+/// points at the offending field.
+pub fn find_schema_error(tracks: &[Value]) -> Option<SchemaError> {
+    tracks.iter().enumerate().find_map(|(index, track)| {
+        let json = serde_json::to_string_pretty(track).ok()?;
+        let err = serde_json::from_str::<Track>(&json).err()?;
+
+        let lines = json.lines().collect::<Vec<_>>();
+        let end = err.line().clamp(1, lines.len().max(1));
+        let start = end.saturating_sub(SNIPPET_CONTEXT_LINES + 1);
+        let snippet = lines[start..end].join("\n");
+
+        Some(SchemaError {
+            index,
+            rating_key: track
+                .get("ratingKey")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            message: err.to_string(),
+            snippet: utils::truncate_string(&snippet, SNIPPET_MAX_CHARS).to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn build_track_json() -> Value {
+        serde_json::to_value(Track::default()).unwrap()
+    }
+
+    #[test]
+    fn test_find_schema_error_accepts_valid_tracks() {
+        assert_eq!(None, find_schema_error(&[build_track_json()]));
+    }
+
+    #[test]
+    fn test_find_schema_error_reports_malformed_track() {
+        let mut malformed = build_track_json();
+        malformed["ratingKey"] = serde_json::json!("100002");
+        malformed["parentIndex"] = serde_json::json!("one");
+
+        let err = find_schema_error(&[build_track_json(), malformed]).unwrap();
+
+        assert_eq!(1, err.index);
+        assert_eq!(Some("100002".to_string()), err.rating_key);
+        assert!(err.message.starts_with("invalid type: string \"one\""));
+        assert!(err
+            .snippet
+            .lines()
+            .last()
+            .unwrap()
+            .contains(r#""parentIndex": "one""#));
+    }
+}
@@ -0,0 +1,90 @@
+//! Read-only statistics about a plex library
+
+use std::collections::BTreeMap;
+
+use crate::plex::models::tracks::Track;
+
+/// The widest bar printed in a histogram
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// Counts tracks by the release year of their album, or by decade if `by_decade` is set
+///
+/// Tracks without a year are counted under `None`
+pub fn count_tracks_by_year(tracks: &[Track], by_decade: bool) -> BTreeMap<Option<i32>, usize> {
+    let mut counts = BTreeMap::new();
+    for track in tracks {
+        let year = track
+            .get_year()
+            .map(|year| if by_decade { year - year % 10 } else { year });
+        *counts.entry(year).or_default() += 1;
+    }
+
+    counts
+}
+
+/// Formats track counts as a histogram with one line per year
+pub fn format_year_histogram(counts: &BTreeMap<Option<i32>, usize>, by_decade: bool) -> String {
+    let max = counts.values().copied().max().unwrap_or_default();
+
+    counts
+        .iter()
+        .map(|(year, count)| {
+            let label = match year {
+                Some(year) if by_decade => format!("{year}s"),
+                Some(year) => year.to_string(),
+                None => "Unknown".to_string(),
+            };
+            let width = if max == 0 {
+                0
+            } else {
+                (count * HISTOGRAM_WIDTH).div_ceil(max)
+            };
+            format!("{label:>7} | {} {count}", "#".repeat(width))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::models::tracks::TrackBuilder;
+
+    use super::*;
+
+    fn build_track(year: Option<i32>) -> Track {
+        match year {
+            Some(year) => TrackBuilder::default().year(year).build(),
+            None => TrackBuilder::default().build(),
+        }
+    }
+
+    #[test]
+    fn test_count_tracks_by_decade() {
+        let tracks = [Some(1969), Some(1975), Some(1979), Some(1990), None]
+            .into_iter()
+            .map(build_track)
+            .collect::<Vec<_>>();
+
+        let counts = count_tracks_by_year(&tracks, true);
+        let expected =
+            BTreeMap::from([(None, 1), (Some(1960), 1), (Some(1970), 2), (Some(1990), 1)]);
+        assert_eq!(expected, counts);
+
+        let counts = count_tracks_by_year(&tracks, false);
+        assert_eq!(5, counts.len());
+        assert_eq!(Some(&1), counts.get(&Some(1975)));
+    }
+
+    #[test]
+    fn test_format_year_histogram() {
+        let counts = BTreeMap::from([(Some(1970), 2), (Some(1980), 1)]);
+        let expected = format!(
+            "  1970s | {} 2\n  1980s | {} 1",
+            "#".repeat(HISTOGRAM_WIDTH),
+            "#".repeat(HISTOGRAM_WIDTH / 2)
+        );
+        assert_eq!(expected, format_year_histogram(&counts, true));
+    }
+}
@@ -0,0 +1,229 @@
+//! A client for `plex.tv`, used to sign in with Plex's PIN-based authentication
+//!
+//! See <https://forums.plex.tv/t/authenticating-with-plex/609370> for a description of the flow.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use reqwest::Url;
+use serde::Deserialize;
+use simplelog::debug;
+
+use crate::types::plex::plex_token::PlexToken;
+
+/// The base URL of the `plex.tv` API
+pub static PLEX_TV_URL: &str = "https://plex.tv/";
+
+/// The product name shown to the user on the `plex.tv` sign in page
+static PRODUCT: &str = "hitomi";
+
+/// How often `plex.tv` is asked whether the user has signed in
+pub const PIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a PIN is assumed to be valid when `plex.tv` does not say
+const DEFAULT_PIN_EXPIRES_IN: u64 = 1800;
+
+fn default_pin_expires_in() -> u64 {
+    DEFAULT_PIN_EXPIRES_IN
+}
+
+/// A PIN created on `plex.tv`, which receives a token once the user signs in
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Pin {
+    id: i64,
+    code: String,
+    /// The number of seconds until the PIN expires
+    #[serde(default = "default_pin_expires_in")]
+    expires_in: u64,
+    auth_token: Option<String>,
+}
+
+impl Pin {
+    pub fn get_code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn get_expires_in(&self) -> Duration {
+        Duration::from_secs(self.expires_in)
+    }
+}
+
+pub struct PlexTvClient {
+    base_url: Url,
+    /// Identifies this application to `plex.tv`. A PIN must be checked with the same identifier
+    /// that created it.
+    client_identifier: String,
+    client: reqwest::Client,
+}
+
+impl PlexTvClient {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Ok(Self {
+            base_url: Url::parse(base_url)?,
+            client_identifier: format!("{PRODUCT}-{:016x}", rand::thread_rng().random::<u64>()),
+            client: reqwest::Client::builder().build()?,
+        })
+    }
+
+    /// Creates a new PIN for the user to sign in with
+    pub async fn create_pin(&self) -> Result<Pin> {
+        debug!("Creating plex.tv PIN...");
+        let url = self.base_url.join("api/v2/pins")?;
+        let resp = self
+            .client
+            .post(url)
+            .query(&[("strong", "true")])
+            .headers(self.build_headers()?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        resp.json::<Pin>()
+            .await
+            .with_context(|| "Unable to deserialize plex.tv PIN")
+    }
+
+    /// Fetches the current state of a PIN
+    pub async fn check_pin(&self, pin: &Pin) -> Result<Pin> {
+        let url = self.base_url.join(&format!("api/v2/pins/{}", pin.id))?;
+        let resp = self
+            .client
+            .get(url)
+            .headers(self.build_headers()?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        resp.json::<Pin>()
+            .await
+            .with_context(|| "Unable to deserialize plex.tv PIN")
+    }
+
+    /// Returns the URL where the user signs in to claim the PIN
+    pub fn get_auth_url(&self, pin: &Pin) -> Result<Url> {
+        // The sign in page reads its parameters from the fragment rather than the query
+        let mut url = Url::parse("https://app.plex.tv/auth")?;
+        url.query_pairs_mut()
+            .append_pair("clientID", &self.client_identifier)
+            .append_pair("code", pin.get_code())
+            .append_pair("context[device][product]", PRODUCT);
+        let query = url.query().unwrap_or_default().to_owned();
+        url.set_query(None);
+        url.set_fragment(Some(&format!("?{query}")));
+
+        Ok(url)
+    }
+
+    /// Checks the PIN every `interval` until the user signs in, failing after `timeout`
+    pub async fn wait_for_token(
+        &self,
+        pin: &Pin,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<PlexToken> {
+        let poll = async {
+            loop {
+                let pin = self.check_pin(pin).await?;
+                if let Some(token) = pin.auth_token.filter(|token| !token.is_empty()) {
+                    return PlexToken::try_new(token)
+                        .with_context(|| "plex.tv returned an invalid plex token");
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        };
+
+        tokio::time::timeout(timeout, poll).await.map_err(|_| {
+            anyhow!(
+                "Timed out after {} waiting for sign in",
+                humantime::format_duration(timeout)
+            )
+        })?
+    }
+
+    fn build_headers(&self) -> Result<reqwest::header::HeaderMap> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::ACCEPT, "application/json".parse()?);
+        headers.insert("X-Plex-Product", PRODUCT.parse()?);
+        headers.insert("X-Plex-Client-Identifier", self.client_identifier.parse()?);
+
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::mock_server::MockServer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_token() {
+        static CHECKS: AtomicUsize = AtomicUsize::new(0);
+
+        // The user signs in between the second and third checks
+        let server = MockServer::start(|path| {
+            if path.starts_with("/api/v2/pins?") {
+                return r#"{"id": 42, "code": "ABCD", "expiresIn": 1800, "authToken": null}"#
+                    .to_string();
+            }
+
+            if CHECKS.fetch_add(1, Ordering::SeqCst) < 2 {
+                r#"{"id": 42, "code": "ABCD", "authToken": null}"#.to_string()
+            } else {
+                r#"{"id": 42, "code": "ABCD", "authToken": "NJlYINZmB-Hdy78xubjR"}"#.to_string()
+            }
+        })
+        .await;
+        let client = PlexTvClient::new(server.get_url()).unwrap();
+
+        let pin = client.create_pin().await.unwrap();
+        assert_eq!("ABCD", pin.get_code());
+        assert_eq!(Duration::from_secs(1800), pin.get_expires_in());
+
+        let token = client
+            .wait_for_token(&pin, Duration::from_millis(1), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!("NJlYINZmB-Hdy78xubjR", token.as_str());
+        assert_eq!(
+            vec![
+                "POST /api/v2/pins?strong=true",
+                "GET /api/v2/pins/42",
+                "GET /api/v2/pins/42",
+                "GET /api/v2/pins/42",
+            ],
+            server.get_requests()
+        );
+    }
+
+    #[test]
+    fn test_pin_without_expiry_uses_default() {
+        let pin: Pin = serde_json::from_str(r#"{"id": 42, "code": "ABCD"}"#).unwrap();
+        assert_eq!(
+            Duration::from_secs(DEFAULT_PIN_EXPIRES_IN),
+            pin.get_expires_in()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_token_invalid_token() {
+        let server = MockServer::start(|_| {
+            r#"{"id": 42, "code": "ABCD", "authToken": "not-a-token"}"#.to_string()
+        })
+        .await;
+        let client = PlexTvClient::new(server.get_url()).unwrap();
+
+        let pin = client.check_pin(&Pin::default()).await.unwrap();
+        let result = client
+            .wait_for_token(&pin, Duration::from_millis(1), Duration::from_secs(5))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Tracks the refreshes underway so that they can be listed and cancelled while running in loop
+//! mode
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use simplelog::info;
+use tokio::task::{AbortHandle, JoinSet};
+
+#[derive(Debug)]
+struct ActiveRefresh {
+    title: String,
+    handle: AbortHandle,
+}
+
+/// The refreshes underway, keyed by profile id
+///
+/// Clones share the same refreshes, so a clone can be handed to tasks that list or cancel them.
+#[derive(Clone, Debug, Default)]
+pub struct ActiveRefreshes {
+    refreshes: Arc<Mutex<HashMap<i32, ActiveRefresh>>>,
+}
+
+impl ActiveRefreshes {
+    /// Spawns a profile's refresh into `set`, tracking it until it completes
+    pub fn spawn<T, F>(&self, set: &mut JoinSet<T>, profile_id: i32, title: &str, refresh: F)
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+    {
+        // The lock is held until the refresh is tracked, so that a refresh that finishes
+        // immediately cannot be removed before it is added
+        let mut refreshes = self.refreshes.lock().unwrap();
+        let tracked = self.clone();
+        let handle = set.spawn(async move {
+            let result = refresh.await;
+            tracked.refreshes.lock().unwrap().remove(&profile_id);
+            result
+        });
+        refreshes.insert(
+            profile_id,
+            ActiveRefresh {
+                title: title.to_owned(),
+                handle,
+            },
+        );
+    }
+
+    /// Returns the id and title of each profile being refreshed, ordered by title
+    pub fn list(&self) -> Vec<(i32, String)> {
+        let mut refreshes = self
+            .refreshes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(profile_id, refresh)| (*profile_id, refresh.title.to_owned()))
+            .collect::<Vec<_>>();
+        refreshes.sort_by(|a, b| a.1.cmp(&b.1));
+        refreshes
+    }
+
+    /// Aborts the refresh of a profile, returning `false` if it is not being refreshed
+    pub fn cancel(&self, profile_id: i32) -> bool {
+        match self.refreshes.lock().unwrap().remove(&profile_id) {
+            Some(refresh) => {
+                refresh.handle.abort();
+                info!("Cancelled the refresh of `{}`", refresh.title);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancelled_refresh_is_removed() {
+        let active = ActiveRefreshes::default();
+        let mut set = JoinSet::new();
+        active.spawn(&mut set, 1, "Daily Mix", async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        active.spawn(&mut set, 2, "Morning Mix", async {});
+
+        // The finished refresh removes itself
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(vec![(1, "Daily Mix".to_string())], active.list());
+
+        assert!(active.cancel(1));
+        assert!(!active.cancel(1));
+        assert_eq!(Vec::<(i32, String)>::new(), active.list());
+
+        let mut cancelled = 0;
+        while let Some(res) = set.join_next().await {
+            if res.is_err_and(|err| err.is_cancelled()) {
+                cancelled += 1;
+            }
+        }
+        assert_eq!(1, cancelled);
+    }
+}
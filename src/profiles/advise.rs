@@ -0,0 +1,96 @@
+//! Recommends a refresh interval based on how long a profile takes to build
+
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::profiles::VALID_INTERVALS;
+
+/// How many times longer than a build the refresh interval should be, so that a slow refresh
+/// never overlaps the next one
+const BUILD_TIME_MARGIN: u32 = 3;
+
+/// A recommended refresh interval and the reasoning behind it
+#[derive(Debug, PartialEq)]
+pub struct IntervalAdvice {
+    build_time: Duration,
+    current_interval: u32,
+    recommended_interval: u32,
+}
+
+impl IntervalAdvice {
+    /// Recommends the shortest valid interval that is at least [`BUILD_TIME_MARGIN`] times the
+    /// build time, or the longest valid interval if none are long enough
+    pub fn new(build_time: Duration, current_interval: u32) -> Self {
+        let minimum = build_time * BUILD_TIME_MARGIN;
+        let recommended_interval = VALID_INTERVALS
+            .iter()
+            .copied()
+            .find(|interval| Duration::from_secs(u64::from(*interval) * 60) >= minimum)
+            .unwrap_or(VALID_INTERVALS[VALID_INTERVALS.len() - 1]);
+
+        Self {
+            build_time,
+            current_interval,
+            recommended_interval,
+        }
+    }
+
+    fn is_too_slow(&self) -> bool {
+        Duration::from_secs(u64::from(self.recommended_interval) * 60)
+            < self.build_time * BUILD_TIME_MARGIN
+    }
+}
+
+impl Display for IntervalAdvice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut str = format!("Build time:           {:.2?}", self.build_time);
+        str += &format!("\nCurrent interval:     {} minutes", self.current_interval);
+        str += &format!(
+            "\nRecommended interval: {} minutes",
+            self.recommended_interval
+        );
+
+        str += &format!(
+            "\n\nA refresh should take no more than a third of the interval, so that a slow refresh \
+             never overlaps the next one. At least {:.2?} is needed between refreshes.",
+            self.build_time * BUILD_TIME_MARGIN
+        );
+        if self.is_too_slow() {
+            str += "\nEven the longest interval is shorter than this. Consider lowering the \
+                    profile's time limit or track limit.";
+        } else if self.recommended_interval < self.current_interval {
+            str += "\nThe current interval is longer than needed, so the profile could refresh more often.";
+        } else if self.recommended_interval > self.current_interval {
+            str += "\nThe current interval is too short and refreshes may overlap.";
+        } else {
+            str += "\nThe current interval is already the recommended interval.";
+        }
+
+        writeln!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_slow_build_recommends_longer_interval() {
+        let fast = IntervalAdvice::new(Duration::from_secs(5), 5);
+        let slow = IntervalAdvice::new(Duration::from_secs(150), 5);
+
+        assert_eq!(2, fast.recommended_interval);
+        assert_eq!(10, slow.recommended_interval);
+        assert!(slow.recommended_interval > fast.recommended_interval);
+    }
+
+    #[test]
+    fn test_very_slow_build_recommends_longest_interval() {
+        let advice = IntervalAdvice::new(Duration::from_secs(3600), 5);
+
+        assert_eq!(30, advice.recommended_interval);
+        assert!(advice.is_too_slow());
+    }
+}
@@ -0,0 +1,210 @@
+//! Measures how long each phase of building a profile's tracks takes
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::plex::models::tracks::Track;
+use crate::profiles::profile::Profile;
+use crate::profiles::profile_tracks::ProfileTracks;
+use crate::profiles::track_source::TrackSource;
+use crate::types::plex::hub_identifier::HubIdentifier;
+
+/// How long each phase of building a profile's tracks took
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PhaseTimings {
+    /// Fetching the tracks of each section
+    pub fetch: Duration,
+    /// Deduplicating, trimming, and randomizing the tracks of each section
+    pub filter: Duration,
+    /// Merging the sections into a single playlist
+    pub merge: Duration,
+}
+
+/// A [`TrackSource`] that only fetches a given set of tracks once
+struct CachedTrackSource<'a, S: TrackSource + Sync> {
+    source: &'a S,
+    tracks: Mutex<HashMap<String, Vec<Track>>>,
+}
+
+impl<'a, S: TrackSource + Sync> CachedTrackSource<'a, S> {
+    fn new(source: &'a S) -> Self {
+        Self {
+            source,
+            tracks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cache_key(filters: &HashMap<String, String>, sort: &[&str], limit: Option<i32>) -> String {
+        let filters = filters
+            .iter()
+            .sorted()
+            .map(|(k, v)| format!("{k}={v}"))
+            .join("&");
+        format!("{filters}|{}|{limit:?}", sort.join(","))
+    }
+}
+
+impl<S: TrackSource + Sync> TrackSource for CachedTrackSource<'_, S> {
+    async fn fetch(
+        &self,
+        filters: HashMap<String, String>,
+        sort: Vec<&str>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Track>> {
+        let key = Self::cache_key(&filters, &sort, limit);
+        if let Some(tracks) = self.tracks.lock().unwrap().get(&key) {
+            return Ok(tracks.clone());
+        }
+
+        let tracks = self.source.fetch(filters, sort, limit).await?;
+        self.tracks.lock().unwrap().insert(key, tracks.clone());
+
+        Ok(tracks)
+    }
+
+    async fn fetch_collection_artists(&self, collection_id: &str) -> Result<Vec<String>> {
+        self.source.fetch_collection_artists(collection_id).await
+    }
+
+    async fn fetch_album_track_count(&self, album_id: &str) -> Result<u32> {
+        self.source.fetch_album_track_count(album_id).await
+    }
+
+    async fn fetch_hub_artists(&self, hub_identifier: &HubIdentifier) -> Result<Vec<String>> {
+        self.source.fetch_hub_artists(hub_identifier).await
+    }
+}
+
+/// Builds a profile's tracks `runs` times and returns the timings of each run
+///
+/// If `cached` is true, tracks are only fetched from the source during the first run
+pub async fn run_bench(
+    source: &(impl TrackSource + Sync),
+    profile: &Profile,
+    runs: usize,
+    cached: bool,
+) -> Result<Vec<PhaseTimings>> {
+    let cached_source = CachedTrackSource::new(source);
+
+    let mut timings = vec![];
+    for _ in 0..runs {
+        let (_, run_timings) = if cached {
+            ProfileTracks::new_timed(&cached_source, profile).await?
+        } else {
+            ProfileTracks::new_timed(source, profile).await?
+        };
+        timings.push(run_timings);
+    }
+
+    Ok(timings)
+}
+
+/// The minimum, median, and maximum duration of a phase
+#[derive(Debug, Default, PartialEq)]
+struct PhaseSummary {
+    min: Duration,
+    median: Duration,
+    max: Duration,
+}
+
+impl PhaseSummary {
+    fn new(durations: impl Iterator<Item = Duration>) -> Self {
+        let durations = durations.sorted().collect_vec();
+        if durations.is_empty() {
+            return Self::default();
+        }
+
+        Self {
+            min: durations[0],
+            median: durations[durations.len() / 2],
+            max: durations[durations.len() - 1],
+        }
+    }
+}
+
+/// A summary of the timings of each phase across every run
+#[derive(Debug, Default, PartialEq)]
+pub struct BenchSummary {
+    runs: usize,
+    fetch: PhaseSummary,
+    filter: PhaseSummary,
+    merge: PhaseSummary,
+}
+
+impl BenchSummary {
+    pub fn new(timings: &[PhaseTimings]) -> Self {
+        Self {
+            runs: timings.len(),
+            fetch: PhaseSummary::new(timings.iter().map(|t| t.fetch)),
+            filter: PhaseSummary::new(timings.iter().map(|t| t.filter)),
+            merge: PhaseSummary::new(timings.iter().map(|t| t.merge)),
+        }
+    }
+}
+
+impl Display for BenchSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut str = format!("{} run{}", self.runs, if self.runs == 1 { "" } else { "s" });
+        str += &format!(
+            "\n{:<8} {:>12} {:>12} {:>12}",
+            "Phase", "Min", "Median", "Max"
+        );
+        for (phase, summary) in [
+            ("Fetch", &self.fetch),
+            ("Filter", &self.filter),
+            ("Merge", &self.merge),
+        ] {
+            str += &format!(
+                "\n{:<8} {:>12} {:>12} {:>12}",
+                phase,
+                format!("{:.2?}", summary.min),
+                format!("{:.2?}", summary.median),
+                format!("{:.2?}", summary.max)
+            );
+        }
+
+        writeln!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bench_summary() {
+        let timings = [30, 10, 20]
+            .into_iter()
+            .map(|ms| PhaseTimings {
+                fetch: Duration::from_millis(ms),
+                filter: Duration::from_millis(ms * 2),
+                merge: Duration::from_millis(1),
+            })
+            .collect_vec();
+
+        let summary = BenchSummary::new(&timings);
+        assert_eq!(3, summary.runs);
+        assert_eq!(
+            PhaseSummary {
+                min: Duration::from_millis(10),
+                median: Duration::from_millis(20),
+                max: Duration::from_millis(30),
+            },
+            summary.fetch
+        );
+        assert_eq!(Duration::from_millis(40), summary.filter.median);
+        assert_eq!(Duration::from_millis(1), summary.merge.max);
+    }
+
+    #[test]
+    fn test_bench_summary_no_runs() {
+        assert_eq!(BenchSummary::default(), BenchSummary::new(&[]));
+    }
+}
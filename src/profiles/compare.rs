@@ -0,0 +1,223 @@
+//! Compares two profiles and their sections field by field
+
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::profiles::profile::Profile;
+use crate::profiles::profile_section::ProfileSection;
+use crate::profiles::DEFAULT_MERGE_ORDER;
+
+/// Fields that identify a profile or are calculated at runtime, and so are not worth comparing
+static IGNORED_FIELDS: [&str; 9] = [
+    "profile_id",
+    "profile_section_id",
+    "playlist_id",
+    "num_sections",
+    "section_time_limit",
+    "refreshes_per_hour",
+    "current_refresh",
+    "next_refresh_at",
+    "eligible_for_refresh",
+];
+
+/// The values of a single field in two profiles
+#[derive(Debug, PartialEq)]
+pub struct FieldDiff {
+    field: String,
+    left: String,
+    right: String,
+}
+
+impl FieldDiff {
+    pub fn is_different(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+/// Compares the serialized fields of two values, prefixing each field name with `prefix`
+///
+/// A missing value is treated as having no fields, so every field of the other value differs.
+fn diff_fields<T: Serialize>(
+    prefix: &str,
+    left: Option<&T>,
+    right: Option<&T>,
+) -> Result<Vec<FieldDiff>> {
+    let left = serde_json::to_value(left)?;
+    let right = serde_json::to_value(right)?;
+
+    let mut fields = vec![];
+    for value in [&left, &right] {
+        if let Value::Object(map) = value {
+            for key in map.keys() {
+                if !fields.contains(key) && !IGNORED_FIELDS.contains(&key.as_str()) {
+                    fields.push(key.to_owned());
+                }
+            }
+        }
+    }
+
+    let diffs = fields
+        .into_iter()
+        .map(|field| FieldDiff {
+            left: format_value(left.get(&field)),
+            right: format_value(right.get(&field)),
+            field: format!("{prefix}{field}"),
+        })
+        .collect();
+
+    Ok(diffs)
+}
+
+fn format_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::String(value)) => value.to_owned(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// A field-by-field comparison of two profiles and their sections
+#[derive(Debug, PartialEq)]
+pub struct ProfileComparison {
+    left_title: String,
+    right_title: String,
+    diffs: Vec<FieldDiff>,
+}
+
+impl ProfileComparison {
+    /// Compares two profiles, pairing their sections by section type
+    pub fn new(
+        left: &Profile,
+        left_sections: &[ProfileSection],
+        right: &Profile,
+        right_sections: &[ProfileSection],
+    ) -> Result<Self> {
+        let mut diffs = diff_fields("", Some(left), Some(right))?;
+
+        for section_type in DEFAULT_MERGE_ORDER {
+            let find_section = |sections: &[ProfileSection]| {
+                sections
+                    .iter()
+                    .find(|section| section.get_section_type() == section_type)
+                    .cloned()
+            };
+            let left_section = find_section(left_sections);
+            let right_section = find_section(right_sections);
+            if left_section.is_none() && right_section.is_none() {
+                continue;
+            }
+
+            diffs.extend(diff_fields(
+                &format!("{section_type}."),
+                left_section.as_ref(),
+                right_section.as_ref(),
+            )?);
+        }
+
+        Ok(Self {
+            left_title: left.get_title().to_string(),
+            right_title: right.get_title().to_string(),
+            diffs,
+        })
+    }
+
+    pub fn get_differences(&self) -> Vec<&FieldDiff> {
+        self.diffs
+            .iter()
+            .filter(|diff| diff.is_different())
+            .collect()
+    }
+}
+
+impl Display for ProfileComparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let field_width = self
+            .diffs
+            .iter()
+            .map(|diff| diff.field.len())
+            .max()
+            .unwrap_or_default()
+            .max("Field".len());
+        let left_width = self
+            .diffs
+            .iter()
+            .map(|diff| diff.left.len())
+            .max()
+            .unwrap_or_default()
+            .max(self.left_title.len());
+
+        let mut str = format!(
+            "  {:<field_width$}  {:<left_width$}  {}",
+            "Field", self.left_title, self.right_title
+        );
+        for diff in &self.diffs {
+            str += &format!(
+                "\n{} {:<field_width$}  {:<left_width$}  {}",
+                if diff.is_different() { "*" } else { " " },
+                diff.field,
+                diff.left,
+                diff.right
+            );
+        }
+
+        let differences = self.get_differences().len();
+        str += &format!(
+            "\n\n{differences} difference{} marked with `*`",
+            if differences == 1 { "" } else { "s" }
+        );
+
+        writeln!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::profiles::profile::ProfileBuilder;
+    use crate::types::plex::plex_id::PlexId;
+    use crate::types::Title;
+
+    use super::*;
+
+    #[test]
+    fn test_compare_profiles() {
+        let build_profile = |playlist_id: &str, time_limit: u32| {
+            ProfileBuilder::default()
+                .playlist_id(PlexId::try_new(playlist_id).unwrap())
+                .title(Title::try_new("Morning Mix").unwrap())
+                .time_limit(time_limit)
+                .build()
+                .unwrap()
+        };
+        let sections = [serde_json::from_value::<ProfileSection>(serde_json::json!({
+            "section_type": "Unplayed",
+            "sorting": "viewCount"
+        }))
+        .unwrap()];
+
+        let comparison = ProfileComparison::new(
+            &build_profile("123456", 4),
+            &sections,
+            &build_profile("654321", 12),
+            &sections,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![&FieldDiff {
+                field: "time_limit".to_string(),
+                left: "4".to_string(),
+                right: "12".to_string(),
+            }],
+            comparison.get_differences()
+        );
+        assert!(comparison
+            .diffs
+            .iter()
+            .any(|diff| diff.field == "Unplayed Tracks.sorting"));
+    }
+}
@@ -0,0 +1,257 @@
+//! Re-derives the computed fields of the `v_profile` view to catch drift between the view and
+//! the rust logic that depends on it
+
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use sqlx::FromRow;
+
+use crate::db;
+use crate::profiles::profile::UNLIMITED_TIME_LIMIT;
+use crate::profiles::profile_section::ProfileSection;
+
+/// The maximum number of sections a profile can have
+const MAX_SECTIONS: u32 = 3;
+
+/// A profile's inputs and computed fields as read from `v_profile`
+#[derive(Clone, Debug, FromRow)]
+pub struct ProfileViewRecord {
+    profile_id: i32,
+    profile_title: String,
+    enabled: bool,
+    /// The time limit stored in the profile table, before the view substitutes unlimited limits
+    raw_time_limit: u32,
+    refresh_interval: u32,
+    /// The unix timestamp the view was evaluated at
+    evaluated_at: i64,
+    num_sections: u32,
+    has_max_sections: bool,
+    /// Null when a profile has no enabled sections
+    section_time_limit: Option<f64>,
+    refreshes_per_hour: u32,
+    current_refresh: i64,
+    next_refresh_at: i64,
+    eligible_for_refresh: bool,
+}
+
+/// The fields `v_profile` computes for each profile
+#[derive(Debug, PartialEq)]
+pub struct DerivedFields {
+    num_sections: u32,
+    has_max_sections: bool,
+    section_time_limit: Option<f64>,
+    refreshes_per_hour: u32,
+    current_refresh: i64,
+    next_refresh_at: i64,
+    eligible_for_refresh: bool,
+}
+
+impl DerivedFields {
+    /// The fields as computed by the view
+    pub fn from_view(record: &ProfileViewRecord) -> Self {
+        Self {
+            num_sections: record.num_sections,
+            has_max_sections: record.has_max_sections,
+            section_time_limit: record.section_time_limit,
+            refreshes_per_hour: record.refreshes_per_hour,
+            current_refresh: record.current_refresh,
+            next_refresh_at: record.next_refresh_at,
+            eligible_for_refresh: record.eligible_for_refresh,
+        }
+    }
+
+    /// The fields derived from the profile's inputs and sections at the time the view was
+    /// evaluated
+    pub fn derive(record: &ProfileViewRecord, sections: &[ProfileSection]) -> Self {
+        let num_sections = if record.enabled {
+            sections
+                .iter()
+                .filter(|section| {
+                    section.get_profile_id() == record.profile_id && section.is_enabled()
+                })
+                .count() as u32
+        } else {
+            0
+        };
+
+        let time_limit = if record.raw_time_limit == 0 {
+            UNLIMITED_TIME_LIMIT
+        } else {
+            record.raw_time_limit
+        };
+
+        let section_time_limit = if num_sections == 0 {
+            None
+        } else {
+            Some(time_limit as f64 / num_sections as f64)
+        };
+
+        let interval = record.refresh_interval as i64;
+        let interval_seconds = interval * 60;
+        let now = record.evaluated_at;
+        let minute = (now / 60) % 60;
+
+        Self {
+            num_sections,
+            has_max_sections: num_sections >= MAX_SECTIONS,
+            section_time_limit,
+            refreshes_per_hour: 60 / record.refresh_interval,
+            current_refresh: now - now % interval_seconds,
+            next_refresh_at: now + (interval_seconds - now % interval_seconds),
+            eligible_for_refresh: minute % interval == 0,
+        }
+    }
+}
+
+/// A computed field whose value in the view differs from the value derived in rust
+#[derive(Debug, PartialEq)]
+pub struct FieldMismatch {
+    profile_title: String,
+    field: &'static str,
+    view: String,
+    derived: String,
+}
+
+impl Display for FieldMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: `{}` is {} in the view but {} when derived",
+            self.profile_title, self.field, self.view, self.derived
+        )
+    }
+}
+
+/// Compares the view's computed fields for each profile against the fields derived in rust
+pub fn find_mismatches(
+    records: &[ProfileViewRecord],
+    sections: &[ProfileSection],
+) -> Vec<FieldMismatch> {
+    let mut mismatches = vec![];
+
+    for record in records {
+        let view = DerivedFields::from_view(record);
+        let derived = DerivedFields::derive(record, sections);
+
+        let mut compare = |field: &'static str, view: String, derived: String| {
+            if view != derived {
+                mismatches.push(FieldMismatch {
+                    profile_title: record.profile_title.to_owned(),
+                    field,
+                    view,
+                    derived,
+                })
+            }
+        };
+
+        compare(
+            "num_sections",
+            view.num_sections.to_string(),
+            derived.num_sections.to_string(),
+        );
+        compare(
+            "has_max_sections",
+            view.has_max_sections.to_string(),
+            derived.has_max_sections.to_string(),
+        );
+        compare(
+            "section_time_limit",
+            format_section_time_limit(view.section_time_limit),
+            format_section_time_limit(derived.section_time_limit),
+        );
+        compare(
+            "refreshes_per_hour",
+            view.refreshes_per_hour.to_string(),
+            derived.refreshes_per_hour.to_string(),
+        );
+        compare(
+            "current_refresh",
+            view.current_refresh.to_string(),
+            derived.current_refresh.to_string(),
+        );
+        compare(
+            "next_refresh_at",
+            view.next_refresh_at.to_string(),
+            derived.next_refresh_at.to_string(),
+        );
+        compare(
+            "eligible_for_refresh",
+            view.eligible_for_refresh.to_string(),
+            derived.eligible_for_refresh.to_string(),
+        );
+    }
+
+    mismatches
+}
+
+/// Reads every profile from `v_profile` and reports the computed fields that differ from the
+/// fields derived in rust
+pub async fn recompute_profiles() -> Result<Vec<FieldMismatch>> {
+    let records = db::profiles::fetch_profile_view_records().await?;
+    let sections = db::profiles::fetch_profile_sections().await?;
+
+    Ok(find_mismatches(&records, &sections))
+}
+
+fn format_section_time_limit(section_time_limit: Option<f64>) -> String {
+    match section_time_limit {
+        None => String::from("null"),
+        Some(section_time_limit) => format!("{section_time_limit:.6}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn build_record(evaluated_at: i64) -> ProfileViewRecord {
+        ProfileViewRecord {
+            profile_id: 1,
+            profile_title: String::from("Test"),
+            enabled: true,
+            raw_time_limit: 0,
+            refresh_interval: 15,
+            evaluated_at,
+            num_sections: 0,
+            has_max_sections: false,
+            section_time_limit: None,
+            refreshes_per_hour: 4,
+            current_refresh: 1_699_999_200,
+            next_refresh_at: 1_700_000_100,
+            eligible_for_refresh: false,
+        }
+    }
+
+    #[test]
+    fn test_derive_refresh_fields() {
+        // 2023-11-14 22:13:20 UTC
+        let record = build_record(1_700_000_000);
+
+        let derived = DerivedFields::derive(&record, &[]);
+
+        assert_eq!(DerivedFields::from_view(&record), derived);
+        assert!(find_mismatches(&[record], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_find_mismatches_reports_drift() {
+        let mut record = build_record(1_700_000_000);
+        record.refreshes_per_hour = 3;
+        record.eligible_for_refresh = true;
+
+        let mismatches = find_mismatches(&[record], &[]);
+
+        assert_eq!(
+            vec![
+                "Test: `refreshes_per_hour` is 3 in the view but 4 when derived",
+                "Test: `eligible_for_refresh` is true in the view but false when derived",
+            ],
+            mismatches
+                .iter()
+                .map(|mismatch| mismatch.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Loading profiles from JSON files on disk, as stored before profiles were kept in the database
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::profiles::profile::Profile;
+use crate::profiles::profile_section::ProfileSection;
+
+/// A profile and its sections as stored in a single JSON file
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct ProfileFile {
+    #[serde(flatten)]
+    pub profile: Profile,
+    #[serde(default)]
+    pub sections: Vec<ProfileSection>,
+}
+
+/// Loads every `.json` profile file in a directory, ordered by file name
+pub async fn load_profiles_from_disk(dir: &Path) -> Result<Vec<ProfileFile>> {
+    let mut paths = vec![];
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Unable to read profiles directory `{}`", dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut profiles = vec![];
+    for path in paths {
+        profiles.push(load_profile_file(&path).await?);
+    }
+
+    Ok(profiles)
+}
+
+/// Loads a single profile file
+pub async fn load_profile_file(path: &Path) -> Result<ProfileFile> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Unable to read profile file `{}`", path.display()))?;
+    serde_json::from_str::<ProfileFile>(&contents)
+        .with_context(|| format!("Unable to parse profile file `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::profiles::SectionType;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_profiles_from_disk() {
+        let dir = std::env::temp_dir().join(format!("hitomi-import-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let first = r#"{
+            "title": "Morning Mix",
+            "summary": "Wake up",
+            "profile_source": "Library",
+            "time_limit": 4,
+            "sections": [
+                {
+                    "enabled": true,
+                    "section_type": "Unplayed",
+                    "minimum_track_rating": 3,
+                    "sorting": "viewCount"
+                }
+            ]
+        }"#;
+        let second = r#"{
+            "title": "Evening Mix",
+            "summary": "Wind down",
+            "profile_source": "Library",
+            "sections": [
+                { "enabled": true, "section_type": "LeastPlayed", "sorting": "viewCount" },
+                { "enabled": true, "section_type": "Oldest", "sorting": "lastViewedAt" }
+            ]
+        }"#;
+        tokio::fs::write(dir.join("a.json"), first).await.unwrap();
+        tokio::fs::write(dir.join("b.json"), second).await.unwrap();
+        tokio::fs::write(dir.join("notes.txt"), "not a profile")
+            .await
+            .unwrap();
+
+        let profiles = load_profiles_from_disk(&dir).await.unwrap();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(2, profiles.len());
+        assert_eq!("Morning Mix", profiles[0].profile.get_title());
+        assert_eq!(4, profiles[0].profile.get_time_limit());
+        assert_eq!(1, profiles[0].sections.len());
+        assert_eq!(3, profiles[0].sections[0].get_minimum_track_rating());
+        assert_eq!("Evening Mix", profiles[1].profile.get_title());
+        assert_eq!(
+            vec![SectionType::LeastPlayed, SectionType::Oldest],
+            profiles[1]
+                .sections
+                .iter()
+                .map(|section| section.get_section_type())
+                .collect::<Vec<_>>()
+        );
+    }
+}
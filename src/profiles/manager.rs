@@ -1,33 +1,60 @@
 //! Manages profiles
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Confirm;
 use itertools::Itertools;
-use jiff::Zoned;
-use simplelog::{error, info};
+use jiff::{Timestamp, Zoned};
+use simplelog::{error, info, warn};
+use tokio::sync::watch;
 use tokio::task::JoinSet;
 
+use crate::config::Config;
+use crate::export::ExportFormat;
 use crate::plex::models::playlists::Playlist;
+use crate::plex::models::tracks;
 use crate::plex::models::tracks::Track;
 use crate::plex::PlexClient;
+use crate::profiles::active::ActiveRefreshes;
+use crate::profiles::advise::IntervalAdvice;
+use crate::profiles::bench;
+use crate::profiles::bench::BenchSummary;
+use crate::profiles::compare::ProfileComparison;
+use crate::profiles::import;
+use crate::profiles::import::ProfileFile;
+use crate::profiles::overlap;
 use crate::profiles::profile::Profile;
 use crate::profiles::profile_section::ProfileSection;
-use crate::profiles::profile_tracks::ProfileTracks;
+use crate::profiles::profile_tracks;
+use crate::profiles::profile_tracks::{ArtistTrack, ProfileTracks, SourceUnavailable};
 use crate::profiles::refresh_result::RefreshResult;
-use crate::profiles::ProfileAction;
+use crate::profiles::{ProfileAction, ProfileSortBy};
 use crate::types::plex::plex_id::PlexId;
-use crate::{config, db};
+use crate::{config, db, export};
 
 #[derive(Clone, Debug)]
 pub struct ProfileManager {
+    config: Config,
     plex_client: PlexClient,
     playlists: Vec<Playlist>,
+    active_refreshes: ActiveRefreshes,
 }
 
+/// The number of times to try adding tracks to a playlist after it has been cleared
+const ADD_ITEMS_ATTEMPTS: u32 = 3;
+/// The delay before retrying to add tracks to a playlist, doubled after each retry
+const ADD_ITEMS_BACKOFF: Duration = Duration::from_secs(2);
+/// How long refreshes already underway are given to finish once a shutdown is requested
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+/// The delay before retrying profiles whose tracks could not be fetched, doubled after each retry
+const REFRESH_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
 // INITIALIZATION
 impl ProfileManager {
     pub async fn new() -> Result<Self> {
@@ -36,8 +63,10 @@ impl ProfileManager {
         let playlists = plex_client.get_playlists().to_vec();
 
         let manager = ProfileManager {
+            config,
             plex_client,
             playlists,
+            active_refreshes: ActiveRefreshes::default(),
         };
         Ok(manager)
     }
@@ -50,9 +79,26 @@ impl ProfileManager {
     }
 }
 
+// CONFIG
+impl ProfileManager {
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+}
+
+// REFRESHES
+impl ProfileManager {
+    /// The refreshes underway, which can be listed and cancelled while refreshing
+    pub fn get_active_refreshes(&self) -> &ActiveRefreshes {
+        &self.active_refreshes
+    }
+}
+
 // PLAYLISTS
 impl ProfileManager {
+    /// Finds a playlist in plex by its profile title, including the configured prefix and suffix
     pub fn get_playlist_by_title(&self, title: &str) -> Option<&Playlist> {
+        let title = self.plex_client.get_playlist_title(title);
         self.playlists.iter().find(|p| p.get_title() == title)
     }
 }
@@ -66,12 +112,22 @@ impl ProfileManager {
         if ran_once && !self.fetch_any_profile_refresh().await? {
             return Ok(vec![]);
         }
-        let to_refresh = db::profiles::fetch_profiles_to_refresh(!ran_once).await?;
+        let hour = Zoned::now().hour() as u32;
+        let to_refresh = db::profiles::fetch_profiles_to_refresh(!ran_once)
+            .await?
+            .into_iter()
+            .filter(|profile| profile.is_within_active_hours(hour))
+            .collect();
         Ok(to_refresh)
     }
 
-    pub async fn list_profiles_and_sections(&self) -> Result<()> {
-        let profiles = db::profiles::fetch_profiles(false).await?;
+    pub async fn list_profiles_and_sections(
+        &self,
+        sort_by: ProfileSortBy,
+        reverse: bool,
+    ) -> Result<()> {
+        let mut profiles = db::profiles::fetch_profiles(false).await?;
+        sort_profiles(&mut profiles, sort_by, reverse);
 
         for profile in profiles {
             println!("{}", profile.get_title());
@@ -84,6 +140,202 @@ impl ProfileManager {
         Ok(())
     }
 
+    /// Reports profiles whose playlists no longer exist on the plex server
+    ///
+    /// If `fix` is `true`, the missing playlists are recreated and the stored playlist ids are updated
+    pub async fn audit_profiles(&self, fix: bool) -> Result<()> {
+        let profiles = db::profiles::fetch_profiles(false).await?;
+        let orphaned = find_orphaned_profiles(&profiles, &self.playlists);
+
+        if orphaned.is_empty() {
+            info!("All profiles have a matching playlist in plex.");
+            return Ok(());
+        }
+
+        warn!(
+            "Found {} profile{} without a matching playlist in plex:",
+            orphaned.len(),
+            if orphaned.len() == 1 { "" } else { "s" }
+        );
+        for profile in &orphaned {
+            println!(
+                " - {} (playlist id: {})",
+                profile.get_title(),
+                profile.get_playlist_id()
+            );
+        }
+
+        if !fix {
+            return Ok(());
+        }
+
+        for profile in orphaned {
+            if self.plex_client.is_read_only() {
+                info!(
+                    "[READ-ONLY] Would have recreated playlist for `{}`",
+                    profile.get_title()
+                );
+                continue;
+            }
+
+            info!("Recreating playlist for `{}`...", profile.get_title());
+            let playlist_id = self.plex_client.create_playlist(profile).await?;
+            let playlist_id = PlexId::try_new(playlist_id)?;
+            db::profiles::update_playlist_id(profile.get_profile_id(), &playlist_id).await?;
+
+            let mut profile = profile.to_owned();
+            profile.set_playlist_id(playlist_id);
+            let refresh_result = update_playlist(
+                self.get_plex_client().new_refresh_cycle(),
+                profile,
+                self.config.clone(),
+            )
+            .await?;
+            println!("{refresh_result}\n");
+        }
+
+        Ok(())
+    }
+
+    /// Deletes disabled profiles after confirmation
+    ///
+    /// If `older_than` is set, only profiles disabled more than that many days ago are pruned. If
+    /// `delete_playlists` is `true`, the profiles' playlists are also deleted from plex.
+    pub async fn prune_profiles(
+        &self,
+        older_than: Option<u32>,
+        delete_playlists: bool,
+    ) -> Result<()> {
+        let profiles = db::profiles::fetch_prunable_profiles(older_than).await?;
+
+        if profiles.is_empty() {
+            info!("No disabled profiles to prune.");
+            return Ok(());
+        }
+
+        println!(
+            "Found {} disabled profile{}:",
+            profiles.len(),
+            if profiles.len() == 1 { "" } else { "s" }
+        );
+        for profile in &profiles {
+            println!(" - {}", profile.get_title());
+        }
+
+        let prune = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(if delete_playlists {
+                "Delete these profiles and their playlists?"
+            } else {
+                "Delete these profiles?"
+            })
+            .default(false)
+            .interact()?;
+        if !prune {
+            info!("No profiles were pruned");
+            return Ok(());
+        }
+
+        for profile in &profiles {
+            if self.plex_client.is_read_only() {
+                info!(
+                    "[READ-ONLY] Would have deleted profile `{}`",
+                    profile.get_title()
+                );
+                continue;
+            }
+
+            if delete_playlists {
+                self.plex_client
+                    .delete_playlist(profile.get_playlist_id())
+                    .await?;
+            }
+            db::profiles::delete_profile(profile.get_profile_id()).await?;
+            info!("Deleted profile `{}`", profile.get_title());
+        }
+
+        Ok(())
+    }
+
+    /// Builds a profile with overridden limits into a temporary shadow playlist, leaving the
+    /// profile's own playlist untouched
+    pub async fn shadow_profile(
+        &self,
+        profile: &Profile,
+        time_limit: Option<u32>,
+        track_limit: Option<u32>,
+    ) -> Result<()> {
+        let shadow = profile.to_shadow(time_limit, track_limit)?;
+
+        info!("Building `{}`...", shadow.get_title());
+        let profile_tracks = ProfileTracks::new(self.get_plex_client(), &shadow).await?;
+        let playlist_id = create_shadow_playlist(
+            self.get_plex_client(),
+            &shadow,
+            &profile_tracks.get_track_ids(),
+            &db::profiles::fetch_profile_shadow_playlist_ids(shadow.get_profile_id()).await?,
+        )
+        .await?;
+        if !self.plex_client.is_read_only() {
+            db::profiles::save_shadow_playlist(shadow.get_profile_id(), playlist_id.as_str())
+                .await?;
+            db::profiles::clear_track_ids_hash(shadow.get_profile_id()).await?;
+        }
+
+        print_refresh_results(
+            profile_tracks.get_merged_tracks(),
+            shadow.get_title(),
+            ProfileAction::Create { save: true },
+        );
+        info!("Shadow playlist id: {playlist_id}");
+
+        Ok(())
+    }
+
+    /// Deletes the shadow playlists created by `hitomi` from plex, after confirmation
+    ///
+    /// Only playlists recorded when their shadow was built are deleted, so playlists that merely
+    /// share the shadow suffix are left alone.
+    pub async fn cleanup_shadows(&self) -> Result<()> {
+        let shadow_ids = db::profiles::fetch_shadow_playlist_ids().await?;
+        let shadows = self
+            .plex_client
+            .get_shadow_playlists()
+            .into_iter()
+            .filter(|playlist| shadow_ids.contains(playlist.get_id()))
+            .collect::<Vec<_>>();
+        if shadows.is_empty() {
+            info!("No shadow playlists to delete.");
+            return Ok(());
+        }
+
+        println!("The following shadow playlists will be deleted:");
+        for playlist in &shadows {
+            println!(" - {}", playlist.get_title());
+        }
+
+        let delete = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Delete these playlists?")
+            .default(false)
+            .interact()?;
+        if !delete {
+            info!("No shadow playlists were deleted");
+            return Ok(());
+        }
+
+        for playlist in shadows {
+            self.plex_client
+                .delete_playlist(&PlexId::try_new(playlist.get_id())?)
+                .await?;
+            // The playlist is still in plex, so it must stay recorded for a later cleanup
+            if !self.plex_client.is_read_only() {
+                db::profiles::delete_shadow_playlist(playlist.get_id()).await?;
+                info!("Deleted shadow playlist `{}`", playlist.get_title());
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn fetch_any_profile_refresh(&self) -> Result<bool> {
         if Zoned::now().second() != 0 {
             return Ok(false);
@@ -120,33 +372,94 @@ impl ProfileManager {
         Ok(())
     }
 
+    /// Prints when each profile will next refresh, soonest first
+    pub async fn print_next_refreshes(&self, enabled_only: bool) -> Result<()> {
+        let mut profiles = db::profiles::fetch_profiles(enabled_only).await?;
+        sort_profiles(&mut profiles, ProfileSortBy::NextRefresh, false);
+
+        println!(
+            "{}",
+            format_next_refresh_table(&profiles, Timestamp::now().as_second())
+        );
+
+        Ok(())
+    }
+
+    /// Refreshes every profile that is due for a refresh
+    ///
+    /// If `shutdown` is signalled while playlists are refreshing, the refreshes already underway
+    /// are given [`SHUTDOWN_TIMEOUT`] to finish so that no playlist is left half-updated.
     pub async fn refresh_playlists_from_profiles(
         &self,
         run_loop: bool,
         ran_once: bool,
+        shutdown: &mut watch::Receiver<bool>,
     ) -> Result<()> {
         if ran_once && !self.fetch_any_profile_refresh().await? {
             return Ok(());
         }
 
         let profiles = self.get_profiles_to_refresh(ran_once).await?;
-        let mut set = JoinSet::new();
-        for profile in profiles {
-            set.spawn(update_playlist(self.get_plex_client().to_owned(), profile));
+        self.refresh_profiles(profiles, shutdown).await?;
+
+        if run_loop && !*shutdown.borrow() {
+            self.print_update().await?;
         }
 
-        let mut results = vec![];
-        while let Some(res) = set.join_next().await {
-            let res = res?;
+        Ok(())
+    }
 
-            match res {
-                Ok(refresh_result) => results.push(refresh_result),
-                Err(err) => {
-                    error!("An error occurred while attempting to refresh playlists`: {err}")
-                }
-            }
+    /// Refreshes every profile whose one-time refresh is due, removing the scheduled refreshes so
+    /// that each fires only once
+    pub async fn refresh_scheduled_profiles(
+        &self,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<()> {
+        let profile_ids =
+            db::profiles::take_due_scheduled_refreshes(Timestamp::now().as_second()).await?;
+        if profile_ids.is_empty() {
+            return Ok(());
         }
 
+        let mut profiles = vec![];
+        for profile_id in profile_ids {
+            profiles.push(db::profiles::fetch_profile(profile_id).await?);
+        }
+        info!(
+            "Running scheduled refresh of {}",
+            profiles
+                .iter()
+                .map(|profile| profile.get_title())
+                .join(", ")
+        );
+
+        self.refresh_profiles(profiles, shutdown).await
+    }
+
+    /// Refreshes the given profiles concurrently and prints the results
+    async fn refresh_profiles(
+        &self,
+        profiles: Vec<Profile>,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<()> {
+        self.plex_client.clear_collection_artists_cache();
+        let plex_client = self.get_plex_client();
+        let results = run_refreshes(
+            &self.active_refreshes,
+            profiles,
+            self.config.get_refresh_retries(),
+            REFRESH_RETRY_BACKOFF,
+            shutdown,
+            |profile| {
+                update_playlist(
+                    plex_client.new_refresh_cycle(),
+                    profile,
+                    self.config.clone(),
+                )
+            },
+        )
+        .await?;
+
         info!(
             "<b>{} Profile{} updated at {}:</b>",
             results.len(),
@@ -157,24 +470,23 @@ impl ProfileManager {
             println!("{result}\n");
         }
 
-        if run_loop {
-            self.print_update().await?;
-        }
-
         Ok(())
     }
 
+    /// Creates a playlist for a new profile and saves the profile
+    ///
+    /// Asks for confirmation first unless `save` is set or stdin is not a terminal
     pub async fn create_playlist(
         &mut self,
         profile: &Profile,
         sections: &[ProfileSection],
+        save: bool,
     ) -> Result<()> {
-        let save = Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt("Would you like to save this profile?")
-            .default(true)
-            .interact()?;
+        let save = confirm_save(save, std::io::stdin().is_terminal())?;
 
-        if save {
+        if save && self.plex_client.is_read_only() {
+            info!("[READ-ONLY] Would have created a playlist and saved the profile");
+        } else if save {
             info!("Creating playlist in plex...");
             let playlist_id = self.plex_client.create_playlist(profile).await?;
             let playlist_id = PlexId::try_new(playlist_id)?;
@@ -191,7 +503,7 @@ impl ProfileManager {
             print_refresh_results(
                 profile_tracks.get_merged_tracks(),
                 profile.get_title(),
-                ProfileAction::Create,
+                ProfileAction::Create { save },
             );
         } else {
             info!("Playlist not saved");
@@ -200,9 +512,230 @@ impl ProfileManager {
         Ok(())
     }
 
-    pub async fn preview_playlist(&self, profile: &Profile) -> Result<()> {
+    /// Imports every profile file in a directory into the database
+    ///
+    /// Profiles whose title already exists in the database are skipped. An existing playlist with
+    /// a matching title is reused, otherwise a new playlist is created.
+    pub async fn import_profiles_from_dir(&mut self, dir: &str) -> Result<()> {
+        let profile_files = import::load_profiles_from_disk(Path::new(dir)).await?;
+        info!(
+            "Found {} profile file{} in `{dir}`",
+            profile_files.len(),
+            if profile_files.len() == 1 { "" } else { "s" }
+        );
+
+        let mut imported = 0;
+        let mut skipped = 0;
+        for ProfileFile { profile, sections } in profile_files {
+            let title = profile.get_title();
+            if db::profiles::fetch_profile_by_title(title).await?.is_some() {
+                warn!("Profile `{title}` already exists. Skipping.");
+                skipped += 1;
+                continue;
+            }
+
+            if self.plex_client.is_read_only() {
+                info!("[READ-ONLY] Would have imported profile `{title}`");
+                skipped += 1;
+                continue;
+            }
+
+            let playlist_id = match self.get_playlist_by_title(title) {
+                Some(playlist) => {
+                    info!("Reusing existing playlist for `{title}`...");
+                    playlist.get_id().to_owned()
+                }
+                None => {
+                    info!("Creating playlist for `{title}`...");
+                    self.plex_client.create_playlist(&profile).await?
+                }
+            };
+            let playlist_id = PlexId::try_new(playlist_id)?;
+
+            db::profiles::create_profile(playlist_id.as_str(), &profile, &sections).await?;
+            imported += 1;
+        }
+
+        info!("Imported {imported} profile(s), skipped {skipped} profile(s)");
+
+        Ok(())
+    }
+
+    /// Prints the filters, sorting, and track limit sent to plex for each section of a profile
+    pub async fn print_profile_filters(&self, profile: &Profile) -> Result<()> {
+        println!("{}", profile.get_title());
+
+        let limit = profile_tracks::get_section_track_limit(profile.get_time_limit() as f64);
+        for section in profile.fetch_sections().await? {
+            println!("\n  {}", section.get_section_type());
+            if !section.is_enabled() {
+                println!("    Disabled");
+                continue;
+            }
+
+            let filters =
+                profile_tracks::fetch_section_filters(self.get_plex_client(), profile, &section)
+                    .await?;
+            println!("    Filters:");
+            for (key, value) in filters.iter().sorted() {
+                println!("      {key}={value}");
+            }
+            println!("    Sort:  {}", section.get_sorting_vec().join(","));
+            println!(
+                "    Limit: {}",
+                limit.map_or("None".to_string(), |limit| limit.to_string())
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Builds a profile's playlist and exports its tracks to the given format
+    pub async fn export_playlist(
+        &self,
+        profile: &Profile,
+        format: ExportFormat,
+        include_art: bool,
+    ) -> Result<String> {
+        let plex_client = self.get_plex_client();
+        let profile_tracks = ProfileTracks::new(plex_client, profile).await?;
+        export::export_tracks(
+            profile_tracks.get_merged_tracks(),
+            format,
+            include_art.then_some(plex_client),
+        )
+    }
+
+    /// Builds a profile's tracks several times and prints how long each phase took
+    pub async fn advise_interval(&self, profile: &Profile) -> Result<()> {
+        info!(
+            "Building `{}` to measure its build time...",
+            profile.get_title()
+        );
+        let (_, timings) = ProfileTracks::new_timed(self.get_plex_client(), profile).await?;
+        let build_time = timings.fetch + timings.filter + timings.merge;
+
+        println!(
+            "{}",
+            IntervalAdvice::new(build_time, *profile.get_refresh_interval())
+        );
+
+        Ok(())
+    }
+
+    /// Lists the candidate tracks by an artist in each section of a profile and whether they
+    /// survive the section's artist limit
+    pub async fn print_artist_tracks(&self, profile: &Profile, artist_name: &str) -> Result<()> {
+        let artists = self.plex_client.search_for_artist(artist_name).await?;
+        let artist = artists
+            .iter()
+            .find(|artist| artist.get_title().eq_ignore_ascii_case(artist_name))
+            .or(artists.first())
+            .ok_or_else(|| anyhow!("No artist matching `{artist_name}` was found"))?;
+
+        info!("Building candidate tracks for `{}`...", profile.get_title());
+        let artist_tracks =
+            profile_tracks::fetch_artist_tracks(self.get_plex_client(), profile, artist.get_id())
+                .await?;
+
+        if artist_tracks.is_empty() {
+            println!(
+                "No candidate tracks by `{}` were found in `{}`",
+                artist.get_title(),
+                profile.get_title()
+            );
+        } else {
+            println!("{}", format_artist_tracks_table(&artist_tracks));
+        }
+
+        Ok(())
+    }
+
+    /// Prints the tracks shared by the playlists of every given profile
+    pub async fn print_overlap(&self, profiles: &[Profile]) -> Result<()> {
+        info!("Building {} profiles...", profiles.len());
+        let overlap = overlap::find_overlap(self.get_plex_client(), profiles).await?;
+        println!("{overlap}");
+
+        Ok(())
+    }
+
+    pub async fn bench_profile(&self, profile: &Profile, runs: usize, cached: bool) -> Result<()> {
+        info!(
+            "Benchmarking `{}` over {runs} run{}...",
+            profile.get_title(),
+            if runs == 1 { "" } else { "s" }
+        );
+        let timings = bench::run_bench(self.get_plex_client(), profile, runs, cached).await?;
+        println!("{}", BenchSummary::new(&timings));
+
+        Ok(())
+    }
+
+    pub async fn compare_profiles(&self, left: &Profile, right: &Profile) -> Result<()> {
+        let comparison = ProfileComparison::new(
+            left,
+            &left.fetch_sections().await?,
+            right,
+            &right.fetch_sections().await?,
+        )?;
+        println!("{comparison}");
+
+        Ok(())
+    }
+
+    /// Replaces a profile's playlist with the tracks from one of its snapshots, where `1` is the
+    /// most recent snapshot
+    pub async fn restore_snapshot(&self, profile: &Profile, snapshot: u32) -> Result<()> {
+        let Some(track_ids) =
+            db::profiles::fetch_playlist_snapshot(profile.get_profile_id(), snapshot).await?
+        else {
+            return Err(anyhow!(
+                "Snapshot {snapshot} of `{}` was not found",
+                profile.get_title()
+            ));
+        };
+
+        info!(
+            "Restoring {} tracks from snapshot {snapshot} of `{}`...",
+            track_ids.len(),
+            profile.get_title()
+        );
+        if !self.plex_client.is_read_only() {
+            db::profiles::clear_track_ids_hash(profile.get_profile_id()).await?;
+        }
+        replace_playlist_items(self.get_plex_client(), profile, &track_ids).await
+    }
+
+    pub async fn preview_playlist(
+        &self,
+        profile: &Profile,
+        count: usize,
+        relative_dates: bool,
+        by_artist: bool,
+        order_note: bool,
+    ) -> Result<()> {
         let profile_tracks = ProfileTracks::new(self.get_plex_client(), profile).await?;
-        profile_tracks.print_preview();
+        if by_artist {
+            profile_tracks.print_artist_counts();
+        } else {
+            profile_tracks.print_preview(count, relative_dates);
+        }
+
+        if order_note {
+            let sections =
+                db::profiles::fetch_profile_sections_for_profile(profile.get_profile_id()).await?;
+            match profile_tracks::item_order_reason(profile, &sections) {
+                Some(reason) => println!(
+                    "\nNote: `{}` relies on the order of its tracks, since {reason}. Plex shows playlists in this order unless the playlist is sorted by a column in plex, which hides the order hitomi built.",
+                    profile.get_title()
+                ),
+                None => println!(
+                    "\nNote: the tracks of `{}` are shuffled, so sorting the playlist in plex does not lose anything.",
+                    profile.get_title()
+                ),
+            }
+        }
 
         Ok(())
     }
@@ -210,14 +743,57 @@ impl ProfileManager {
 
 // UTILITY FUNCTIONS #############################################################
 
+/// Creates a playlist for a shadow profile and fills it with tracks, replacing the earlier shadows
+/// of the same profile
+///
+/// `shadow_ids` are the playlists recorded when the profile's earlier shadows were built. Each of
+/// them still in plex is deleted, even if the profile was renamed since, and no other playlist is
+/// ever replaced.
+async fn create_shadow_playlist(
+    plex_client: &PlexClient,
+    shadow: &Profile,
+    track_ids: &[String],
+    shadow_ids: &HashSet<String>,
+) -> Result<PlexId> {
+    for existing in plex_client
+        .get_playlists()
+        .iter()
+        .filter(|playlist| shadow_ids.contains(playlist.get_id()))
+    {
+        plex_client
+            .delete_playlist(&PlexId::try_new(existing.get_id())?)
+            .await?;
+    }
+
+    let playlist_id = PlexId::try_new(plex_client.create_playlist(shadow).await?)?;
+    plex_client
+        .add_items_to_playlist(&playlist_id, track_ids)
+        .await?;
+
+    Ok(playlist_id)
+}
+
+/// Returns whether a new profile should be saved, only prompting when `save` is not set and the
+/// session is interactive
+fn confirm_save(save: bool, interactive: bool) -> Result<bool> {
+    if save || !interactive {
+        return Ok(true);
+    }
+
+    let save = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Would you like to save this profile?")
+        .default(true)
+        .interact()?;
+
+    Ok(save)
+}
+
 fn print_refresh_results(tracks: &[Track], playlist_title: &str, action: ProfileAction) {
     let size = tracks.len();
 
-    let duration: i64 = tracks.iter().map(|t| t.get_track_duration()).sum();
-    let duration = Duration::from_millis(duration as u64);
-    let duration = humantime::format_duration(duration).to_string();
+    let duration = humantime::format_duration(tracks::total_duration(tracks)).to_string();
 
-    let action = if action == ProfileAction::Create {
+    let action = if matches!(action, ProfileAction::Create { .. }) {
         "created"
     } else {
         "updated"
@@ -232,34 +808,1168 @@ fn print_refresh_results(tracks: &[Track], playlist_title: &str, action: Profile
     );
 }
 
-async fn update_playlist(plex_client: PlexClient, profile: Profile) -> Result<RefreshResult> {
-    let profile_tracks = ProfileTracks::new(&plex_client, &profile).await?;
+/// Records the tracks used by a refresh if the profile avoids recent repeats
+async fn save_refresh_history(
+    plex_client: &PlexClient,
+    profile: &Profile,
+    profile_tracks: &ProfileTracks,
+) -> Result<()> {
+    let cycles = profile.get_avoid_recent_repeats_cycles();
+    if cycles == 0 || plex_client.is_read_only() {
+        return Ok(());
+    }
+
+    db::profiles::save_refresh_history(
+        profile.get_profile_id(),
+        &profile_tracks.get_track_guids(),
+        cycles,
+    )
+    .await
+}
+
+/// Refreshes the profiles concurrently and returns the results of the refreshes that succeeded
+///
+/// Profiles whose tracks could not be fetched are refreshed again once the others have finished,
+/// up to `retries` times, waiting `backoff` before the first retry and twice as long before each
+/// one after.
+async fn run_refreshes<F, Fut>(
+    active_refreshes: &ActiveRefreshes,
+    mut profiles: Vec<Profile>,
+    retries: u32,
+    backoff: Duration,
+    shutdown: &mut watch::Receiver<bool>,
+    refresh: F,
+) -> Result<Vec<RefreshResult>>
+where
+    F: Fn(Profile) -> Fut,
+    Fut: Future<Output = Result<RefreshResult>> + Send + 'static,
+{
+    let mut results = vec![];
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        let mut set = JoinSet::new();
+        for profile in profiles {
+            let title = profile.get_title().to_owned();
+            let refresh = refresh(profile.clone());
+            active_refreshes.spawn(&mut set, profile.get_profile_id(), &title, async move {
+                (profile, refresh.await)
+            });
+        }
+
+        let mut failed = vec![];
+        for (profile, res) in join_refresh_tasks(&mut set, shutdown, SHUTDOWN_TIMEOUT).await? {
+            match res {
+                Ok(refresh_result) => results.push(refresh_result),
+                Err(err) if attempt < retries && err.is::<SourceUnavailable>() => {
+                    warn!("{err:#}");
+                    failed.push(profile);
+                }
+                Err(err) => {
+                    error!("An error occurred while attempting to refresh playlists`: {err:#}")
+                }
+            }
+        }
+
+        if failed.is_empty() || *shutdown.borrow() {
+            return Ok(results);
+        }
+
+        attempt += 1;
+        info!(
+            "Retrying {} profile{} in {} (attempt {attempt} of {retries})...",
+            failed.len(),
+            if failed.len() == 1 { "" } else { "s" },
+            humantime::format_duration(delay)
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            Ok(()) = shutdown.changed() => {}
+        }
+        if *shutdown.borrow() {
+            return Ok(results);
+        }
+
+        delay *= 2;
+        profiles = failed;
+    }
+}
+
+/// Waits for every task in the set to finish and returns their results
+///
+/// Cancelled tasks are skipped. Once `shutdown` is signalled, the remaining tasks are given `timeout` to finish before they
+/// are aborted. Tasks are never spawned here, so no new refreshes begin after a shutdown.
+async fn join_refresh_tasks<T: 'static>(
+    set: &mut JoinSet<T>,
+    shutdown: &mut watch::Receiver<bool>,
+    timeout: Duration,
+) -> Result<Vec<T>> {
+    let mut results = vec![];
+
+    while !*shutdown.borrow() {
+        tokio::select! {
+            res = set.join_next() => match res {
+                Some(Err(err)) if err.is_cancelled() => {}
+                Some(res) => results.push(res?),
+                None => return Ok(results),
+            },
+            // Disabled if the sender is dropped, since a shutdown can no longer be requested
+            Ok(()) = shutdown.changed() => {}
+        }
+    }
+
+    if set.is_empty() {
+        return Ok(results);
+    }
+
+    info!(
+        "Shutdown requested. Waiting up to {} for {} refresh{} to finish...",
+        humantime::format_duration(timeout),
+        set.len(),
+        if set.len() == 1 { "" } else { "es" }
+    );
+    let drain = async {
+        while let Some(res) = set.join_next().await {
+            match res {
+                Err(err) if err.is_cancelled() => {}
+                res => results.push(res?),
+            }
+        }
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(timeout, drain).await {
+        Ok(res) => res?,
+        Err(_) => {
+            warn!(
+                "{} refresh{} did not finish in time and will be abandoned",
+                set.len(),
+                if set.len() == 1 { "" } else { "es" }
+            );
+            set.abort_all();
+        }
+    }
+
+    Ok(results)
+}
+
+/// Builds a table of each profile's refresh schedule, with the minutes from `now` until the
+/// next refresh
+fn format_next_refresh_table(profiles: &[Profile], now: i64) -> String {
+    let title_width = profiles
+        .iter()
+        .map(|profile| profile.get_title().len())
+        .max()
+        .unwrap_or_default()
+        .max("Profile".len());
+
+    let mut str = format!(
+        "{:<title_width$}  {:>7}  {:>12}  {:>8}  {:>8}  {:>7}",
+        "Profile", "Current", "Next Refresh", "In (min)", "Eligible", "Enabled"
+    );
+    for profile in profiles {
+        let minutes_until = ((profile.get_next_refresh_at() - now).max(0) + 59) / 60;
+        str += &format!(
+            "\n{:<title_width$}  {:>7}  {:>12}  {:>8}  {:>8}  {:>7}",
+            profile.get_title(),
+            profile.get_current_refresh_hour_minute(),
+            profile.get_next_refresh_hour_minute(),
+            minutes_until,
+            if profile.is_eligible_for_refresh() {
+                "yes"
+            } else {
+                "no"
+            },
+            if profile.get_enabled() { "yes" } else { "no" },
+        );
+    }
+
+    str
+}
+
+/// Formats the candidate tracks by an artist as a table
+fn format_artist_tracks_table(artist_tracks: &[ArtistTrack]) -> String {
+    let section_width = artist_tracks
+        .iter()
+        .map(|artist_track| artist_track.section_type.to_string().len())
+        .max()
+        .unwrap_or_default()
+        .max("Section".len());
+
+    let mut str = format!(
+        "{:<section_width$}  {:>5}  {:>7}  Track",
+        "Section", "Plays", "Kept"
+    );
+    for artist_track in artist_tracks {
+        str += &format!(
+            "\n{:<section_width$}  {:>5}  {:>7}  {}",
+            artist_track.section_type.to_string(),
+            artist_track.track.get_plays(),
+            if artist_track.kept { "yes" } else { "trimmed" },
+            artist_track.track.get_track_title(),
+        );
+    }
+
+    let kept = artist_tracks
+        .iter()
+        .filter(|artist_track| artist_track.kept)
+        .count();
+    str += &format!("\n\n{kept} of {} track(s) kept", artist_tracks.len());
+
+    str
+}
+
+/// Sorts profiles by the given field, using the title to break ties
+fn sort_profiles(profiles: &mut [Profile], sort_by: ProfileSortBy, reverse: bool) {
+    // Profiles without a track limit sort after every limited profile
+    let track_limit = |profile: &Profile| {
+        let limit = profile.get_track_limit();
+        (limit == 0, limit)
+    };
+
+    profiles.sort_by(|a, b| {
+        let ordering = match sort_by {
+            ProfileSortBy::Title => std::cmp::Ordering::Equal,
+            ProfileSortBy::NextRefresh => a.get_next_refresh_at().cmp(&b.get_next_refresh_at()),
+            ProfileSortBy::TrackLimit => track_limit(a).cmp(&track_limit(b)),
+            ProfileSortBy::Enabled => b.get_enabled().cmp(&a.get_enabled()),
+        };
+        ordering.then_with(|| {
+            a.get_title()
+                .to_lowercase()
+                .cmp(&b.get_title().to_lowercase())
+        })
+    });
+
+    if reverse {
+        profiles.reverse();
+    }
+}
+
+/// Returns the profiles whose playlist id cannot be found in the given playlists
+fn find_orphaned_profiles<'a>(profiles: &'a [Profile], playlists: &[Playlist]) -> Vec<&'a Profile> {
+    profiles
+        .iter()
+        .filter(|profile| {
+            !playlists
+                .iter()
+                .any(|playlist| playlist.get_id() == profile.get_playlist_id().as_str())
+        })
+        .collect()
+}
+
+async fn update_playlist(
+    plex_client: PlexClient,
+    profile: Profile,
+    config: Config,
+) -> Result<RefreshResult> {
+    if profile.is_summary_only() {
+        return update_playlist_summary(plex_client, profile).await;
+    }
+
+    let profile_tracks = match ProfileTracks::new(&plex_client, &profile).await {
+        Ok(profile_tracks) => profile_tracks,
+        Err(err) => {
+            let reason = skipped_refresh_reason(&err);
+            // Plex is likely failing already, so the summary must not replace the original error
+            if let Err(summary_err) =
+                summarize_skipped_refresh(&plex_client, &config, &profile, &reason).await
+            {
+                warn!(
+                    "Unable to update the summary of skipped profile `{}`: {summary_err}",
+                    profile.get_title()
+                );
+            }
+            return Err(err);
+        }
+    };
+
+    let num_valid = profile_tracks.get_num_valid();
+    let num_tracks = profile_tracks.get_merged_tracks().len();
+    let skip_reason = if !meets_minimum_valid_sections(&profile, num_valid) {
+        Some(format!("only {num_valid} section(s) returned tracks"))
+    } else if !meets_minimum_playlist_size(&profile, num_tracks)? {
+        Some(format!("only {num_tracks} track(s) were found"))
+    } else {
+        None
+    };
+
+    if let Some(skip_reason) = skip_reason {
+        summarize_skipped_refresh(&plex_client, &config, &profile, &skip_reason).await?;
+        let tracks = plex_client
+            .fetch_playlist_items(profile.get_playlist_id())
+            .await?;
+        return Ok(RefreshResult::new(
+            profile.get_title(),
+            &tracks,
+            ProfileAction::Update,
+        ));
+    }
+
     info!("Updating `{}` playlist...", profile.get_title());
 
+    let track_ids = profile_tracks.get_track_ids();
+    let previous_hash = db::profiles::fetch_track_ids_hash(profile.get_profile_id()).await?;
+    let hash = hash_track_ids(&track_ids);
+    if refresh_playlist_items(
+        &plex_client,
+        &config,
+        &profile,
+        &track_ids,
+        &hash,
+        previous_hash.as_deref(),
+    )
+    .await?
+        && !plex_client.is_read_only()
+    {
+        db::profiles::save_track_ids_hash(profile.get_profile_id(), &hash).await?;
+    }
+
+    plex_client
+        .update_summary(profile.get_playlist_id(), &build_summary(&profile))
+        .await?;
+    plex_client
+        .rename_playlist(profile.get_playlist_id(), profile.get_title())
+        .await?;
+
+    save_refresh_history(&plex_client, &profile, &profile_tracks).await?;
+    db::profiles::mark_profile_used(profile.get_profile_id()).await?;
+
+    let refresh_result = RefreshResult::new(
+        profile.get_title(),
+        profile_tracks.get_merged_tracks(),
+        ProfileAction::Update,
+    );
+
+    Ok(refresh_result)
+}
+
+/// Checks the number of sections that returned tracks against the profile's minimum, logging a
+/// warning if the refresh should be skipped
+fn meets_minimum_valid_sections(profile: &Profile, num_valid: usize) -> bool {
+    let minimum = profile.get_minimum_valid_sections() as usize;
+    if num_valid >= minimum {
+        return true;
+    }
+
+    warn!(
+        "<b>Only {num_valid} section(s) of `{}` returned tracks, fewer than the minimum of {minimum}. Skipping update.</b>",
+        profile.get_title()
+    );
+    false
+}
+
+/// Checks the number of merged tracks against the profile's minimum playlist size
+///
+/// Returns `false` if the refresh should be skipped, or an error if the profile is configured to
+/// fail when the playlist would be too small.
+fn meets_minimum_playlist_size(profile: &Profile, num_tracks: usize) -> Result<bool> {
+    let minimum = profile.get_minimum_playlist_size() as usize;
+    if num_tracks >= minimum {
+        return Ok(true);
+    }
+
+    if profile.get_fail_below_minimum_size() {
+        return Err(anyhow!(
+            "`{}` would contain {num_tracks} track(s), fewer than the minimum of {minimum}",
+            profile.get_title()
+        ));
+    }
+
+    warn!(
+        "<b>`{}` would contain {num_tracks} track(s), fewer than the minimum of {minimum}. Skipping update.</b>",
+        profile.get_title()
+    );
+    Ok(false)
+}
+
+/// Hashes a playlist's track ids with 64-bit FNV-1a, which is stable across builds so that the
+/// stored hash stays comparable
+fn hash_track_ids(track_ids: &[String]) -> String {
+    let hash = track_ids
+        .iter()
+        .flat_map(|id| id.as_bytes().iter().chain(b","))
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    format!("{hash:016x}")
+}
+
+/// Snapshots and replaces a playlist's items, unless skipping unchanged refreshes is enabled and
+/// `hash` matches the hash stored by the last refresh
+///
+/// Returns `false` if the playlist's items were left untouched.
+async fn refresh_playlist_items(
+    plex_client: &PlexClient,
+    config: &Config,
+    profile: &Profile,
+    track_ids: &[String],
+    hash: &str,
+    previous_hash: Option<&str>,
+) -> Result<bool> {
+    if config.get_skip_unchanged_refreshes() && previous_hash == Some(hash) {
+        info!(
+            "Tracks of `{}` are unchanged. Skipping update of playlist items.",
+            profile.get_title()
+        );
+        return Ok(false);
+    }
+
+    save_playlist_snapshot(plex_client, profile).await?;
+    replace_playlist_items(plex_client, profile, track_ids).await?;
+
+    Ok(true)
+}
+
+/// Clears a playlist and fills it with the given tracks
+async fn replace_playlist_items(
+    plex_client: &PlexClient,
+    profile: &Profile,
+    track_ids: &[String],
+) -> Result<()> {
     info!("Wiping destination playlist...");
     plex_client
         .clear_playlist(profile.get_playlist_id())
         .await?;
 
     info!("Updating destination playlist...");
-    plex_client
-        .add_items_to_playlist(profile.get_playlist_id(), &profile_tracks.get_track_ids())
-        .await?;
+    add_items_with_retry(plex_client, profile, track_ids, ADD_ITEMS_BACKOFF).await
+}
+
+/// Saves the playlist's current tracks before it is refreshed, if the profile keeps snapshots
+async fn save_playlist_snapshot(plex_client: &PlexClient, profile: &Profile) -> Result<()> {
+    let keep = profile.get_snapshot_count();
+    if keep == 0 || plex_client.is_read_only() {
+        return Ok(());
+    }
+
+    let track_ids = plex_client
+        .fetch_playlist_items(profile.get_playlist_id())
+        .await?
+        .iter()
+        .map(|track| track.get_id().to_owned())
+        .collect_vec();
+    if track_ids.is_empty() {
+        return Ok(());
+    }
+
+    db::profiles::save_playlist_snapshot(profile.get_profile_id(), &track_ids, keep).await
+}
+
+/// Adds tracks to a playlist that was just cleared, retrying with an increasing delay on failure
+///
+/// If every attempt fails, the playlist is left empty until the next refresh.
+async fn add_items_with_retry(
+    plex_client: &PlexClient,
+    profile: &Profile,
+    track_ids: &[String],
+    backoff: Duration,
+) -> Result<()> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match plex_client
+            .add_items_to_playlist(profile.get_playlist_id(), track_ids)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(err) if track_ids.is_empty() || attempt >= ADD_ITEMS_ATTEMPTS => {
+                error!(
+                    "<b>The playlist for `{}` was cleared but no tracks could be added. It will remain empty until the next refresh.</b>",
+                    profile.get_title()
+                );
+                return Err(err);
+            }
+            Err(err) => {
+                warn!(
+                    "Attempt {attempt} of {ADD_ITEMS_ATTEMPTS} to add tracks to `{}` failed: {err}\nRetrying in {}...",
+                    profile.get_title(),
+                    humantime::format_duration(delay)
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
 
-    let summary = format!(
+fn build_summary(profile: &Profile) -> String {
+    format!(
         "{}\n{}",
         profile.get_next_refresh_str(),
         profile.get_summary()
-    );
+    )
+}
+
+/// The summary written when a refresh is skipped, noting why and when it was skipped
+fn build_skipped_summary(profile: &Profile, reason: &str, skipped_at: &Zoned) -> String {
+    format!(
+        "SKIPPED: {reason} at {}\nNEXT UPDATE: {}\n{}",
+        skipped_at.strftime("%F %T"),
+        profile.get_next_refresh_hour_minute(),
+        profile.get_summary()
+    )
+}
+
+/// Describes why a refresh whose tracks could not be built was skipped
+///
+/// Only a failed fetch is blamed on the source. Any other error, such as an invalid merge order or
+/// an empty section, is reported as it is.
+fn skipped_refresh_reason(err: &anyhow::Error) -> String {
+    if err.is::<SourceUnavailable>() {
+        "the source could not be read".to_string()
+    } else {
+        err.to_string()
+    }
+}
+
+/// Notes a skipped refresh in the playlist summary, if enabled in the config
+///
+/// The playlist's tracks are left untouched.
+async fn summarize_skipped_refresh(
+    plex_client: &PlexClient,
+    config: &Config,
+    profile: &Profile,
+    reason: &str,
+) -> Result<()> {
+    if !config.get_summarize_skipped_refreshes() {
+        return Ok(());
+    }
+
     plex_client
-        .update_summary(profile.get_playlist_id(), &summary)
+        .update_summary(
+            profile.get_playlist_id(),
+            &build_skipped_summary(profile, reason, &Zoned::now()),
+        )
+        .await
+}
+
+/// Updates only the summary of a playlist, leaving its tracks untouched
+async fn update_playlist_summary(
+    plex_client: PlexClient,
+    profile: Profile,
+) -> Result<RefreshResult> {
+    info!("Updating summary of `{}` playlist...", profile.get_title());
+    plex_client
+        .update_summary(profile.get_playlist_id(), &build_summary(&profile))
         .await?;
 
-    let refresh_result = RefreshResult::new(
+    let tracks = plex_client
+        .fetch_playlist_items(profile.get_playlist_id())
+        .await?;
+
+    Ok(RefreshResult::new(
         profile.get_title(),
-        profile_tracks.get_merged_tracks(),
+        &tracks,
         ProfileAction::Update,
-    );
+    ))
+}
 
-    Ok(refresh_result)
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use reqwest::Url;
+
+    use crate::config::ConfigBuilder;
+    use crate::plex::mock_server::MockServer;
+    use crate::profiles::profile::ProfileBuilder;
+    use crate::types::Title;
+
+    use super::*;
+
+    /// Starts building a config with the settings every config requires
+    fn build_config() -> ConfigBuilder {
+        let mut builder = ConfigBuilder::default();
+        builder
+            .plex_token(String::from("NJlYINZmB-Hdy78xubjR"))
+            .plex_url(String::from("http://127.0.0.1:32400"))
+            .primary_section_id(1);
+        builder
+    }
+
+    #[tokio::test]
+    async fn test_shadow_playlist_is_distinct() {
+        let server = MockServer::start(|path| {
+            if path.starts_with("/library/sections?") {
+                r#"{"MediaContainer": {"Directory": [{"key": "0", "title": "Music", "type": "artist"}]}}"#
+                    .to_string()
+            } else if path.starts_with("/playlists?") {
+                r#"{"MediaContainer": {"Metadata": [{"ratingKey": "654321"}]}}"#.to_string()
+            } else {
+                r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+            }
+        })
+        .await;
+        let plex_client = server.client();
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Daily Mix").unwrap())
+            .playlist_id(PlexId::try_new("111111").unwrap())
+            .build()
+            .unwrap();
+        let shadow = profile.to_shadow(None, None).unwrap();
+
+        let playlist_id = create_shadow_playlist(
+            &plex_client,
+            &shadow,
+            &["100001".to_string()],
+            &HashSet::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!("654321", playlist_id.as_str());
+        assert_ne!(profile.get_playlist_id(), &playlist_id);
+        let requests = server.get_requests();
+        assert!(requests
+            .iter()
+            .any(|request| request.starts_with("PUT /playlists/654321/items?")));
+        assert!(!requests.iter().any(|request| request.contains("111111")));
+    }
+
+    #[tokio::test]
+    async fn test_shadow_playlist_replaces_recorded_shadows() {
+        let server = MockServer::start(|path| {
+            if path.starts_with("/library/sections?") {
+                r#"{"MediaContainer": {"Directory": [{"key": "0", "title": "Music", "type": "artist"}]}}"#
+                    .to_string()
+            } else if path.starts_with("/playlists?") {
+                r#"{"MediaContainer": {"Metadata": [{"ratingKey": "654321"}]}}"#.to_string()
+            } else {
+                r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+            }
+        })
+        .await;
+        let build_playlist = |id: &str, title: &str| -> Playlist {
+            serde_json::from_value(serde_json::json!({
+                "ratingKey": id,
+                "key": format!("/playlists/{id}/items"),
+                "title": title,
+                "summary": "",
+                "leafCount": 0,
+            }))
+            .unwrap()
+        };
+        let plex_client = server
+            .client_builder()
+            .playlists(vec![
+                // Recorded before the profile was renamed
+                build_playlist("222222", "Old Mix (shadow)"),
+                // Not created by hitomi
+                build_playlist("333333", "Daily Mix (shadow)"),
+            ])
+            .build()
+            .unwrap();
+        let shadow = ProfileBuilder::default()
+            .title(Title::try_new("Daily Mix").unwrap())
+            .build()
+            .unwrap()
+            .to_shadow(None, None)
+            .unwrap();
+
+        create_shadow_playlist(
+            &plex_client,
+            &shadow,
+            &["100001".to_string()],
+            &HashSet::from(["222222".to_string()]),
+        )
+        .await
+        .unwrap();
+
+        let requests = server.get_requests();
+        assert!(requests
+            .iter()
+            .any(|request| request.starts_with("DELETE /playlists/222222?")));
+        assert!(!requests.iter().any(|request| request.contains("333333")));
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_refresh_skips_playlist_items() {
+        let server =
+            MockServer::start(|_| r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string())
+                .await;
+        let plex_client = server.client();
+        let config = build_config()
+            .skip_unchanged_refreshes(true)
+            .build()
+            .unwrap();
+        let profile = ProfileBuilder::default()
+            .playlist_id(PlexId::try_new("111111").unwrap())
+            .build()
+            .unwrap();
+        let track_ids = vec!["100001".to_string(), "100002".to_string()];
+        let hash = hash_track_ids(&track_ids);
+
+        let refreshed = refresh_playlist_items(
+            &plex_client,
+            &config,
+            &profile,
+            &track_ids,
+            &hash,
+            Some(&hash),
+        )
+        .await
+        .unwrap();
+        assert!(!refreshed);
+        assert_eq!(0, server.get_request_count());
+
+        let changed = hash_track_ids(&track_ids[..1]);
+        let refreshed = refresh_playlist_items(
+            &plex_client,
+            &config,
+            &profile,
+            &track_ids,
+            &hash,
+            Some(&changed),
+        )
+        .await
+        .unwrap();
+        assert!(refreshed);
+        assert!(server
+            .get_requests()
+            .iter()
+            .any(|request| request.starts_with("DELETE /playlists/111111/items")));
+    }
+
+    #[test]
+    fn test_hash_track_ids() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!("cbf29ce484222325", hash_track_ids(&[]));
+        assert_eq!(
+            hash_track_ids(&ids(&["100001", "100002"])),
+            hash_track_ids(&ids(&["100001", "100002"]))
+        );
+        assert_ne!(
+            hash_track_ids(&ids(&["100001", "100002"])),
+            hash_track_ids(&ids(&["100002", "100001"]))
+        );
+        assert_ne!(
+            hash_track_ids(&ids(&["1000011", "00002"])),
+            hash_track_ids(&ids(&["100001", "100002"]))
+        );
+    }
+
+    #[test]
+    fn test_skipped_refresh_reason() {
+        let source_err = anyhow!("timed out").context(SourceUnavailable("Flaky".to_string()));
+        assert_eq!(
+            "the source could not be read",
+            skipped_refresh_reason(&source_err)
+        );
+
+        let profile_err = anyhow!("The `Unplayed` section of `Flaky` returned no tracks");
+        assert_eq!(
+            "The `Unplayed` section of `Flaky` returned no tracks",
+            skipped_refresh_reason(&profile_err)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skipped_refresh_writes_summary() {
+        let server = MockServer::start(|_| String::new()).await;
+        let profile = ProfileBuilder::default()
+            .playlist_id(PlexId::try_new("111111").unwrap())
+            .summary("Tracks I have not heard in a while".to_string())
+            .build()
+            .unwrap();
+
+        // Nothing is written unless enabled
+        let plex_client = server.client();
+        summarize_skipped_refresh(
+            &plex_client,
+            &Config::default(),
+            &profile,
+            "only 0 track(s) were found",
+        )
+        .await
+        .unwrap();
+        assert_eq!(0, server.get_request_count());
+
+        let config = build_config()
+            .summarize_skipped_refreshes(true)
+            .build()
+            .unwrap();
+        summarize_skipped_refresh(
+            &plex_client,
+            &config,
+            &profile,
+            "only 0 track(s) were found",
+        )
+        .await
+        .unwrap();
+
+        let requests = server.get_requests();
+        assert_eq!(1, requests.len());
+        let (method, path) = requests[0].split_once(' ').unwrap();
+        assert_eq!("PUT", method);
+        let url = Url::parse(&format!("{}{}", server.get_url(), &path[1..])).unwrap();
+        assert_eq!("/playlists/111111", url.path());
+        let summary = url
+            .query_pairs()
+            .find(|(key, _)| key == "summary")
+            .unwrap()
+            .1
+            .to_string();
+        let (skipped, rest) = summary.split_once('\n').unwrap();
+        assert!(skipped.starts_with("SKIPPED: only 0 track(s) were found at "));
+        assert!(rest.starts_with("NEXT UPDATE: "));
+        assert!(rest.ends_with("\nTracks I have not heard in a while"));
+    }
+
+    #[test]
+    fn test_build_skipped_summary() {
+        let profile = ProfileBuilder::default()
+            .summary("Tracks I have not heard in a while".to_string())
+            .build()
+            .unwrap();
+        let skipped_at: Zoned = "2024-07-01T08:30:00[UTC]".parse().unwrap();
+
+        let summary = build_skipped_summary(&profile, "only 2 track(s) were found", &skipped_at);
+
+        assert_eq!(
+            format!(
+                "SKIPPED: only 2 track(s) were found at 2024-07-01 08:30:00\nNEXT UPDATE: {}\nTracks I have not heard in a while",
+                profile.get_next_refresh_hour_minute()
+            ),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_confirm_save_skips_prompt() {
+        // Prompting would fail since tests do not run in a terminal
+        assert!(confirm_save(true, true).unwrap());
+        assert!(confirm_save(false, false).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_add_items_retries_after_failure() {
+        static ADD_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+        // The first attempt receives an invalid response
+        let server = MockServer::start(|_| {
+            if ADD_ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+                "Internal Server Error".to_string()
+            } else {
+                r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+            }
+        })
+        .await;
+        let profile = ProfileBuilder::default()
+            .playlist_id(PlexId::try_new("123456").unwrap())
+            .build()
+            .unwrap();
+
+        let result = add_items_with_retry(
+            &server.client(),
+            &profile,
+            &["1001".to_string()],
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(2, server.get_request_count());
+    }
+
+    #[tokio::test]
+    async fn test_summary_only_does_not_change_items() {
+        let server = MockServer::start(|path| {
+            if path.contains("/items") {
+                r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+            } else {
+                String::new()
+            }
+        })
+        .await;
+        let profile = ProfileBuilder::default()
+            .playlist_id(PlexId::try_new("123456").unwrap())
+            .summary_only(true)
+            .build()
+            .unwrap();
+
+        update_playlist(server.client(), profile, Config::default())
+            .await
+            .unwrap();
+
+        let requests = server.get_requests();
+        assert!(requests
+            .iter()
+            .any(|r| r.starts_with("PUT /playlists/123456?")));
+        assert!(!requests
+            .iter()
+            .any(|r| r.contains("/items") && !r.starts_with("GET ")));
+    }
+
+    #[tokio::test]
+    async fn test_replace_playlist_items_with_snapshot() {
+        let server = MockServer::start(|path| {
+            if path.contains("/items?uri") {
+                r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+            } else {
+                String::new()
+            }
+        })
+        .await;
+        let profile = ProfileBuilder::default()
+            .playlist_id(PlexId::try_new("123456").unwrap())
+            .build()
+            .unwrap();
+        let snapshot = ["1001".to_string(), "1002".to_string()];
+
+        replace_playlist_items(&server.client(), &profile, &snapshot)
+            .await
+            .unwrap();
+
+        let requests = server.get_requests();
+        assert_eq!(2, requests.len());
+        assert!(requests[0].starts_with("DELETE /playlists/123456/items"));
+        assert!(requests[1].starts_with("PUT /playlists/123456/items?"));
+        assert!(requests[1].contains("1001%2C1002"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_retried() {
+        let (_tx, mut rx) = watch::channel(false);
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Flaky").unwrap())
+            .build()
+            .unwrap();
+
+        // Fails to fetch the tracks the first `failures` times
+        let refresh = |attempts: &Arc<AtomicUsize>, failures: usize| {
+            let attempts = attempts.clone();
+            move |profile: Profile| {
+                let attempts = attempts.clone();
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                        return Err(anyhow!("timed out")
+                            .context(SourceUnavailable(profile.get_title().to_owned())));
+                    }
+                    Ok(RefreshResult::new(
+                        profile.get_title(),
+                        &[],
+                        ProfileAction::Update,
+                    ))
+                }
+            }
+        };
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let results = run_refreshes(
+            &ActiveRefreshes::default(),
+            vec![profile.clone()],
+            1,
+            Duration::from_millis(1),
+            &mut rx,
+            refresh(&attempts, 1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+
+        // Retries stop once they are exhausted
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let results = run_refreshes(
+            &ActiveRefreshes::default(),
+            vec![profile],
+            1,
+            Duration::from_millis(1),
+            &mut rx,
+            refresh(&attempts, 2),
+        )
+        .await
+        .unwrap();
+        assert!(results.is_empty());
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_join_refresh_tasks_drains_after_shutdown() {
+        let (tx, mut rx) = watch::channel(false);
+        let mut set = JoinSet::new();
+        for i in 0..3 {
+            set.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                i
+            });
+        }
+
+        // Request a shutdown while every task is still running
+        tx.send(true).unwrap();
+        let results = join_refresh_tasks(&mut set, &mut rx, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(vec![0, 1, 2], results.into_iter().sorted().collect_vec());
+    }
+
+    #[tokio::test]
+    async fn test_join_refresh_tasks_aborts_after_timeout() {
+        let (tx, mut rx) = watch::channel(false);
+        let mut set = JoinSet::new();
+        set.spawn(async { 1 });
+        set.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            2
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        tx.send(true).unwrap();
+        let results = join_refresh_tasks(&mut set, &mut rx, Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        assert_eq!(vec![1], results);
+    }
+
+    #[test]
+    fn test_sort_profiles_by_next_refresh() {
+        let mut profiles = [("Later", 300), ("Soonest", 100), ("Soon", 200)]
+            .into_iter()
+            .map(|(title, next_refresh_at)| {
+                ProfileBuilder::default()
+                    .title(Title::try_new(title).unwrap())
+                    .next_refresh_at(next_refresh_at)
+                    .build()
+                    .unwrap()
+            })
+            .collect_vec();
+
+        sort_profiles(&mut profiles, ProfileSortBy::NextRefresh, false);
+        assert_eq!(
+            vec!["Soonest", "Soon", "Later"],
+            profiles.iter().map(|p| p.get_title()).collect_vec()
+        );
+
+        sort_profiles(&mut profiles, ProfileSortBy::NextRefresh, true);
+        assert_eq!(
+            vec!["Later", "Soon", "Soonest"],
+            profiles.iter().map(|p| p.get_title()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_sort_profiles_by_track_limit() {
+        let mut profiles = [("Unlimited", 0), ("Large", 500), ("Small", 100)]
+            .into_iter()
+            .map(|(title, track_limit)| {
+                ProfileBuilder::default()
+                    .title(Title::try_new(title).unwrap())
+                    .track_limit(track_limit)
+                    .build()
+                    .unwrap()
+            })
+            .collect_vec();
+
+        sort_profiles(&mut profiles, ProfileSortBy::TrackLimit, false);
+        assert_eq!(
+            vec!["Small", "Large", "Unlimited"],
+            profiles.iter().map(|p| p.get_title()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_format_next_refresh_table() {
+        let now = 1_700_000_000;
+        let profiles = [("Morning Mix", 90, true), ("Night", 600, false)]
+            .into_iter()
+            .map(|(title, seconds_until, enabled)| {
+                ProfileBuilder::default()
+                    .title(Title::try_new(title).unwrap())
+                    .enabled(enabled)
+                    .current_refresh(now - 30)
+                    .next_refresh_at(now + seconds_until)
+                    .build()
+                    .unwrap()
+            })
+            .collect_vec();
+
+        let table = format_next_refresh_table(&profiles, now);
+        let lines = table.lines().collect_vec();
+        assert_eq!(3, lines.len());
+        assert_eq!(
+            "Profile      Current  Next Refresh  In (min)  Eligible  Enabled",
+            lines[0]
+        );
+        assert_eq!(
+            format!(
+                "Morning Mix    {}         {}         2        no      yes",
+                profiles[0].get_current_refresh_hour_minute(),
+                profiles[0].get_next_refresh_hour_minute()
+            ),
+            lines[1]
+        );
+        assert_eq!(
+            format!(
+                "Night          {}         {}        10        no       no",
+                profiles[1].get_current_refresh_hour_minute(),
+                profiles[1].get_next_refresh_hour_minute()
+            ),
+            lines[2]
+        );
+    }
+
+    #[test]
+    fn test_find_orphaned_profiles() {
+        // The default playlist id is `123456`
+        let playlists = vec![Playlist::default()];
+        let profiles = vec![
+            ProfileBuilder::default()
+                .playlist_id(PlexId::try_new("123456").unwrap())
+                .build()
+                .unwrap(),
+            ProfileBuilder::default()
+                .playlist_id(PlexId::try_new("654321").unwrap())
+                .build()
+                .unwrap(),
+        ];
+
+        let orphaned = find_orphaned_profiles(&profiles, &playlists);
+        assert_eq!(1, orphaned.len());
+        assert_eq!("654321", orphaned[0].get_playlist_id().as_str());
+    }
+
+    #[test]
+    fn test_meets_minimum_playlist_size() {
+        let skip = ProfileBuilder::default()
+            .minimum_playlist_size(10)
+            .build()
+            .unwrap();
+        assert!(meets_minimum_playlist_size(&skip, 10).unwrap());
+        assert!(!meets_minimum_playlist_size(&skip, 9).unwrap());
+
+        let fail = ProfileBuilder::default()
+            .minimum_playlist_size(10)
+            .fail_below_minimum_size(true)
+            .build()
+            .unwrap();
+        assert!(meets_minimum_playlist_size(&fail, 10).unwrap());
+        assert!(meets_minimum_playlist_size(&fail, 9).is_err());
+
+        let disabled = ProfileBuilder::default().build().unwrap();
+        assert!(meets_minimum_playlist_size(&disabled, 0).unwrap());
+    }
+
+    #[test]
+    fn test_meets_minimum_valid_sections() {
+        let profile = ProfileBuilder::default().build().unwrap();
+        assert_eq!(1, profile.get_minimum_valid_sections());
+        assert!(meets_minimum_valid_sections(&profile, 1));
+        assert!(!meets_minimum_valid_sections(&profile, 0));
+
+        let profile = ProfileBuilder::default()
+            .minimum_valid_sections(2)
+            .build()
+            .unwrap();
+        assert!(meets_minimum_valid_sections(&profile, 3));
+        assert!(meets_minimum_valid_sections(&profile, 2));
+        assert!(!meets_minimum_valid_sections(&profile, 1));
+    }
 }
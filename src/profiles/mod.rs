@@ -1,17 +1,39 @@
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString, FromRepr, VariantNames};
 
+use crate::export::ExportFormat;
+use crate::output::{JsonStyle, OutputFormat};
+
+pub mod active;
+mod advise;
+mod bench;
+mod compare;
+pub mod derived;
+mod import;
 pub mod manager;
+mod overlap;
 pub mod profile;
 pub mod profile_section;
 mod profile_tracks;
 mod refresh_result;
+pub mod simulate;
+pub mod track_source;
 pub mod wizards;
 
 /// Divisors of 60
 static VALID_INTERVALS: [u32; 10] = [2, 3, 4, 5, 6, 10, 12, 15, 20, 30];
 
+/// The default order in which sections are merged into a playlist
+pub static DEFAULT_MERGE_ORDER: [SectionType; 3] = [
+    SectionType::Unplayed,
+    SectionType::LeastPlayed,
+    SectionType::Oldest,
+];
+
+/// The default number of tracks displayed when previewing a playlist
+pub const PREVIEW_COUNT: usize = 25;
+
 #[derive(
     Clone,
     Copy,
@@ -40,6 +62,238 @@ pub enum SectionType {
     Oldest,
 }
 
+/// The fields by which the profile list can be sorted
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ProfileSortBy {
+    /// Alphabetically by title
+    #[default]
+    Title,
+    /// The time at which the profile next refreshes, soonest first
+    NextRefresh,
+    /// The track limit of the profile's playlist, smallest first and unlimited profiles last
+    TrackLimit,
+    /// Enabled profiles first
+    Enabled,
+}
+
+/// Determines how tracks are favored when shuffling tracks within a group
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+    VariantNames,
+)]
+#[sqlx(type_name = "randomization_bias")]
+pub enum RandomizationBias {
+    /// Every track is equally likely to be placed first
+    #[default]
+    Uniform,
+    /// Tracks that were recently added to the library are slightly more likely to be placed first
+    #[strum(to_string = "Favor Recent")]
+    FavorRecent,
+    /// Tracks with a higher rating are slightly more likely to be placed first
+    #[strum(to_string = "Favor Rating")]
+    FavorRating,
+}
+
+/// Determines how oldest tracks are grouped by their last played date when randomizing
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+    VariantNames,
+)]
+#[sqlx(type_name = "oldest_bucket")]
+pub enum OldestBucket {
+    /// Tracks last played in the same year are grouped together (e.g., `2024`)
+    Year,
+    /// Tracks last played in the same year and month are grouped together (e.g., `2024-07`)
+    #[default]
+    #[strum(to_string = "Year and Month")]
+    YearMonth,
+    /// Tracks last played in the same ISO week are grouped together (e.g., `2024-W27`)
+    #[strum(to_string = "Year and Week")]
+    YearWeek,
+}
+
+/// Determines which version of a track is kept when deduplicating by title and artist, if both
+/// explicit and clean versions are available
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+    VariantNames,
+)]
+#[sqlx(type_name = "content_rating_preference")]
+pub enum ContentRatingPreference {
+    /// Either version may be kept
+    #[default]
+    #[strum(to_string = "No Preference")]
+    NoPreference,
+    /// The explicit version is kept
+    #[strum(to_string = "Prefer Explicit")]
+    PreferExplicit,
+    /// The clean version is kept
+    #[strum(to_string = "Prefer Clean")]
+    PreferClean,
+}
+
+/// Determines which fields of a track must match for tracks to be considered duplicates when
+/// deduplicating by title and artist
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+    VariantNames,
+)]
+#[sqlx(type_name = "dedup_key")]
+pub enum DedupKey {
+    /// Tracks with the same title by the same artist are duplicates, so covers by other artists
+    /// are kept
+    #[default]
+    #[strum(to_string = "Title and Artist")]
+    TitleArtist,
+    /// Tracks with the same title are duplicates, regardless of the artist
+    #[strum(to_string = "Title Only")]
+    TitleOnly,
+    /// Tracks with the same Plex GUID are duplicates
+    #[strum(to_string = "GUID Only")]
+    GuidOnly,
+}
+
+/// The duration in milliseconds assumed for tracks without one under
+/// [`MissingDurationPolicy::AssumeDefault`]
+pub const ASSUMED_TRACK_DURATION_MS: i64 = 4 * 60 * 1000;
+
+/// Determines how tracks that plex reports without a duration are counted against a section's
+/// time limit
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+    VariantNames,
+)]
+#[sqlx(type_name = "missing_duration_policy")]
+pub enum MissingDurationPolicy {
+    /// Tracks without a duration are kept and take up no time
+    #[default]
+    #[strum(to_string = "Count as Zero")]
+    CountAsZero,
+    /// Tracks without a duration are removed from the section
+    Drop,
+    /// Tracks without a duration are kept and counted as four minutes long
+    #[strum(to_string = "Assume Four Minutes")]
+    AssumeDefault,
+}
+
+/// Determines what happens when an enabled section of a profile returns no tracks
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    VariantNames,
+)]
+pub enum EmptySectionBehavior {
+    /// The empty section is silently skipped when merging
+    #[default]
+    Skip,
+    /// A warning is logged and the empty section is skipped when merging
+    Warn,
+    /// The refresh fails
+    Fail,
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    VariantNames,
+)]
+pub enum MergeStrategy {
+    /// Alternates between sections one track at a time
+    #[default]
+    Interleave,
+    /// Appends each section in full, one after another
+    Concatenate,
+}
+
+/// Determines how sections are kept even with each other when interleaving
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    FromRepr,
+    PartialEq,
+    Serialize,
+    VariantNames,
+)]
+pub enum BalanceBy {
+    /// Each section contributes one track per round
+    #[default]
+    TrackCount,
+    /// The next track comes from the section that has contributed the least playtime so far, so
+    /// that a section of long tracks does not dominate the playlist
+    Duration,
+}
+
 #[derive(
     Clone,
     Copy,
@@ -60,22 +314,190 @@ pub enum ProfileSource {
     // Playlist,
     #[strum(to_string = "Single Artist")]
     SingleArtist,
+    Hub,
 }
 
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum ProfileAction {
+    /// Verify that each profile's playlist still exists on the plex server
+    Audit {
+        /// Recreate any missing playlists and update the stored playlist ids
+        #[arg(long, default_value_t = false)]
+        fix: bool,
+    },
     /// Create a new profile
-    Create,
+    Create {
+        /// Save the profile without asking for confirmation
+        #[arg(long, alias = "no-confirm", default_value_t = false)]
+        save: bool,
+    },
     /// Delete the playlist
     Delete,
     /// Edit an existing profile
     Edit,
+    /// Export the tracks of a profile's generated playlist to a file
+    Export {
+        /// The id or title of the profile
+        title: String,
+        /// The format of the exported file
+        #[arg(long, value_enum, default_value_t = ExportFormat::default())]
+        format: ExportFormat,
+        /// The file to write the export to. If not provided, the export is printed to the console
+        #[arg(long)]
+        output: Option<String>,
+        /// Include the urls of each track's artwork and its album and artist artwork. The urls
+        /// contain your plex token.
+        #[arg(long, default_value_t = false)]
+        include_art: bool,
+    },
+    /// Build a profile once and recommend a refresh interval based on how long the build took
+    Advise {
+        /// The id or title of the profile
+        title: String,
+    },
+    /// List the candidate tracks by an artist in each section of a profile and whether they
+    /// survive the section's limit on tracks by a single artist
+    ArtistTracks {
+        /// The id or title of the profile
+        title: String,
+        /// The name of the artist
+        artist_name: String,
+    },
+    /// Build a profile's tracks several times and report how long each phase took
+    Bench {
+        /// The id or title of the profile
+        title: String,
+        /// The number of times to build the profile's tracks
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+        /// Fetch tracks from plex once and reuse them for every run
+        #[arg(long, default_value_t = false)]
+        cached: bool,
+    },
+    /// Cancel a profile's refresh that is underway while `run --run-loop` is running
+    Cancel {
+        /// The id or title of the profile
+        title: String,
+    },
+    /// Print a field-by-field comparison of two profiles and their sections
+    Compare {
+        /// The id or title of the first profile
+        title_a: String,
+        /// The id or title of the second profile
+        title_b: String,
+    },
+    /// Display the filters and sorting sent to plex for each section of a profile
+    Filters {
+        /// The id or title of the profile
+        title: String,
+    },
+    /// Import profiles stored as JSON files in a directory into the database
+    ImportDir {
+        /// The directory containing the profile files
+        dir: String,
+    },
     /// List existing profiles found on disk
-    List,
+    List {
+        /// The field by which profiles are sorted
+        #[arg(long, value_enum, default_value_t = ProfileSortBy::default())]
+        sort_by: ProfileSortBy,
+        /// Reverse the sort order
+        #[arg(long, default_value_t = false)]
+        reverse: bool,
+    },
+    /// Display when each profile will next refresh
+    Next {
+        /// Only display enabled profiles
+        #[arg(long, default_value_t = false)]
+        enabled_only: bool,
+    },
     /// Display a sample of songs from the profile
-    Preview,
+    Preview {
+        /// The id or title of the profile. If not provided, a profile is selected interactively
+        profile: Option<String>,
+        /// The number of tracks to display, or `0` to display all tracks
+        #[arg(long, default_value_t = PREVIEW_COUNT)]
+        count: usize,
+        /// Display when each track was last played relative to now, e.g. `3 days ago`
+        #[arg(long, default_value_t = false)]
+        relative_dates: bool,
+        /// Display each artist with their number of tracks instead of listing the tracks
+        #[arg(long, default_value_t = false)]
+        by_artist: bool,
+        /// Note whether the playlist relies on the order of its tracks, which is lost if the
+        /// playlist is sorted in plex
+        #[arg(long, default_value_t = false)]
+        order_note: bool,
+        /// List profiles alphabetically instead of most recently used first
+        #[arg(long, default_value_t = false)]
+        alpha: bool,
+    },
+    /// Print the tracks shared by the playlists of two or more profiles
+    Overlap {
+        /// The ids or titles of the profiles to compare
+        #[arg(required = true, num_args = 2..)]
+        titles: Vec<String>,
+    },
+    /// Delete disabled profiles
+    Prune {
+        /// Only prune profiles that were disabled more than this many days ago
+        #[arg(long)]
+        older_than: Option<u32>,
+        /// Also delete the playlists of pruned profiles from plex
+        #[arg(long, default_value_t = false)]
+        delete_playlists: bool,
+    },
+    /// Refresh a profile once at the given time, the next time `run --run-loop` is running then
+    ScheduleOnce {
+        /// The id or title of the profile
+        title: String,
+        /// A time of day such as `18:00`, or a date and time such as `2024-06-01 18:00`
+        time: String,
+    },
+    /// Build a profile with overridden limits into a temporary `<title> (shadow)` playlist,
+    /// leaving the profile's playlist untouched
+    Shadow {
+        /// The id or title of the profile
+        title: String,
+        /// Override the profile's time limit, in hours
+        #[arg(long)]
+        time_limit: Option<u32>,
+        /// Override the profile's track limit
+        #[arg(long)]
+        track_limit: Option<u32>,
+    },
+    /// Delete every shadow playlist from plex
+    ShadowCleanup,
+    /// Build a profile from a profile file against tracks from a fixture file, without connecting
+    /// to plex, and print the resulting playlist
+    Simulate {
+        /// A profile file, in the format read by `import-dir`
+        profile_file: String,
+        /// A JSON array of tracks, or a plex response containing them
+        tracks_file: String,
+    },
+    /// Replace a profile's playlist with the tracks saved in one of its snapshots
+    Restore {
+        /// The id or title of the profile
+        title: String,
+        /// The snapshot to restore, where `1` is the most recent snapshot
+        #[arg(long, default_value_t = 1)]
+        snapshot: u32,
+    },
     /// Update profile's playlist on the plex server
     Update,
     /// View profiles
-    View,
+    View {
+        /// The id or title of the profile. If not provided, a profile is selected interactively
+        profile: Option<String>,
+        /// The format in which the profile is printed
+        #[arg(long, value_enum, default_value_t = OutputFormat::default())]
+        output: OutputFormat,
+        /// How JSON output is serialized when `--output json` is used
+        #[arg(long, value_enum, default_value_t = JsonStyle::default())]
+        json_style: JsonStyle,
+        /// List profiles alphabetically instead of most recently used first
+        #[arg(long, default_value_t = false)]
+        alpha: bool,
+    },
 }
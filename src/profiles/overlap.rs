@@ -0,0 +1,166 @@
+//! Finds the tracks shared by the playlists of several profiles
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+
+use crate::plex::models::tracks::Track;
+use crate::profiles::profile::Profile;
+use crate::profiles::profile_tracks::ProfileTracks;
+use crate::profiles::track_source::TrackSource;
+
+/// The tracks, matched by guid, that appear in the merged tracks of every compared profile
+#[derive(Debug, PartialEq)]
+pub struct ProfileOverlap {
+    titles: Vec<String>,
+    shared: Vec<Track>,
+}
+
+impl ProfileOverlap {
+    /// Finds the tracks shared by every profile, in the order of the first profile's tracks
+    pub fn new(profiles: &[(String, Vec<Track>)]) -> Self {
+        let titles = profiles
+            .iter()
+            .map(|(title, _)| title.to_owned())
+            .collect::<Vec<_>>();
+
+        let guid_sets = profiles
+            .iter()
+            .skip(1)
+            .map(|(_, tracks)| {
+                tracks
+                    .iter()
+                    .map(|track| track.get_guid())
+                    .collect::<HashSet<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        let shared = profiles
+            .first()
+            .map(|(_, tracks)| {
+                tracks
+                    .iter()
+                    .filter(|track| {
+                        guid_sets
+                            .iter()
+                            .all(|guids| guids.contains(track.get_guid()))
+                            && seen.insert(track.get_guid())
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { titles, shared }
+    }
+}
+
+/// Builds the merged tracks of each profile and finds the tracks shared by all of them
+pub async fn find_overlap(
+    source: &impl TrackSource,
+    profiles: &[Profile],
+) -> Result<ProfileOverlap> {
+    let mut merged = vec![];
+    for profile in profiles {
+        let profile_tracks = ProfileTracks::new(source, profile).await?;
+        merged.push((
+            profile.get_title().to_owned(),
+            profile_tracks.get_merged_tracks().to_vec(),
+        ));
+    }
+
+    Ok(ProfileOverlap::new(&merged))
+}
+
+impl Display for ProfileOverlap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut str = format!(
+            "{} track{} shared by {}",
+            self.shared.len(),
+            if self.shared.len() == 1 { "" } else { "s" },
+            self.titles.join(", ")
+        );
+        for track in &self.shared {
+            str += &format!(
+                "\n - {} - {}",
+                track.get_track_artist(),
+                track.get_track_title()
+            );
+        }
+
+        write!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::models::tracks::TrackBuilder;
+
+    use super::*;
+
+    fn build_track(id: &str, guid: &str, title: &str) -> Track {
+        TrackBuilder::default()
+            .id(id)
+            .guid(guid)
+            .title(title)
+            .artist("Rush")
+            .build()
+    }
+
+    #[test]
+    fn test_overlap_finds_shared_track() {
+        let shared = build_track("100001", "plex://track/1", "Limelight");
+        let profiles = vec![
+            (
+                "Morning".to_string(),
+                vec![
+                    build_track("100002", "plex://track/2", "Tom Sawyer"),
+                    shared.clone(),
+                ],
+            ),
+            (
+                "Evening".to_string(),
+                vec![
+                    // The same recording from another album shares the guid
+                    build_track("100003", "plex://track/1", "Limelight"),
+                    build_track("100004", "plex://track/4", "YYZ"),
+                ],
+            ),
+        ];
+
+        let overlap = ProfileOverlap::new(&profiles);
+
+        assert_eq!(vec![shared], overlap.shared);
+        assert_eq!(
+            "1 track shared by Morning, Evening\n - Rush - Limelight",
+            overlap.to_string()
+        );
+    }
+
+    #[test]
+    fn test_overlap_requires_every_profile() {
+        let profiles = vec![
+            (
+                "A".to_string(),
+                vec![build_track("100001", "plex://track/1", "Limelight")],
+            ),
+            (
+                "B".to_string(),
+                vec![build_track("100001", "plex://track/1", "Limelight")],
+            ),
+            (
+                "C".to_string(),
+                vec![build_track("100002", "plex://track/2", "YYZ")],
+            ),
+        ];
+
+        let overlap = ProfileOverlap::new(&profiles);
+
+        assert!(overlap.shared.is_empty());
+        assert_eq!("0 tracks shared by A, B, C", overlap.to_string());
+    }
+}
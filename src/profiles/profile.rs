@@ -3,22 +3,37 @@ use std::fmt::{Display, Formatter};
 
 use anyhow::Result;
 use derive_builder::Builder;
+use itertools::Itertools;
 use jiff::tz::TimeZone;
 use jiff::{Timestamp, Zoned};
 use serde::{Deserialize, Serialize};
 
 use crate::db;
 use crate::profiles::profile_section::ProfileSection;
-use crate::profiles::ProfileSource;
+use crate::profiles::{
+    BalanceBy, EmptySectionBehavior, MergeStrategy, ProfileSource, SectionType, DEFAULT_MERGE_ORDER,
+};
+use crate::types::plex::hub_identifier::HubIdentifier;
 use crate::types::plex::plex_id::PlexId;
 use crate::types::profiles::profile_source_id::ProfileSourceId;
 use crate::types::profiles::refresh_interval::RefreshInterval;
 use crate::types::Title;
 
+/// A time limit of zero means no limit, which the `v_profile` view stores as a year
+pub const UNLIMITED_TIME_LIMIT: u32 = 365 * 24;
+
+/// Appended to a profile's title to name the temporary playlist of a shadow profile
+pub const SHADOW_SUFFIX: &str = " (shadow)";
+
 // PROFILE ####################################################################
 
+fn default_minimum_valid_sections() -> u32 {
+    1
+}
+
 #[derive(Builder, Clone, Debug, Default, Deserialize, Serialize, PartialEq, sqlx::FromRow)]
 #[builder(default)]
+#[serde(default)]
 pub struct Profile {
     /// The primary key in the database
     profile_id: i32,
@@ -35,12 +50,46 @@ pub struct Profile {
     /// The location from which the profile fetches tracks
     profile_source: ProfileSource,
     profile_source_id: Option<ProfileSourceId>,
+    /// The plex hub from which the profile fetches artists, if the profile source is a hub
+    hub_identifier: Option<HubIdentifier>,
     /// How often in minutes the profile should refresh in an hour
     refresh_interval: RefreshInterval,
     /// The time limit in hours of the playlist.
     time_limit: u32,
     /// The track limit of the playlist
     track_limit: u32,
+    /// Determines what happens when an enabled section returns no tracks
+    empty_section_behavior: EmptySectionBehavior,
+    /// The order in which sections are merged into the playlist. If empty, the default order is used.
+    merge_order: Vec<SectionType>,
+    /// Determines how sections are combined into the playlist
+    merge_strategy: MergeStrategy,
+    /// Determines whether interleaved sections are balanced by track count or by playtime
+    balance_by: BalanceBy,
+    /// The maximum number of tracks by a single artist across the merged playlist, on top of each
+    /// section's own limit. `0` disables the limit.
+    max_tracks_by_artist_global: u32,
+    /// If true, refreshes only update the playlist summary and never change its tracks
+    summary_only: bool,
+    /// The number of previous refreshes whose tracks are excluded from the next refresh
+    avoid_recent_repeats_cycles: u32,
+    /// The number of previous versions of the playlist kept as snapshots before each refresh
+    snapshot_count: u32,
+    /// The smallest number of tracks a refreshed playlist may contain. `0` disables the check.
+    minimum_playlist_size: u32,
+    /// If true, a refresh below the minimum playlist size fails instead of being skipped
+    fail_below_minimum_size: bool,
+    /// If true, tracks are shuffled with a seed derived from the profile id and the refresh
+    /// timestamp, so that a given refresh can be reproduced
+    seeded_shuffle: bool,
+    /// The number of sections that must return tracks for a refresh to update the playlist
+    #[builder(default = "1")]
+    #[serde(default = "default_minimum_valid_sections")]
+    minimum_valid_sections: u32,
+    /// The hour (local time) at which the profile begins refreshing each day
+    active_hours_start: Option<u32>,
+    /// The hour (local time) at which the profile stops refreshing each day
+    active_hours_end: Option<u32>,
     #[builder(default)]
     num_sections: u32,
     #[builder(default)]
@@ -70,6 +119,23 @@ impl Profile {
         &self.playlist_id
     }
 
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = summary;
+    }
+
+    /// Fills in a summary template using this profile's settings
+    ///
+    /// Supported placeholders are `{title}`, `{sections}`, `{interval}`, `{time_limit}`, and
+    /// `{source}`. Unknown placeholders are left as they are.
+    pub fn render_summary_template(&self, template: &str, num_sections: usize) -> String {
+        template
+            .replace("{title}", self.get_title())
+            .replace("{sections}", &num_sections.to_string())
+            .replace("{interval}", &self.get_refresh_interval().to_string())
+            .replace("{time_limit}", &self.get_time_limit().to_string())
+            .replace("{source}", &self.get_profile_source().to_string())
+    }
+
     pub fn set_playlist_id(&mut self, playlist_id: PlexId) {
         self.playlist_id = playlist_id
     }
@@ -102,6 +168,14 @@ impl Profile {
         }
     }
 
+    pub fn get_hub_identifier(&self) -> Option<&HubIdentifier> {
+        self.hub_identifier.as_ref()
+    }
+
+    pub fn get_hub_identifier_str(&self) -> Option<&str> {
+        self.hub_identifier.as_ref().map(|id| id.as_str())
+    }
+
     pub async fn fetch_sections(&self) -> Result<Vec<ProfileSection>> {
         let sections = db::profiles::fetch_profile_sections_for_profile(self.profile_id).await?;
         Ok(sections)
@@ -123,10 +197,135 @@ impl Profile {
         self.track_limit
     }
 
+    pub fn get_empty_section_behavior(&self) -> EmptySectionBehavior {
+        self.empty_section_behavior
+    }
+
+    /// Returns the order in which sections are merged, falling back to [`DEFAULT_MERGE_ORDER`]
+    pub fn get_merge_order(&self) -> Vec<SectionType> {
+        if self.merge_order.is_empty() {
+            DEFAULT_MERGE_ORDER.to_vec()
+        } else {
+            self.merge_order.clone()
+        }
+    }
+
+    pub fn get_avoid_recent_repeats_cycles(&self) -> u32 {
+        self.avoid_recent_repeats_cycles
+    }
+
+    pub fn get_snapshot_count(&self) -> u32 {
+        self.snapshot_count
+    }
+
+    pub fn get_minimum_playlist_size(&self) -> u32 {
+        self.minimum_playlist_size
+    }
+
+    pub fn get_fail_below_minimum_size(&self) -> bool {
+        self.fail_below_minimum_size
+    }
+
+    pub fn get_minimum_valid_sections(&self) -> u32 {
+        self.minimum_valid_sections
+    }
+
+    pub fn get_seeded_shuffle(&self) -> bool {
+        self.seeded_shuffle
+    }
+
+    pub fn is_summary_only(&self) -> bool {
+        self.summary_only
+    }
+
+    pub fn get_merge_strategy(&self) -> MergeStrategy {
+        self.merge_strategy
+    }
+
+    pub fn get_balance_by(&self) -> BalanceBy {
+        self.balance_by
+    }
+
+    pub fn get_max_tracks_by_artist_global(&self) -> u32 {
+        self.max_tracks_by_artist_global
+    }
+
+    pub fn has_custom_merge_order(&self) -> bool {
+        !self.merge_order.is_empty()
+    }
+
+    /// Returns the custom merge order serialized as JSON, or `None` if the default order is used
+    pub fn get_merge_order_json(&self) -> Result<Option<String>> {
+        if self.merge_order.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(serde_json::to_string(&self.merge_order)?))
+        }
+    }
+
+    pub fn get_active_hours_start(&self) -> Option<u32> {
+        self.active_hours_start
+    }
+
+    pub fn get_active_hours_end(&self) -> Option<u32> {
+        self.active_hours_end
+    }
+
+    /// Checks if the given hour falls within the profile's active hours
+    ///
+    /// The start hour is inclusive and the end hour is exclusive. Windows that wrap past midnight
+    /// (e.g., `22` to `2`) are supported. A profile without active hours is always active.
+    pub fn is_within_active_hours(&self, hour: u32) -> bool {
+        match (self.active_hours_start, self.active_hours_end) {
+            (Some(start), Some(end)) if start < end => hour >= start && hour < end,
+            (Some(start), Some(end)) if start > end => hour >= start || hour < end,
+            _ => true,
+        }
+    }
+
     pub fn get_section_time_limit(&self) -> f64 {
         self.section_time_limit
     }
 
+    /// Clones this profile for a temporary shadow playlist, applying any overridden limits
+    ///
+    /// The shadow is titled `<title> (shadow)` and its playlist id is reset, so building it never
+    /// touches the real playlist.
+    pub fn to_shadow(&self, time_limit: Option<u32>, track_limit: Option<u32>) -> Result<Profile> {
+        let mut shadow = self.clone();
+        shadow.title = Title::try_new(format!("{}{SHADOW_SUFFIX}", self.title))?;
+        shadow.playlist_id = PlexId::default();
+
+        if let Some(time_limit) = time_limit {
+            let time_limit = if time_limit == 0 {
+                UNLIMITED_TIME_LIMIT
+            } else {
+                time_limit
+            };
+            shadow.time_limit = time_limit;
+            if shadow.num_sections > 0 {
+                shadow.section_time_limit = time_limit as f64 / shadow.num_sections as f64;
+            }
+        }
+        if let Some(track_limit) = track_limit {
+            shadow.track_limit = track_limit;
+        }
+
+        Ok(shadow)
+    }
+
+    /// Sets the number of enabled sections and the time limit of each, as `v_profile` would for a
+    /// profile that was never saved to the database
+    pub fn derive_section_fields(&mut self, num_sections: u32) {
+        if self.time_limit == 0 {
+            self.time_limit = UNLIMITED_TIME_LIMIT;
+        }
+        self.num_sections = num_sections;
+        if num_sections > 0 {
+            self.section_time_limit = self.time_limit as f64 / num_sections as f64;
+        }
+    }
+
     pub fn get_refreshes_per_hour(&self) -> u32 {
         self.refreshes_per_hour
     }
@@ -138,12 +337,24 @@ impl Profile {
         self.eligible_for_refresh
     }
 
+    pub fn get_next_refresh_at(&self) -> i64 {
+        self.next_refresh_at
+    }
+
+    pub fn get_current_refresh(&self) -> i64 {
+        self.current_refresh
+    }
+
+    pub fn is_eligible_for_refresh(&self) -> bool {
+        self.eligible_for_refresh
+    }
+
+    pub fn get_current_refresh_hour_minute(&self) -> String {
+        format_hour_minute(self.current_refresh)
+    }
+
     pub fn get_next_refresh_hour_minute(&self) -> String {
-        Timestamp::from_second(self.next_refresh_at)
-            .unwrap()
-            .to_zoned(TimeZone::system())
-            .strftime("%H:%M")
-            .to_string()
+        format_hour_minute(self.next_refresh_at)
     }
 
     pub fn get_next_refresh_str(&self) -> String {
@@ -173,6 +384,13 @@ impl Profile {
         }
     }
 
+    fn active_hours_str(&self) -> String {
+        match (self.active_hours_start, self.active_hours_end) {
+            (Some(start), Some(end)) if start != end => format!("{start:02}:00 to {end:02}:00"),
+            _ => "Always".to_string(),
+        }
+    }
+
     fn get_track_limit_str(&self) -> String {
         if self.track_limit == 0 {
             "No Limit".to_string()
@@ -180,6 +398,16 @@ impl Profile {
             format!("{} tracks", self.track_limit)
         }
     }
+
+    fn minimum_playlist_size_str(&self) -> String {
+        if self.minimum_playlist_size == 0 {
+            "None".to_string()
+        } else if self.fail_below_minimum_size {
+            format!("{} tracks (fail)", self.minimum_playlist_size)
+        } else {
+            format!("{} tracks (skip)", self.minimum_playlist_size)
+        }
+    }
 }
 
 impl Display for Profile {
@@ -191,6 +419,31 @@ impl Display for Profile {
         str += &format!("\nRefresh Interval: {}", self.refresh_interval_str());
         str += &format!("\nTime Limit:       {}", self.time_limit_str());
         str += &format!("\nTrack Limit:      {}", self.get_track_limit_str());
+        str += &format!("\nActive Hours:     {}", self.active_hours_str());
+        str += &format!("\nEmpty Sections:   {}", self.empty_section_behavior);
+        str += &format!(
+            "\nMerge Order:      {}",
+            self.get_merge_order().iter().join(", ")
+        );
+        str += &format!("\nMerge Strategy:   {}", self.merge_strategy);
+        if self.merge_strategy == MergeStrategy::Interleave {
+            str += &format!("\nBalance By:       {}", self.balance_by);
+        }
+        if self.max_tracks_by_artist_global > 0 {
+            str += &format!(
+                "\nMax. by Artist:   {} track(s)",
+                self.max_tracks_by_artist_global
+            );
+        }
+        str += &format!("\nSummary Only:     {}", self.summary_only);
+        str += &format!(
+            "\nAvoid Repeats:    {} refresh(es)",
+            self.avoid_recent_repeats_cycles
+        );
+        str += &format!("\nSnapshots:        {}", self.snapshot_count);
+        str += &format!("\nMinimum Size:     {}", self.minimum_playlist_size_str());
+        str += &format!("\nSeeded Shuffle:   {}", self.seeded_shuffle);
+        str += &format!("\nMin. Sections:    {}", self.minimum_valid_sections);
 
         // TODO fix sections info
         str += "\n\nSections:";
@@ -209,3 +462,101 @@ impl Display for Profile {
         write!(f, "{str}")
     }
 }
+
+/// Formats a unix timestamp in seconds as the local hour and minute, e.g. `14:05`
+fn format_hour_minute(timestamp: i64) -> String {
+    Timestamp::from_second(timestamp)
+        .unwrap()
+        .to_zoned(TimeZone::system())
+        .strftime("%H:%M")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn build_profile(start: Option<u32>, end: Option<u32>) -> Profile {
+        ProfileBuilder::default()
+            .active_hours_start(start)
+            .active_hours_end(end)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_to_shadow() {
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Daily Mix").unwrap())
+            .playlist_id(PlexId::try_new("111111").unwrap())
+            .time_limit(6)
+            .num_sections(3)
+            .section_time_limit(2.0)
+            .track_limit(100)
+            .build()
+            .unwrap();
+
+        let shadow = profile.to_shadow(Some(12), None).unwrap();
+
+        assert_eq!("Daily Mix (shadow)", shadow.get_title());
+        assert_ne!(profile.get_playlist_id(), shadow.get_playlist_id());
+        assert_eq!(12, shadow.get_time_limit());
+        assert_eq!(4.0, shadow.get_section_time_limit());
+        assert_eq!(100, shadow.get_track_limit());
+    }
+
+    #[test]
+    fn test_within_active_hours() {
+        let profile = build_profile(Some(8), Some(23));
+        assert_eq!(true, profile.is_within_active_hours(8));
+        assert_eq!(true, profile.is_within_active_hours(15));
+        assert_eq!(true, profile.is_within_active_hours(22));
+    }
+
+    #[test]
+    fn test_outside_active_hours() {
+        let profile = build_profile(Some(8), Some(23));
+        assert_eq!(false, profile.is_within_active_hours(3));
+        assert_eq!(false, profile.is_within_active_hours(7));
+        assert_eq!(false, profile.is_within_active_hours(23));
+    }
+
+    #[test]
+    fn test_active_hours_wrapping_midnight() {
+        let profile = build_profile(Some(22), Some(2));
+        assert_eq!(true, profile.is_within_active_hours(22));
+        assert_eq!(true, profile.is_within_active_hours(0));
+        assert_eq!(true, profile.is_within_active_hours(1));
+        assert_eq!(false, profile.is_within_active_hours(2));
+        assert_eq!(false, profile.is_within_active_hours(12));
+    }
+
+    #[test]
+    fn test_render_summary_template() {
+        let profile = ProfileBuilder::default()
+            .title(Title::try_new("Morning Mix").unwrap())
+            .refresh_interval(RefreshInterval::try_new(15).unwrap())
+            .time_limit(4)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            "Morning Mix: 3 sections, refreshed every 15m for 4h {unknown}",
+            profile.render_summary_template(
+                "{title}: {sections} sections, refreshed every {interval}m for {time_limit}h {unknown}",
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_active_hours() {
+        let profile = build_profile(None, None);
+        assert!((0..24).all(|hour| profile.is_within_active_hours(hour)));
+
+        let profile = build_profile(Some(5), Some(5));
+        assert!((0..24).all(|hour| profile.is_within_active_hours(hour)));
+    }
+}
@@ -1,12 +1,18 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
+use anyhow::Result;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
-use crate::profiles::SectionType;
+use crate::profiles::{
+    ContentRatingPreference, DedupKey, MissingDurationPolicy, OldestBucket, RandomizationBias,
+    SectionType,
+};
 
 #[allow(dead_code)]
 #[derive(Builder, Clone, Debug, Default, Deserialize, PartialEq, Serialize, sqlx::FromRow)]
+#[serde(default)]
 pub struct ProfileSection {
     /// The primary key in the database
     #[builder(setter(skip))]
@@ -19,16 +25,96 @@ pub struct ProfileSection {
     /// the resulting playlist.
     deduplicate_tracks_by_guid: bool,
     deduplicate_tracks_by_title_and_artist: bool,
+    /// Determines which fields must match for tracks to be considered duplicates when
+    /// deduplicating tracks by title and artist
+    #[builder(default)]
+    dedup_key: DedupKey,
+    /// Determines whether the explicit or clean version of a track is kept when deduplicating
+    /// tracks by title and artist
+    #[builder(default)]
+    content_rating_preference: ContentRatingPreference,
     enabled: bool,
     /// Caps the number of tracks by an artist that can appear in a single playlist.
     /// A value of `0` allows for an unlimited number of tracks.
     maximum_tracks_by_artist: u32,
     minimum_track_rating: u32,
+    /// The minimum percentage (`0` to `100`) of an album's tracks that must qualify for the
+    /// section before any of the album's tracks are included. A value of `0` disables the check.
+    #[builder(default)]
+    minimum_album_completeness: u32,
     randomize_tracks: bool,
+    /// Favors certain tracks when randomizing tracks within a group
+    #[builder(default)]
+    randomization_bias: RandomizationBias,
+    /// Determines how tracks are grouped by their last played date when randomizing an oldest
+    /// tracks section
+    #[builder(default)]
+    oldest_bucket: OldestBucket,
+    /// The number of tracks at the front of the section that keep their sorted order when
+    /// randomizing, so that only the remaining tracks are shuffled
+    #[builder(default)]
+    keep_top_n_fixed: u32,
+    /// Removes tracks that have never been played from an oldest tracks section, so that the
+    /// section only contains tracks that were played long ago
+    #[builder(default)]
+    exclude_never_played: bool,
+    /// Tracks with any of these genres are removed from the section
+    #[builder(default)]
+    #[sqlx(json)]
+    exclude_genres: Vec<String>,
+    /// Tracks shorter than this many seconds are removed from the section. A value of `0`
+    /// disables the bound.
+    #[builder(default)]
+    min_duration_seconds: u32,
+    /// Tracks longer than this many seconds are removed from the section. A value of `0`
+    /// disables the bound.
+    #[builder(default)]
+    max_duration_seconds: u32,
+    /// Determines how tracks without a duration are counted against the section's time limit
+    #[builder(default)]
+    missing_duration_policy: MissingDurationPolicy,
+    /// Counts tracks credited to an artist featuring others (e.g., `Artist feat. X`) against the
+    /// primary artist when limiting the tracks by a single artist
+    #[builder(default)]
+    normalize_featured_artists: bool,
+    /// Removes tracks played within the last day from the section
+    #[builder(default = "true")]
+    #[serde(default = "default_exclude_recently_played")]
+    exclude_recently_played: bool,
+    /// Includes tracks that have never been played in a least played section, which otherwise only
+    /// includes tracks played at least once
+    #[builder(default)]
+    least_played_includes_unplayed: bool,
+    /// The number of plays a track needs to be included in a least played or oldest section, so
+    /// that tracks played only once do not flood the section. Values of `0` and `1` both require
+    /// a single play, and the value is ignored if a least played section includes unplayed tracks.
+    #[builder(default)]
+    minimum_plays: u32,
+    /// Orders a least played section by each track's plays minus its skips, so that frequently
+    /// skipped tracks are treated as less played
+    #[builder(default)]
+    use_net_plays: bool,
+    /// Orders an unplayed section so that recently added tracks come before older tracks with the
+    /// same rating
+    #[builder(default)]
+    boost_recently_added: bool,
+    /// Requests the section's tracks from plex in a random order instead of the section's
+    /// sorting, and skips sorting and shuffling them locally
+    #[builder(default)]
+    server_random: bool,
+    /// Raw plex filters (e.g., `label` or `decade`) added to the filters built for the section.
+    /// They are applied last, so an extra filter replaces a built-in filter with the same key.
+    #[builder(default)]
+    #[sqlx(json)]
+    extra_filters: HashMap<String, String>,
     section_type: SectionType,
     sorting: String,
 }
 
+fn default_exclude_recently_played() -> bool {
+    true
+}
+
 impl ProfileSection {
     pub fn new() -> Self {
         Self::default()
@@ -80,6 +166,10 @@ impl ProfileSection {
         (self.get_minimum_track_rating() - 1) * 2
     }
 
+    pub fn get_minimum_album_completeness(&self) -> u32 {
+        self.minimum_album_completeness
+    }
+
     pub fn get_sorting_vec(&self) -> Vec<&str> {
         self.sorting.split(',').collect::<_>()
     }
@@ -96,6 +186,10 @@ impl ProfileSection {
         self.deduplicate_tracks_by_title_and_artist
     }
 
+    pub fn get_dedup_key(&self) -> DedupKey {
+        self.dedup_key
+    }
+
     pub fn get_maximum_tracks_by_artist(&self) -> u32 {
         self.maximum_tracks_by_artist
     }
@@ -103,6 +197,84 @@ impl ProfileSection {
     pub fn get_randomize_tracks(&self) -> bool {
         self.randomize_tracks
     }
+
+    pub fn get_keep_top_n_fixed(&self) -> u32 {
+        self.keep_top_n_fixed
+    }
+
+    pub fn get_randomization_bias(&self) -> RandomizationBias {
+        self.randomization_bias
+    }
+
+    pub fn get_oldest_bucket(&self) -> OldestBucket {
+        self.oldest_bucket
+    }
+
+    pub fn get_exclude_never_played(&self) -> bool {
+        self.exclude_never_played
+    }
+
+    pub fn get_exclude_genres(&self) -> &[String] {
+        &self.exclude_genres
+    }
+
+    /// Returns the excluded genres serialized as a JSON array
+    pub fn get_exclude_genres_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.exclude_genres)?)
+    }
+
+    pub fn get_min_duration_seconds(&self) -> u32 {
+        self.min_duration_seconds
+    }
+
+    pub fn get_max_duration_seconds(&self) -> u32 {
+        self.max_duration_seconds
+    }
+
+    pub fn get_missing_duration_policy(&self) -> MissingDurationPolicy {
+        self.missing_duration_policy
+    }
+
+    pub fn get_normalize_featured_artists(&self) -> bool {
+        self.normalize_featured_artists
+    }
+
+    pub fn get_least_played_includes_unplayed(&self) -> bool {
+        self.least_played_includes_unplayed
+    }
+
+    pub fn get_minimum_plays(&self) -> u32 {
+        self.minimum_plays
+    }
+
+    pub fn get_use_net_plays(&self) -> bool {
+        self.use_net_plays
+    }
+
+    pub fn get_boost_recently_added(&self) -> bool {
+        self.boost_recently_added
+    }
+
+    pub fn get_server_random(&self) -> bool {
+        self.server_random
+    }
+
+    pub fn get_extra_filters(&self) -> &HashMap<String, String> {
+        &self.extra_filters
+    }
+
+    /// Returns the extra filters serialized as a JSON object
+    pub fn get_extra_filters_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.extra_filters)?)
+    }
+
+    pub fn get_exclude_recently_played(&self) -> bool {
+        self.exclude_recently_played
+    }
+
+    pub fn get_content_rating_preference(&self) -> ContentRatingPreference {
+        self.content_rating_preference
+    }
 }
 
 impl Display for ProfileSection {
@@ -120,6 +292,16 @@ impl Display for ProfileSection {
             "\n    Deduplicate tracks by title and artist: {}",
             self.deduplicate_tracks_by_title_and_artist
         );
+        if self.deduplicate_tracks_by_title_and_artist {
+            str += &format!(
+                "\n    Duplicate tracks share:                 {}",
+                self.dedup_key
+            );
+            str += &format!(
+                "\n    Explicit or clean versions:             {}",
+                self.content_rating_preference
+            );
+        }
         str += &format!(
             "\n    Maximum tracks by artist:               {}",
             if self.maximum_tracks_by_artist == 0 {
@@ -128,14 +310,110 @@ impl Display for ProfileSection {
                 format!("{} track(s)", self.maximum_tracks_by_artist)
             }
         );
+        if self.maximum_tracks_by_artist > 0 {
+            str += &format!(
+                "\n    Normalize featured artists:             {}",
+                self.normalize_featured_artists
+            );
+        }
         str += &format!(
             "\n    Minimum track rating:                   {} stars",
             self.minimum_track_rating
         );
+        if self.minimum_album_completeness > 0 {
+            str += &format!(
+                "\n    Minimum album completeness:             {}%",
+                self.minimum_album_completeness
+            );
+        }
+        str += &format!(
+            "\n    Exclude recently played tracks:         {}",
+            self.exclude_recently_played
+        );
         str += &format!(
-            "\n    Sorting:                                {}",
-            self.sorting
+            "\n    Randomization bias:                     {}",
+            self.randomization_bias
         );
+        if self.randomize_tracks {
+            str += &format!(
+                "\n    Tracks kept in order before shuffling:  {}",
+                self.keep_top_n_fixed
+            );
+        }
+        if self.is_unplayed_section() {
+            str += &format!(
+                "\n    Recently added tracks first:            {}",
+                self.boost_recently_added
+            );
+        }
+        if self.is_least_played_section() {
+            str += &format!(
+                "\n    Include never played tracks:            {}",
+                self.least_played_includes_unplayed
+            );
+            str += &format!(
+                "\n    Order by plays minus skips:             {}",
+                self.use_net_plays
+            );
+        }
+        if self.minimum_plays > 1 && !self.is_unplayed_section() {
+            str += &format!(
+                "\n    Minimum plays:                          {}",
+                self.minimum_plays
+            );
+        }
+        if self.is_oldest_section() {
+            str += &format!(
+                "\n    Oldest tracks grouped by:               {}",
+                self.oldest_bucket
+            );
+            str += &format!(
+                "\n    Exclude never played tracks:            {}",
+                self.exclude_never_played
+            );
+        }
+        if !self.exclude_genres.is_empty() {
+            str += &format!(
+                "\n    Excluded genres:                        {}",
+                self.exclude_genres.join(", ")
+            );
+        }
+        if self.min_duration_seconds > 0 {
+            str += &format!(
+                "\n    Minimum track duration:                 {} seconds",
+                self.min_duration_seconds
+            );
+        }
+        if self.max_duration_seconds > 0 {
+            str += &format!(
+                "\n    Maximum track duration:                 {} seconds",
+                self.max_duration_seconds
+            );
+        }
+        str += &format!(
+            "\n    Tracks without a duration:              {}",
+            self.missing_duration_policy
+        );
+        if !self.extra_filters.is_empty() {
+            let mut extra_filters = self
+                .extra_filters
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>();
+            extra_filters.sort();
+            str += &format!(
+                "\n    Extra plex filters:                     {}",
+                extra_filters.join("; ")
+            );
+        }
+        if self.server_random {
+            str += "\n    Sorting:                                random (by plex)";
+        } else {
+            str += &format!(
+                "\n    Sorting:                                {}",
+                self.sorting
+            );
+        }
 
         writeln!(f, "{str}")
     }
@@ -1,20 +1,54 @@
 #![allow(dead_code)]
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use derive_builder::Builder;
+use futures_util::{stream, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use rand::prelude::SliceRandom;
-use simplelog::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::slice::ParallelSliceMut;
+use simplelog::{info, warn};
 use std::cmp::Reverse;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::time;
+use std::time::Instant;
 
 use crate::db;
+use crate::http_client;
+use crate::plex::models::tracks;
 use crate::plex::models::tracks::Track;
-use crate::plex::PlexClient;
+use crate::profiles::bench::PhaseTimings;
 use crate::profiles::profile::Profile;
 use crate::profiles::profile_section::ProfileSection;
-use crate::profiles::{ProfileSource, SectionType};
+use crate::profiles::track_source::TrackSource;
+use crate::profiles::{
+    BalanceBy, ContentRatingPreference, DedupKey, EmptySectionBehavior, MergeStrategy,
+    MissingDurationPolicy, OldestBucket, ProfileSource, RandomizationBias, SectionType,
+    ASSUMED_TRACK_DURATION_MS,
+};
+
+/// The most artist ids sent in a single request for a section's tracks, so that the url for a
+/// collection with many artists does not grow too long for plex
+const MAX_ARTIST_IDS_PER_REQUEST: usize = 100;
+
+/// The most album track counts requested from plex at once when checking album completeness
+const MAX_CONCURRENT_ALBUM_REQUESTS: usize = 8;
+
+/// The context of the error returned when a profile's tracks could not be fetched from plex,
+/// which marks the refresh as one that can be retried within the same cycle
+///
+/// Errors in the profile itself, such as an invalid merge order, are never marked, since
+/// retrying them cannot succeed.
+#[derive(Debug)]
+pub struct SourceUnavailable(pub String);
+
+impl fmt::Display for SourceUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The tracks for `{}` could not be fetched", self.0)
+    }
+}
 
 #[derive(Builder, Clone)]
 pub struct ProfileTracks {
@@ -35,11 +69,30 @@ impl AsRef<ProfileTracks> for ProfileTracks {
 }
 
 impl ProfileTracks {
-    pub async fn new(plex_client: &PlexClient, profile: &Profile) -> Result<Self> {
-        let profile_tracks = fetch_profile_tracks(plex_client, profile).await?;
+    pub async fn new(source: &impl TrackSource, profile: &Profile) -> Result<Self> {
+        let (profile_tracks, _) = Self::new_timed(source, profile).await?;
         Ok(profile_tracks)
     }
 
+    /// Builds the profile's tracks and records how long each phase of the build took
+    pub async fn new_timed(
+        source: &impl TrackSource,
+        profile: &Profile,
+    ) -> Result<(Self, PhaseTimings)> {
+        let sections =
+            db::profiles::fetch_profile_sections_for_profile(profile.get_profile_id()).await?;
+        let recent_guids = if profile.get_avoid_recent_repeats_cycles() > 0 {
+            db::profiles::fetch_recent_track_guids(
+                profile.get_profile_id(),
+                profile.get_avoid_recent_repeats_cycles(),
+            )
+            .await?
+        } else {
+            HashSet::new()
+        };
+        fetch_profile_tracks_timed(source, profile, &sections, &recent_guids).await
+    }
+
     pub fn have_unplayed_tracks(&self) -> bool {
         !self.unplayed.is_empty()
     }
@@ -73,13 +126,7 @@ impl ProfileTracks {
     }
 
     fn get_total_duration_of_section(&self, section_type: SectionType) -> time::Duration {
-        let tracks = self.get_section_tracks(section_type);
-        let total = tracks.iter().fold(0, |mut acc, track| {
-            acc += track.get_track_duration();
-            acc
-        });
-        // Duration::from(total)
-        time::Duration::from_millis(total as u64)
+        tracks::total_duration(self.get_section_tracks(section_type))
     }
 
     /// Returns a slice of the merged tracks
@@ -93,7 +140,7 @@ impl ProfileTracks {
     }
 
     /// Returns the number of valid sections (those that are not empty)
-    fn get_num_valid(&self) -> usize {
+    pub fn get_num_valid(&self) -> usize {
         [
             self.have_unplayed_tracks(),
             self.have_least_played_tracks(),
@@ -124,6 +171,14 @@ impl ProfileTracks {
         .unwrap_or(&0_usize)
     }
 
+    /// Returns the guids of the merged tracks
+    pub fn get_track_guids(&self) -> Vec<&str> {
+        self.merged
+            .iter()
+            .map(|track| track.get_guid())
+            .collect::<Vec<_>>()
+    }
+
     /// Returns a [`Vec`] of track IDs
     pub fn get_track_ids(&self) -> Vec<String> {
         if self.merged.is_empty() {
@@ -142,12 +197,25 @@ impl ProfileTracks {
     /// Runs manual filters for the profile sections
     ///
     /// Manual filters are those that are unique to this application and not included with plex
-    pub fn run_manual_filters(&mut self, profile_sections: &[ProfileSection], time_limit: f64) {
+    pub fn run_manual_filters(
+        &mut self,
+        profile_sections: &[ProfileSection],
+        time_limit: f64,
+        rng: &mut impl Rng,
+    ) {
         info!("Running manual section filters...");
 
         for section in profile_sections {
             let tracks = self.get_section_tracks_mut(section.get_section_type());
-            remove_played_within_last_day(tracks);
+            if section.get_exclude_recently_played() {
+                remove_played_within_last_day(tracks);
+            }
+            apply_missing_duration_policy(tracks, section.get_missing_duration_policy());
+            remove_tracks_outside_duration(
+                tracks,
+                section.get_min_duration_seconds(),
+                section.get_max_duration_seconds(),
+            );
 
             if section.get_deduplicate_tracks_by_guid() {
                 deduplicate_by_track_guid(tracks);
@@ -160,23 +228,47 @@ impl ProfileTracks {
             let tracks = self.get_section_tracks_mut(section.get_section_type());
 
             if section.get_deduplicate_tracks_by_title_and_artist() {
-                deduplicate_by_title_and_artist(tracks);
+                deduplicate_by_title_and_artist(
+                    tracks,
+                    section.get_dedup_key(),
+                    section.get_content_rating_preference(),
+                );
             }
 
             trim_tracks_by_artist(
                 tracks,
                 section.get_maximum_tracks_by_artist(),
                 section.get_section_type(),
+                section.get_normalize_featured_artists(),
             );
 
-            sort_tracks(tracks, section.get_section_type());
+            // Tracks randomized by plex are kept in the order they were returned
+            if !section.get_server_random() {
+                sort_tracks(
+                    tracks,
+                    section.get_section_type(),
+                    section.get_use_net_plays(),
+                    section.get_boost_recently_added(),
+                );
+            }
 
             if time_limit > 0.0 {
-                reduce_to_time_limit(tracks, time_limit);
+                reduce_to_time_limit(
+                    tracks,
+                    time_limit,
+                    get_missing_duration(section.get_missing_duration_policy()),
+                );
             }
 
-            if section.get_randomize_tracks() {
-                randomizer(tracks, section.get_section_type())
+            if section.get_randomize_tracks() && !section.get_server_random() {
+                randomizer(
+                    tracks,
+                    section.get_section_type(),
+                    section.get_randomization_bias(),
+                    section.get_oldest_bucket(),
+                    section.get_keep_top_n_fixed(),
+                    rng,
+                )
             }
         }
     }
@@ -184,28 +276,32 @@ impl ProfileTracks {
     /// Deduplicates the least played and oldest tracks
     ///
     /// Least played is deduplicated first, and oldest is deduplicated second
+    ///
+    /// A time limit less than or equal to zero deduplicates the full lists against each other
     fn deduplicate_lists(&mut self, time_limit: f64) {
         if !self.have_oldest_tracks() || !self.have_least_played_tracks() {
             return;
         }
 
-        if time_limit <= 0.0 {
-            panic!("Time limit cannot be less than or equal to zero")
-        }
-
         deduplicate_tracks_by_lists(&mut self.least_played, &self.oldest, time_limit);
         deduplicate_tracks_by_lists(&mut self.oldest, &self.least_played, time_limit);
     }
 
     /// Merges tracks from each playlist section into a single playlist
     ///
-    /// The following pattern is followed:
+    /// Sections are combined in the given order using the [`MergeStrategy`]. The default order is:
     ///  - Unplayed
     ///  - Least Played
     ///  - Oldest
     ///
-    /// If a track cannot be found in a given section, that section is skipped.
-    pub fn merge(&mut self) {
+    /// When interleaving, if a track cannot be found in a given section, that section is skipped.
+    /// Interleaved sections are balanced using [`BalanceBy`].
+    pub fn merge(
+        &mut self,
+        merge_order: &[SectionType],
+        merge_strategy: MergeStrategy,
+        balance_by: BalanceBy,
+    ) {
         if self.get_none_are_valid() {
             return;
         }
@@ -216,33 +312,131 @@ impl ProfileTracks {
         );
 
         self.merged = Vec::new();
-        for i in 0..self.get_largest_section_length() {
-            if let Some(track) = self.unplayed.get(i) {
-                self.merged.push(track.clone())
+        match merge_strategy {
+            MergeStrategy::Interleave if balance_by == BalanceBy::Duration => {
+                self.merge_by_duration(merge_order)
             }
-
-            if let Some(track) = self.least_played.get(i) {
-                self.merged.push(track.clone())
+            MergeStrategy::Interleave => {
+                for i in 0..self.get_largest_section_length() {
+                    for section_type in merge_order {
+                        if let Some(track) = self.get_section_tracks(*section_type).get(i) {
+                            self.merged.push(track.clone())
+                        }
+                    }
+                }
             }
-
-            if let Some(track) = self.oldest.get(i) {
-                self.merged.push(track.clone())
+            MergeStrategy::Concatenate => {
+                for section_type in merge_order {
+                    let tracks = self.get_section_tracks(*section_type).to_vec();
+                    self.merged.extend(tracks);
+                }
             }
         }
     }
 
-    /// Displays the first 25 tracks in the merged playlist in the console
-    pub fn print_preview(&self) {
-        if self.merged.is_empty() {
+    /// Interleaves sections by always taking the next track from the section that has contributed
+    /// the least playtime so far
+    ///
+    /// Ties go to the section that comes first in the merge order.
+    fn merge_by_duration(&mut self, merge_order: &[SectionType]) {
+        let mut positions = vec![0; merge_order.len()];
+        let mut durations = vec![0; merge_order.len()];
+
+        loop {
+            let next = merge_order
+                .iter()
+                .enumerate()
+                .filter(|(i, section_type)| {
+                    positions[*i] < self.get_section_tracks(**section_type).len()
+                })
+                .min_by_key(|(i, _)| durations[*i]);
+            let Some((i, section_type)) = next else {
+                break;
+            };
+
+            let track = &self.get_section_tracks(*section_type)[positions[i]];
+            durations[i] += track.get_track_duration();
+            positions[i] += 1;
+            self.merged.push(track.clone());
+        }
+    }
+
+    /// Limits the number of tracks by each artist across the merged playlist
+    ///
+    /// Each artist's earliest tracks are kept, so the merged order is preserved. A limit of `0`
+    /// keeps every track.
+    pub fn trim_merged_by_artist(&mut self, maximum_tracks_by_artist: u32) {
+        if maximum_tracks_by_artist == 0 {
             return;
         }
 
-        let preview = self.merged.iter().take(25).collect::<Vec<_>>();
+        let mut artist_occurrences: HashMap<String, u32> = HashMap::new();
+        self.merged.retain(|track| {
+            let occurrences = artist_occurrences
+                .entry(track.get_artist_guid().to_owned())
+                .or_default();
+            *occurrences += 1;
+
+            *occurrences <= maximum_tracks_by_artist
+        });
+    }
+
+    /// Trims the merged playlist to the track limit. A limit of `0` keeps every track.
+    pub fn apply_track_limit(&mut self, track_limit: u32) {
+        if track_limit > 0 {
+            self.merged.truncate(track_limit as usize);
+        }
+    }
+
+    /// Displays the first `count` tracks in the merged playlist in the console
+    ///
+    /// A `count` of `0` displays every track in the merged playlist
+    pub fn print_preview(&self, count: usize, relative_dates: bool) {
+        for line in self.get_preview_lines(count, relative_dates) {
+            println!("{line}")
+        }
+    }
 
-        for (i, track) in preview.iter().enumerate() {
-            println!("{:2} {}", i + 1, track)
+    /// Displays each artist in the merged playlist with their number of tracks, most tracks first
+    pub fn print_artist_counts(&self) {
+        for (artist, count) in self.get_artist_counts() {
+            println!("{count:4} {artist}")
         }
     }
+
+    /// Counts the tracks by each artist in the merged playlist, ordered by count and then by artist
+    fn get_artist_counts(&self) -> Vec<(&str, usize)> {
+        let counts =
+            self.merged
+                .iter()
+                .fold(BTreeMap::new(), |mut acc: BTreeMap<&str, usize>, track| {
+                    *acc.entry(track.get_artist_name()).or_default() += 1;
+                    acc
+                });
+
+        counts
+            .into_iter()
+            .sorted_by_key(|(_, count)| Reverse(*count))
+            .collect_vec()
+    }
+
+    /// Builds the lines displayed by [`ProfileTracks::print_preview`]
+    fn get_preview_lines(&self, count: usize, relative_dates: bool) -> Vec<String> {
+        let count = if count == 0 { self.merged.len() } else { count };
+
+        self.merged
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(i, track)| {
+                if relative_dates {
+                    format!("{:2} {}", i + 1, track.to_string_relative())
+                } else {
+                    format!("{:2} {}", i + 1, track)
+                }
+            })
+            .collect_vec()
+    }
 }
 
 /// Deduplicates one list based on values in other lists
@@ -253,10 +447,8 @@ fn deduplicate_tracks_by_lists(tracks: &mut Vec<Track>, comp: &[Track], time_lim
         let mut tracks_chunks = chunk_by_time_limit(tracks, time_limit);
         let comp_chunks = chunk_by_time_limit(comp, time_limit);
 
-        for i in 0..(tracks_chunks.len() as i32) {
-            let track_chunk = tracks_chunks.get_mut(&i);
-            let comp_chunk = comp_chunks.get(&i);
-            if let (Some(track_chunk), Some(comp_chunk)) = (track_chunk, comp_chunk) {
+        for (i, track_chunk) in tracks_chunks.iter_mut() {
+            if let Some(comp_chunk) = comp_chunks.get(i) {
                 track_chunk.retain(|track| !comp_chunk.contains(track));
             }
         }
@@ -278,74 +470,155 @@ fn deduplicate_tracks_by_lists(tracks: &mut Vec<Track>, comp: &[Track], time_lim
 /// Remove duplicate tracks by the title and artist of a track
 ///
 /// e,g, If the track "The Beatles - Get Back" appears multiple times in a playlist, any duplicates will be removed.
-fn deduplicate_by_title_and_artist(tracks: &mut Vec<Track>) {
-    *tracks = tracks
-        .iter()
-        .sorted_by_key(|track| track.get_title_and_artist_sort_key())
-        .unique_by(|track| track.get_title_and_artist_sort_key())
-        .map(|track| track.to_owned())
-        .collect_vec()
+///
+/// The [`DedupKey`] determines which fields must match, so covers by other artists can be
+/// removed as well. The sort is stable, so the first track of each key is kept, unless the
+/// [`ContentRatingPreference`] favors the explicit or clean version of the track
+fn deduplicate_by_title_and_artist(
+    tracks: &mut Vec<Track>,
+    dedup_key: DedupKey,
+    content_rating_preference: ContentRatingPreference,
+) {
+    tracks.par_sort_by_cached_key(|track| {
+        let preferred = match content_rating_preference {
+            ContentRatingPreference::NoPreference => true,
+            ContentRatingPreference::PreferExplicit => track.is_explicit(),
+            ContentRatingPreference::PreferClean => track.is_clean(),
+        };
+        (build_dedup_key(track, dedup_key), !preferred)
+    });
+    tracks.dedup_by(|a, b| build_dedup_key(a, dedup_key) == build_dedup_key(b, dedup_key));
+}
+
+fn build_dedup_key(track: &Track, dedup_key: DedupKey) -> (String, String) {
+    match dedup_key {
+        DedupKey::TitleArtist => track.get_title_and_artist_sort_key(),
+        DedupKey::TitleOnly => (track.get_track_title().to_string(), String::new()),
+        DedupKey::GuidOnly => (track.get_guid().to_string(), String::new()),
+    }
 }
 
 /// Remove duplicate tracks based on the Plex `GUID`
+///
+/// The track with the highest bitrate is kept
 fn deduplicate_by_track_guid(tracks: &mut Vec<Track>) {
-    *tracks = tracks
-        .iter()
-        .sorted_by_key(|track| (track.get_guid(), Reverse(track.get_bitrate())))
-        .unique_by(|track| track.get_guid())
-        .map(|track| track.to_owned())
-        .collect_vec()
+    tracks.par_sort_by(|a, b| {
+        (a.get_guid(), Reverse(a.get_bitrate())).cmp(&(b.get_guid(), Reverse(b.get_bitrate())))
+    });
+    tracks.dedup_by(|a, b| a.get_guid() == b.get_guid());
 }
 
 /// Trims tracks by artist limit (in other words, the maximum number of tracks that can be included in the list by a single artist)
 ///
-/// Returns early if the limit is zero
+/// The least played tracks of each artist are kept. Returns early if the limit is zero.
+///
+/// If `normalize_featured_artists` is true, tracks with a featuring credit are counted against
+/// the artist guid of the primary artist's own tracks.
 fn trim_tracks_by_artist(
     tracks: &mut Vec<Track>,
     maximum_tracks_by_artist: u32,
     section_type: SectionType,
+    normalize_featured_artists: bool,
 ) {
     if maximum_tracks_by_artist == 0 {
         return;
     }
 
+    sort_for_artist_limit(tracks, section_type);
+
+    let primary_artist_guids: HashMap<String, &str> = if normalize_featured_artists {
+        tracks
+            .iter()
+            .filter(|track| !track.has_featured_artists())
+            .map(|track| (track.get_primary_artist(), track.get_artist_guid()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let artist_guid = |track: &Track| -> String {
+        if !normalize_featured_artists || !track.has_featured_artists() {
+            return track.get_artist_guid().to_owned();
+        }
+
+        primary_artist_guids
+            .get(&track.get_primary_artist())
+            .copied()
+            .unwrap_or(track.get_artist_guid())
+            .to_owned()
+    };
+
+    // Occurrences depend on the order of the tracks, so they are counted sequentially
+    let mut artist_occurrences: HashMap<String, u32> = HashMap::new();
+    let keep = tracks
+        .iter()
+        .map(|track| {
+            let occurrences = artist_occurrences.entry(artist_guid(track)).or_default();
+            *occurrences += 1;
+
+            *occurrences <= maximum_tracks_by_artist
+        })
+        .collect_vec();
+
+    let mut keep = keep.into_iter();
+    tracks.retain(|_| keep.next().unwrap_or(false))
+}
+
+/// Sorts tracks in the order they are considered when limiting the tracks by each artist
+fn sort_for_artist_limit(tracks: &mut [Track], section_type: SectionType) {
     match section_type {
         SectionType::Oldest => {
-            tracks.sort_by_key(|track| (track.get_last_played(), track.get_plays()))
+            tracks.par_sort_by_key(|track| (track.get_last_played(), track.get_plays()))
         }
-        _ => tracks.sort_by_key(|track| (track.get_plays(), track.get_last_played())),
+        _ => tracks.par_sort_by_key(|track| (track.get_plays(), track.get_last_played())),
     }
-
-    let mut artist_occurrences: BTreeMap<String, u32> = BTreeMap::new();
-    tracks.retain(|track| {
-        let artist_guid = track.get_artist_guid().to_owned();
-        let occurrences = artist_occurrences.entry(artist_guid).or_default();
-        *occurrences += 1;
-
-        *occurrences <= maximum_tracks_by_artist
-    })
 }
 
 /// Sorts tracks for a given section
-fn sort_tracks(tracks: &mut [Track], section_type: SectionType) {
+///
+/// `use_net_plays` only applies to least played sections, and `boost_recently_added` only applies
+/// to unplayed sections.
+fn sort_tracks(
+    tracks: &mut [Track],
+    section_type: SectionType,
+    use_net_plays: bool,
+    boost_recently_added: bool,
+) {
     match section_type {
+        SectionType::Unplayed if boost_recently_added => {
+            tracks.sort_by_key(|t| (Reverse(t.get_rating()), Reverse(t.get_added_at())))
+        }
         SectionType::Unplayed => {
             tracks.sort_by_key(|t| (Reverse(t.get_rating()), t.get_plays(), t.get_last_played()))
         }
+        SectionType::LeastPlayed if use_net_plays => {
+            tracks.sort_by_key(|t| (t.get_net_plays(), t.get_last_played()))
+        }
         SectionType::LeastPlayed => tracks.sort_by_key(|t| (t.get_plays(), t.get_last_played())),
         SectionType::Oldest => tracks.sort_by_key(|t| (t.get_last_played(), t.get_plays())),
     }
 }
 
 /// Randomizes tracks for a given section
-fn randomizer(tracks: &mut Vec<Track>, section_type: SectionType) {
-    *tracks = tracks
+///
+/// The first `keep_top_n_fixed` tracks keep their sorted order and only the remaining tracks are
+/// shuffled.
+fn randomizer(
+    tracks: &mut Vec<Track>,
+    section_type: SectionType,
+    bias: RandomizationBias,
+    oldest_bucket: OldestBucket,
+    keep_top_n_fixed: u32,
+    rng: &mut impl Rng,
+) {
+    let fixed = (keep_top_n_fixed as usize).min(tracks.len());
+    let mut shuffled = tracks
+        .split_off(fixed)
         .iter()
         .fold(
             BTreeMap::new(),
             |mut acc: BTreeMap<String, Vec<Track>>, track| {
                 let key = match section_type {
-                    SectionType::Oldest => track.get_last_played_year_and_month(),
+                    SectionType::Oldest => get_oldest_bucket_key(track, oldest_bucket),
                     _ => format!(
                         "{:04}: {}",
                         track.get_plays(),
@@ -359,15 +632,78 @@ fn randomizer(tracks: &mut Vec<Track>, section_type: SectionType) {
         )
         .iter_mut()
         .fold(Vec::new(), |mut acc, (_, group)| {
-            group.shuffle(&mut rand::thread_rng());
+            weighted_shuffle(group, bias, rng);
             acc.append(group);
             acc
-        })
+        });
+
+    tracks.append(&mut shuffled);
+}
+
+/// Builds the key used to group oldest tracks by their last played date
+fn get_oldest_bucket_key(track: &Track, oldest_bucket: OldestBucket) -> String {
+    match oldest_bucket {
+        OldestBucket::Year => track.get_last_played_year(),
+        OldestBucket::YearMonth => track.get_last_played_year_and_month(),
+        OldestBucket::YearWeek => track.get_last_played_year_and_week(),
+    }
+}
+
+/// Shuffles a group of tracks, favoring tracks according to the given bias
+///
+/// Uses weighted random sampling, where each track is assigned a key of `u^(1/w)` and
+/// the tracks are then sorted by key in descending order.
+fn weighted_shuffle(tracks: &mut Vec<Track>, bias: RandomizationBias, rng: &mut impl Rng) {
+    if bias == RandomizationBias::Uniform {
+        tracks.shuffle(rng);
+        return;
+    }
+
+    let weights = get_randomization_weights(tracks, bias);
+    *tracks = tracks
+        .drain(..)
+        .zip(weights)
+        .map(|(track, weight)| (rng.random::<f64>().powf(1.0 / weight), track))
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .map(|(_, track)| track)
+        .collect_vec()
+}
+
+/// Calculates the weight of each track for a weighted shuffle
+///
+/// Every weight is at least `1.0`, so no track is ever excluded from the shuffle
+fn get_randomization_weights(tracks: &[Track], bias: RandomizationBias) -> Vec<f64> {
+    match bias {
+        RandomizationBias::Uniform => vec![1.0; tracks.len()],
+        RandomizationBias::FavorRating => tracks
+            .iter()
+            .map(|track| 1.0 + track.get_rating() as f64)
+            .collect_vec(),
+        RandomizationBias::FavorRecent => {
+            // Ranks tracks from the oldest addition (`0`) to the newest addition
+            let ranks = tracks
+                .iter()
+                .enumerate()
+                .sorted_by_key(|(_, track)| track.get_added_at())
+                .enumerate()
+                .fold(vec![0; tracks.len()], |mut acc, (rank, (i, _))| {
+                    acc[i] = rank;
+                    acc
+                });
+
+            ranks
+                .into_iter()
+                .map(|rank| 1.0 + rank as f64 / tracks.len() as f64)
+                .collect_vec()
+        }
+    }
 }
 
 /// Reduces a list of tracks to a given time limit
-fn reduce_to_time_limit(tracks: &mut Vec<Track>, time_limit: f64) {
-    let index = determine_time_limit_index(tracks, time_limit);
+///
+/// Tracks without a duration are counted as `missing_duration` milliseconds long.
+fn reduce_to_time_limit(tracks: &mut Vec<Track>, time_limit: f64, missing_duration: i64) {
+    let index = determine_time_limit_index(tracks, time_limit, missing_duration);
     *tracks = tracks
         .iter()
         .get(0..=index)
@@ -375,7 +711,7 @@ fn reduce_to_time_limit(tracks: &mut Vec<Track>, time_limit: f64) {
         .collect_vec();
 }
 
-fn determine_time_limit_index(tracks: &[Track], time_limit: f64) -> usize {
+fn determine_time_limit_index(tracks: &[Track], time_limit: f64, missing_duration: i64) -> usize {
     if time_limit == 0.0 {
         return tracks.len();
     }
@@ -383,8 +719,16 @@ fn determine_time_limit_index(tracks: &[Track], time_limit: f64) -> usize {
     // Milliseconds
     let limit = (time_limit * 60.0 * 60.0 * 1000.0).ceil() as i64;
 
+    let track_duration = |track: &Track| {
+        if track.has_duration() {
+            track.get_track_duration()
+        } else {
+            missing_duration
+        }
+    };
+
     // Milliseconds
-    let total_duration: i64 = tracks.iter().map(|track| track.get_track_duration()).sum();
+    let total_duration = tracks.iter().map(track_duration).sum::<i64>();
 
     if total_duration <= limit {
         return tracks.len();
@@ -394,7 +738,7 @@ fn determine_time_limit_index(tracks: &[Track], time_limit: f64) -> usize {
     let index = tracks
         .iter()
         .position(|track| {
-            accum_total += track.get_track_duration();
+            accum_total += track_duration(track);
             accum_total > limit
         })
         .unwrap_or(0);
@@ -408,15 +752,24 @@ fn determine_time_limit_index(tracks: &[Track], time_limit: f64) -> usize {
 ///
 /// If the list of tracks is 72 hours long and the playlist time limit is 12 hours,
 /// then 6 chunks will be returned.
+///
+/// If the time limit is less than or equal to zero, all tracks are returned in a single chunk.
 fn chunk_by_time_limit(tracks: &[Track], time_limit: f64) -> BTreeMap<i32, Vec<Track>> {
     let mut remaining_tracks = tracks.to_vec();
     let mut chunks: BTreeMap<i32, Vec<Track>> = BTreeMap::new();
 
+    if time_limit <= 0.0 {
+        if !remaining_tracks.is_empty() {
+            chunks.insert(1, remaining_tracks);
+        }
+        return chunks;
+    }
+
     let mut day = 1;
     let mut index;
 
     while !remaining_tracks.is_empty() {
-        index = determine_time_limit_index(&remaining_tracks, time_limit);
+        index = determine_time_limit_index(&remaining_tracks, time_limit, 0);
         let mut day_tracks = remaining_tracks.drain(..index).collect_vec();
 
         let entry = chunks.entry(day).or_default();
@@ -428,6 +781,52 @@ fn chunk_by_time_limit(tracks: &[Track], time_limit: f64) -> BTreeMap<i32, Vec<T
     chunks
 }
 
+/// Removes tracks with any of the excluded genres
+fn remove_excluded_genres(tracks: &mut Vec<Track>, exclude_genres: &[String]) {
+    if exclude_genres.is_empty() {
+        return;
+    }
+
+    tracks.retain(|track| !track.has_any_genre(exclude_genres));
+}
+
+/// Removes tracks that were used by one of the profile's recent refreshes
+fn remove_recent_repeats(tracks: &mut Vec<Track>, recent_guids: &HashSet<String>) {
+    if recent_guids.is_empty() {
+        return;
+    }
+
+    tracks.retain(|track| !recent_guids.contains(track.get_guid()));
+}
+
+/// Drops tracks without a duration if the section's policy says to
+fn apply_missing_duration_policy(tracks: &mut Vec<Track>, policy: MissingDurationPolicy) {
+    if policy == MissingDurationPolicy::Drop {
+        tracks.retain(Track::has_duration);
+    }
+}
+
+/// Returns the duration in milliseconds that a track without one counts against the time limit
+fn get_missing_duration(policy: MissingDurationPolicy) -> i64 {
+    match policy {
+        MissingDurationPolicy::AssumeDefault => ASSUMED_TRACK_DURATION_MS,
+        MissingDurationPolicy::CountAsZero | MissingDurationPolicy::Drop => 0,
+    }
+}
+
+/// Removes tracks shorter than `min_seconds` or longer than `max_seconds`
+///
+/// A bound of `0` is ignored. Both bounds are inclusive.
+fn remove_tracks_outside_duration(tracks: &mut Vec<Track>, min_seconds: u32, max_seconds: u32) {
+    let min_ms = i64::from(min_seconds) * 1000;
+    let max_ms = i64::from(max_seconds) * 1000;
+
+    tracks.retain(|track| {
+        let duration = track.get_track_duration();
+        (min_seconds == 0 || duration >= min_ms) && (max_seconds == 0 || duration <= max_ms)
+    });
+}
+
 fn remove_played_within_last_day(tracks: &mut Vec<Track>) {
     *tracks = tracks
         .iter()
@@ -441,22 +840,95 @@ fn remove_played_within_last_day(tracks: &mut Vec<Track>) {
         .collect_vec()
 }
 
-async fn fetch_profile_tracks(
-    plex_client: &PlexClient,
+/// A candidate track by a single artist and whether it survives its section's artist limit
+pub struct ArtistTrack {
+    pub section_type: SectionType,
+    pub track: Track,
+    /// False if the track is removed by the section's `maximum_tracks_by_artist` trim
+    pub kept: bool,
+}
+
+/// Fetches the candidate tracks of each section and returns those by the given artist
+pub async fn fetch_artist_tracks(
+    source: &impl TrackSource,
     profile: &Profile,
-) -> Result<ProfileTracks> {
+    artist_id: &str,
+) -> Result<Vec<ArtistTrack>> {
     let sections =
         db::profiles::fetch_profile_sections_for_profile(profile.get_profile_id()).await?;
 
-    let mut profile_tracks = ProfileTracksBuilder::default();
+    let mut artist_tracks = vec![];
     for section in &sections {
-        let tracks = fetch_section_tracks(
-            plex_client,
-            profile,
-            section,
-            profile.get_time_limit() as f64,
-        )
-        .await?;
+        let tracks =
+            fetch_section_tracks(source, profile, section, profile.get_time_limit() as f64).await?;
+        artist_tracks.extend(find_artist_tracks(section, &tracks, artist_id));
+    }
+
+    Ok(artist_tracks)
+}
+
+/// Returns the tracks by the given artist, marking those that survive the section's artist limit
+///
+/// Tracks are returned in the order the artist limit considers them.
+fn find_artist_tracks(
+    section: &ProfileSection,
+    tracks: &[Track],
+    artist_id: &str,
+) -> Vec<ArtistTrack> {
+    let mut candidates = tracks.to_vec();
+    sort_for_artist_limit(&mut candidates, section.get_section_type());
+
+    let mut kept = tracks.to_vec();
+    trim_tracks_by_artist(
+        &mut kept,
+        section.get_maximum_tracks_by_artist(),
+        section.get_section_type(),
+        section.get_normalize_featured_artists(),
+    );
+    let kept_ids = kept
+        .iter()
+        .map(|track| track.get_id())
+        .collect::<HashSet<_>>();
+
+    candidates
+        .iter()
+        .filter(|track| track.get_artist_id() == artist_id)
+        .map(|track| ArtistTrack {
+            section_type: section.get_section_type(),
+            track: track.clone(),
+            kept: kept_ids.contains(track.get_id()),
+        })
+        .collect()
+}
+
+pub(super) async fn fetch_profile_tracks(
+    source: &impl TrackSource,
+    profile: &Profile,
+    sections: &[ProfileSection],
+    recent_guids: &HashSet<String>,
+) -> Result<ProfileTracks> {
+    let (profile_tracks, _) =
+        fetch_profile_tracks_timed(source, profile, sections, recent_guids).await?;
+    Ok(profile_tracks)
+}
+
+async fn fetch_profile_tracks_timed(
+    source: &impl TrackSource,
+    profile: &Profile,
+    sections: &[ProfileSection],
+    recent_guids: &HashSet<String>,
+) -> Result<(ProfileTracks, PhaseTimings)> {
+    let mut timings = PhaseTimings::default();
+
+    let now = Instant::now();
+    let mut profile_tracks = ProfileTracksBuilder::default();
+    for section in sections {
+        let mut tracks =
+            fetch_section_tracks(source, profile, section, profile.get_time_limit() as f64)
+                .await
+                .map_err(|err| err.context(SourceUnavailable(profile.get_title().to_owned())))?;
+        remove_recent_repeats(&mut tracks, recent_guids);
+        check_empty_section(profile, section, &tracks)?;
 
         match section.get_section_type() {
             SectionType::Unplayed => {
@@ -473,14 +945,122 @@ async fn fetch_profile_tracks(
     let mut profile_tracks = profile_tracks
         .build()
         .expect("Profile tracks could not be built");
-    profile_tracks.run_manual_filters(&sections, profile.get_section_time_limit());
-    profile_tracks.merge();
+    timings.fetch = now.elapsed();
 
-    Ok(profile_tracks)
+    let now = Instant::now();
+    let mut rng = build_rng(profile);
+    profile_tracks.run_manual_filters(sections, profile.get_section_time_limit(), &mut rng);
+    timings.filter = now.elapsed();
+
+    let now = Instant::now();
+    let merge_order = profile.get_merge_order();
+    if profile.has_custom_merge_order() {
+        validate_merge_order(&merge_order, sections)?;
+    }
+    profile_tracks.merge(
+        &merge_order,
+        profile.get_merge_strategy(),
+        profile.get_balance_by(),
+    );
+    profile_tracks.trim_merged_by_artist(profile.get_max_tracks_by_artist_global());
+    profile_tracks.apply_track_limit(profile.get_track_limit());
+    timings.merge = now.elapsed();
+
+    Ok((profile_tracks, timings))
+}
+
+/// Builds the random number generator used to shuffle a profile's tracks
+///
+/// If the profile uses a seeded shuffle, the seed is derived from the profile id and the current
+/// refresh timestamp and logged, so that the refresh can be reproduced.
+fn build_rng(profile: &Profile) -> StdRng {
+    if !profile.get_seeded_shuffle() {
+        return StdRng::seed_from_u64(rand::thread_rng().random());
+    }
+
+    let seed = refresh_seed(profile.get_profile_id(), profile.get_current_refresh());
+    info!(
+        "Shuffling `{}` with seed {seed} (refresh at {})",
+        profile.get_title(),
+        profile.get_current_refresh()
+    );
+    StdRng::seed_from_u64(seed)
+}
+
+/// Builds the seed plex uses to randomize a section's tracks, which is derived from the refresh
+/// like [`build_rng`] if the profile uses a seeded shuffle
+fn build_random_sort_seed(profile: &Profile) -> u32 {
+    if profile.get_seeded_shuffle() {
+        refresh_seed(profile.get_profile_id(), profile.get_current_refresh()) as u32
+    } else {
+        rand::thread_rng().random()
+    }
+}
+
+/// Derives a shuffle seed from a profile id and a refresh timestamp
+pub fn refresh_seed(profile_id: i32, refresh_timestamp: i64) -> u64 {
+    (profile_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ refresh_timestamp as u64
+}
+
+/// Ensures the merge order only contains enabled section types, each at most once
+pub fn validate_merge_order(
+    merge_order: &[SectionType],
+    sections: &[ProfileSection],
+) -> Result<()> {
+    if merge_order
+        .iter()
+        .enumerate()
+        .any(|(i, section_type)| merge_order[..i].contains(section_type))
+    {
+        return Err(anyhow!(
+            "The merge order cannot contain a section more than once"
+        ));
+    }
+
+    for section_type in merge_order {
+        if !sections
+            .iter()
+            .any(|section| section.is_section_type(*section_type) && section.is_enabled())
+        {
+            return Err(anyhow!(
+                "The merge order contains `{section_type}`, which is not an enabled section"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the profile's [`EmptySectionBehavior`] if an enabled section returned no tracks
+fn check_empty_section(
+    profile: &Profile,
+    section: &ProfileSection,
+    tracks: &[Track],
+) -> Result<()> {
+    if !section.is_enabled() || !tracks.is_empty() {
+        return Ok(());
+    }
+
+    match profile.get_empty_section_behavior() {
+        EmptySectionBehavior::Skip => Ok(()),
+        EmptySectionBehavior::Warn => {
+            warn!(
+                "<b>The `{}` section of `{}` returned no tracks. Check the section's filters.</b>",
+                section.get_section_type(),
+                profile.get_title()
+            );
+            Ok(())
+        }
+        EmptySectionBehavior::Fail => Err(anyhow!(
+            "The `{}` section of `{}` returned no tracks",
+            section.get_section_type(),
+            profile.get_title()
+        )),
+    }
 }
 
 async fn fetch_section_tracks(
-    plex_client: &PlexClient,
+    source: &impl TrackSource,
     profile: &Profile,
     section: &ProfileSection,
     time_limit: f64,
@@ -490,6 +1070,159 @@ async fn fetch_section_tracks(
     if !section.is_enabled() {
         return Ok(tracks);
     }
+
+    let filters = fetch_section_filters(source, profile, section).await?;
+    let limit = get_section_track_limit(time_limit);
+    let random_sort = section
+        .get_server_random()
+        .then(|| format!("random:{}", build_random_sort_seed(profile)));
+    let sort = match &random_sort {
+        Some(random_sort) => vec![random_sort.as_str()],
+        None => section.get_sorting_vec(),
+    };
+    tracks = fetch_in_artist_chunks(source, filters, sort, limit).await?;
+
+    if section.is_oldest_section() && section.get_exclude_never_played() {
+        tracks.retain(|track| !track.get_has_never_been_played());
+    }
+
+    // Plex does not reliably apply negated genre filters, so excluded genres are also removed here
+    remove_excluded_genres(&mut tracks, section.get_exclude_genres());
+
+    let minimum_album_completeness = section.get_minimum_album_completeness();
+    if minimum_album_completeness > 0 {
+        let album_ids = tracks
+            .iter()
+            .map(|track| track.get_album_id().to_owned())
+            .unique()
+            .collect_vec();
+
+        let album_track_counts = stream::iter(album_ids)
+            .map(|album_id| async move {
+                let count = source.fetch_album_track_count(&album_id).await?;
+                Ok::<_, anyhow::Error>((album_id, count))
+            })
+            .buffer_unordered(MAX_CONCURRENT_ALBUM_REQUESTS)
+            .try_collect::<HashMap<_, _>>()
+            .await?;
+
+        filter_by_album_completeness(&mut tracks, &album_track_counts, minimum_album_completeness);
+    }
+
+    Ok(tracks)
+}
+
+/// Fetches tracks matching the filters, splitting an `artist.id` filter with more than
+/// [`MAX_ARTIST_IDS_PER_REQUEST`] ids across several requests that each keep the other filters
+///
+/// The tracks of every request are combined, skipping tracks already returned. Each request only
+/// asks for the tracks still missing from `limit`, and no more requests are made once it is
+/// reached.
+async fn fetch_in_artist_chunks(
+    source: &impl TrackSource,
+    filters: HashMap<String, String>,
+    sort: Vec<&str>,
+    limit: Option<i32>,
+) -> Result<Vec<Track>> {
+    // Artist ids are numeric, so the joined filter can be split on commas without unescaping
+    let artist_ids = match filters.get("artist.id") {
+        Some(artist_ids) if artist_ids.split(',').count() > MAX_ARTIST_IDS_PER_REQUEST => {
+            artist_ids.split(',').map(str::to_owned).collect_vec()
+        }
+        _ => return source.fetch(filters, sort, limit).await,
+    };
+
+    let mut tracks = vec![];
+    let mut seen = HashSet::new();
+    for chunk in artist_ids.chunks(MAX_ARTIST_IDS_PER_REQUEST) {
+        let remaining = limit.map(|limit| limit - tracks.len() as i32);
+        if remaining.is_some_and(|remaining| remaining <= 0) {
+            break;
+        }
+
+        let mut chunk_filters = filters.clone();
+        chunk_filters.insert(
+            "artist.id".to_string(),
+            http_client::join_filter_values(chunk),
+        );
+        for track in source.fetch(chunk_filters, sort.clone(), remaining).await? {
+            if seen.insert(track.get_id().to_owned()) {
+                tracks.push(track);
+            }
+        }
+    }
+
+    Ok(tracks)
+}
+
+/// Removes tracks from albums where less than the minimum percentage of the album's tracks qualify
+///
+/// Albums missing from `album_track_counts` are kept
+fn filter_by_album_completeness(
+    tracks: &mut Vec<Track>,
+    album_track_counts: &HashMap<String, u32>,
+    minimum_album_completeness: u32,
+) {
+    let qualifying_counts = tracks
+        .iter()
+        .counts_by(|track| track.get_album_id().to_owned());
+
+    tracks.retain(|track| {
+        let Some(total) = album_track_counts.get(track.get_album_id()) else {
+            return true;
+        };
+        if *total == 0 {
+            return true;
+        }
+
+        let qualifying = qualifying_counts
+            .get(track.get_album_id())
+            .copied()
+            .unwrap_or_default();
+        qualifying * 100 >= (*total * minimum_album_completeness) as usize
+    })
+}
+
+/// Fetches any data required from plex and builds the filters for a section
+pub async fn fetch_section_filters(
+    source: &impl TrackSource,
+    profile: &Profile,
+    section: &ProfileSection,
+) -> Result<HashMap<String, String>> {
+    let source_artists = match profile.get_profile_source() {
+        ProfileSource::Collection => Some(
+            source
+                .fetch_collection_artists(profile.get_profile_source_id().unwrap())
+                .await?,
+        ),
+        ProfileSource::Hub => Some(
+            source
+                .fetch_hub_artists(profile.get_hub_identifier().unwrap())
+                .await?,
+        ),
+        _ => None,
+    };
+
+    Ok(build_section_filters(
+        profile,
+        section,
+        source_artists.as_deref(),
+    ))
+}
+
+/// Builds the filters sent to plex when fetching tracks for a section
+///
+/// `source_artists` are the artist ids included in the profile's collection or hub, and are
+/// only used when the profile source is a collection or hub. A hub without any artists falls
+/// back to the entire library.
+///
+/// The section's extra filters are added last, so they replace any built-in filter with the same
+/// key.
+pub fn build_section_filters(
+    profile: &Profile,
+    section: &ProfileSection,
+    source_artists: Option<&[String]>,
+) -> HashMap<String, String> {
     let mut filters = HashMap::new();
     if section.get_minimum_track_rating_adjusted() != 0 {
         filters.insert(
@@ -500,23 +1233,40 @@ async fn fetch_section_tracks(
 
     if section.is_unplayed_section() {
         filters.insert("viewCount".to_string(), "0".to_string());
-    } else {
+    } else if section.get_minimum_plays() > 1 {
+        // Plex has no "greater than or equal" operator, so filter for one play fewer
+        filters.insert(
+            "viewCount>>".to_string(),
+            (section.get_minimum_plays() - 1).to_string(),
+        );
+    } else if !(section.is_least_played_section() && section.get_least_played_includes_unplayed()) {
+        // Least played sections that include never-played tracks need no play count filter
         filters.insert("viewCount>>".to_string(), "0".to_string());
     }
 
+    if !section.get_exclude_genres().is_empty() {
+        filters.insert(
+            "genre!".to_string(),
+            http_client::join_filter_values(section.get_exclude_genres()),
+        );
+    }
+
     match profile.get_profile_source() {
         // Nothing special needs to be done for a library source, so this branch is left blank
         ProfileSource::Library => {}
         ProfileSource::Collection => {
-            let collection = plex_client
-                .fetch_collection(profile.get_profile_source_id().unwrap())
-                .await?;
-            let artists = plex_client
-                .fetch_artists_from_collection(&collection)
-                .await?;
-            let artists = artists.join(",");
+            let artists = http_client::join_filter_values(source_artists.unwrap_or_default());
             filters.insert("artist.id".to_string(), artists);
         }
+        ProfileSource::Hub => {
+            let artists = source_artists.unwrap_or_default();
+            if !artists.is_empty() {
+                filters.insert(
+                    "artist.id".to_string(),
+                    http_client::join_filter_values(artists),
+                );
+            }
+        }
         ProfileSource::SingleArtist => {
             filters.insert(
                 "artist.id".to_string(),
@@ -525,14 +1275,1464 @@ async fn fetch_section_tracks(
         }
     }
 
-    let limit = if time_limit <= 0.0 {
+    filters.extend(
+        section
+            .get_extra_filters()
+            .iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned())),
+    );
+
+    filters
+}
+
+/// Explains why the order of a profile's playlist matters, or returns `None` if its tracks are
+/// shuffled and sorting the playlist in plex loses nothing
+///
+/// Sections are always merged in the profile's merge order, so any profile with more than one
+/// enabled section relies on the order of its items. A single section only does if it is sorted.
+pub fn item_order_reason(profile: &Profile, sections: &[ProfileSection]) -> Option<String> {
+    let enabled = sections
+        .iter()
+        .filter(|section| section.is_enabled())
+        .collect_vec();
+
+    match enabled.as_slice() {
+        [] => None,
+        [section] if section.get_randomize_tracks() || section.get_server_random() => None,
+        [section] => Some(format!(
+            "its {} are sorted by `{}`",
+            section.get_section_type().to_string().to_lowercase(),
+            section.get_sorting()
+        )),
+        _ => {
+            let merge_order = profile
+                .get_merge_order()
+                .into_iter()
+                .filter(|section_type| {
+                    enabled
+                        .iter()
+                        .any(|section| section.get_section_type() == *section_type)
+                })
+                .map(|section_type| section_type.to_string().to_lowercase())
+                .join(", then ");
+            let strategy = match profile.get_merge_strategy() {
+                MergeStrategy::Interleave => "interleaved",
+                MergeStrategy::Concatenate => "concatenated",
+            };
+            Some(format!("its sections are {strategy} as {merge_order}"))
+        }
+    }
+}
+
+/// Determines the maximum number of tracks to fetch from plex for a section
+pub fn get_section_track_limit(time_limit: f64) -> Option<i32> {
+    if time_limit <= 0.0 {
         None
     } else {
         Some((400.0 * (time_limit / 12.0)).floor() as i32)
-    };
-    tracks = plex_client
-        .fetch_music(filters, section.get_sorting_vec(), limit)
-        .await?;
+    }
+}
 
-    Ok(tracks)
+#[cfg(test)]
+mod tests {
+    use crate::plex::mock_server::MockServer;
+    use crate::plex::models::tracks::TrackBuilder;
+    use crate::profiles::profile::ProfileBuilder;
+    use crate::profiles::profile_section::ProfileSectionBuilder;
+    use crate::profiles::track_source::FixtureTrackSource;
+    use crate::profiles::DEFAULT_MERGE_ORDER;
+    use crate::types::profiles::profile_source_id::ProfileSourceId;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const RANDOMIZER_RUNS: usize = 1000;
+
+    /// Builds a track with the given id, rating (in stars), and added at timestamp (in seconds)
+    fn build_track(id: &str, rating: u32, added_at: i64) -> Track {
+        TrackBuilder::default()
+            .id(id)
+            .rating((rating * 2) as f32)
+            .added_at(added_at)
+            .build()
+    }
+
+    /// Builds a track with the given id and number of plays
+    fn build_played_track(id: &str, plays: i32) -> Track {
+        build_played_track_with(id, plays).build()
+    }
+
+    /// Starts building a track with the given id and number of plays
+    fn build_played_track_with(id: &str, plays: i32) -> TrackBuilder {
+        TrackBuilder::default().id(id).plays(plays)
+    }
+
+    #[test]
+    fn test_sort_unplayed_boosts_recently_added() {
+        let build_added_track = |id: &str, rating: f32, added_at: i64| {
+            build_played_track_with(id, 0)
+                .rating(rating)
+                .added_at(added_at)
+                .build()
+        };
+        let tracks = vec![
+            build_added_track("100001", 8.0, 1_600_000_000),
+            build_added_track("100002", 8.0, 1_700_000_000),
+            build_added_track("100003", 10.0, 1_500_000_000),
+            build_added_track("100004", 6.0, 1_750_000_000),
+        ];
+
+        let mut unboosted = tracks.clone();
+        sort_tracks(&mut unboosted, SectionType::Unplayed, false, false);
+        let mut boosted = tracks.clone();
+        sort_tracks(&mut boosted, SectionType::Unplayed, false, true);
+
+        // Tracks of equal rating keep their order without the boost
+        assert_eq!(
+            vec!["100003", "100001", "100002", "100004"],
+            unboosted.iter().map(|t| t.get_id()).collect::<Vec<_>>()
+        );
+        // The boost only reorders tracks of equal rating, never lifting a lower rated track
+        assert_eq!(
+            vec!["100003", "100002", "100001", "100004"],
+            boosted.iter().map(|t| t.get_id()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_sort_least_played_by_net_plays() {
+        let skipped = build_played_track_with("100001", 5).skips(4).build();
+        let tracks = vec![skipped, build_played_track("100002", 3)];
+
+        let mut by_plays = tracks.clone();
+        sort_tracks(&mut by_plays, SectionType::LeastPlayed, false, false);
+        let mut by_net_plays = tracks.clone();
+        sort_tracks(&mut by_net_plays, SectionType::LeastPlayed, true, false);
+
+        assert_eq!(
+            vec!["100002", "100001"],
+            by_plays.iter().map(|t| t.get_id()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["100001", "100002"],
+            by_net_plays.iter().map(|t| t.get_id()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_randomizer_keeps_top_n_fixed() {
+        let tracks = (100001..=100010)
+            .map(|id| build_played_track(&id.to_string(), 1))
+            .collect::<Vec<_>>();
+
+        let tails = (0..10)
+            .map(|seed| {
+                let mut randomized = tracks.clone();
+                randomizer(
+                    &mut randomized,
+                    SectionType::LeastPlayed,
+                    RandomizationBias::Uniform,
+                    OldestBucket::default(),
+                    3,
+                    &mut StdRng::seed_from_u64(seed),
+                );
+
+                assert_eq!(
+                    vec!["100001", "100002", "100003"],
+                    randomized[..3]
+                        .iter()
+                        .map(|t| t.get_id())
+                        .collect::<Vec<_>>()
+                );
+                randomized[3..]
+                    .iter()
+                    .map(|t| t.get_id().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashSet<_>>();
+
+        assert!(tails.len() > 1);
+    }
+
+    /// Counts how often the track with the given id is placed first after randomizing
+    fn count_first_place(tracks: &[Track], id: &str, bias: RandomizationBias) -> usize {
+        let mut rng = StdRng::seed_from_u64(42);
+        (0..RANDOMIZER_RUNS)
+            .filter(|_| {
+                let mut tracks = tracks.to_vec();
+                randomizer(
+                    &mut tracks,
+                    SectionType::LeastPlayed,
+                    bias,
+                    OldestBucket::default(),
+                    0,
+                    &mut rng,
+                );
+                tracks[0].get_id() == id
+            })
+            .count()
+    }
+
+    fn build_section(section_type: SectionType, minimum_track_rating: u32) -> ProfileSection {
+        ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(minimum_track_rating)
+            .randomize_tracks(false)
+            .section_type(section_type)
+            .sorting("viewCount".to_string())
+            .build()
+            .unwrap()
+    }
+
+    /// Builds a track with the given id and number of plays by the given artist
+    fn build_artist_track(id: &str, artist_id: &str, plays: i32) -> Track {
+        build_played_track_with(id, plays)
+            .artist_id(artist_id)
+            .artist_guid(&format!("plex://artist/{artist_id}"))
+            .build()
+    }
+
+    fn build_profile(profile_source: ProfileSource, profile_source_id: Option<&str>) -> Profile {
+        ProfileBuilder::default()
+            .profile_source(profile_source)
+            .profile_source_id(profile_source_id.map(|id| ProfileSourceId::try_new(id).unwrap()))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build_section_filters_hub() {
+        let profile = build_profile(ProfileSource::Hub, None);
+        let section = build_section(SectionType::LeastPlayed, 0);
+        let artists = vec!["1041".to_string(), "2002".to_string()];
+
+        let filters = build_section_filters(&profile, &section, Some(&artists));
+        let expected = HashMap::from([
+            ("viewCount>>".to_string(), "0".to_string()),
+            ("artist.id".to_string(), "1041,2002".to_string()),
+        ]);
+        assert_eq!(expected, filters);
+
+        // An unavailable hub falls back to the entire library
+        let filters = build_section_filters(&profile, &section, Some(&[]));
+        let expected = HashMap::from([("viewCount>>".to_string(), "0".to_string())]);
+        assert_eq!(expected, filters);
+    }
+
+    #[test]
+    fn test_build_section_filters_least_played_includes_unplayed() {
+        let profile = build_profile(ProfileSource::Library, None);
+        let build_least_played = |least_played_includes_unplayed: bool| {
+            ProfileSectionBuilder::default()
+                .deduplicate_tracks_by_guid(false)
+                .deduplicate_tracks_by_title_and_artist(false)
+                .enabled(true)
+                .maximum_tracks_by_artist(0)
+                .minimum_track_rating(0)
+                .randomize_tracks(false)
+                .least_played_includes_unplayed(least_played_includes_unplayed)
+                .section_type(SectionType::LeastPlayed)
+                .sorting("viewCount".to_string())
+                .build()
+                .unwrap()
+        };
+
+        let filters = build_section_filters(&profile, &build_least_played(false), None);
+        let expected = HashMap::from([("viewCount>>".to_string(), "0".to_string())]);
+        assert_eq!(expected, filters);
+
+        let filters = build_section_filters(&profile, &build_least_played(true), None);
+        assert_eq!(HashMap::new(), filters);
+    }
+
+    #[test]
+    fn test_build_section_filters_minimum_plays() {
+        let profile = build_profile(ProfileSource::Library, None);
+        let build_section = |section_type, minimum_plays, least_played_includes_unplayed| {
+            ProfileSectionBuilder::default()
+                .deduplicate_tracks_by_guid(false)
+                .deduplicate_tracks_by_title_and_artist(false)
+                .enabled(true)
+                .maximum_tracks_by_artist(0)
+                .minimum_track_rating(0)
+                .randomize_tracks(false)
+                .least_played_includes_unplayed(least_played_includes_unplayed)
+                .minimum_plays(minimum_plays)
+                .section_type(section_type)
+                .sorting("viewCount".to_string())
+                .build()
+                .unwrap()
+        };
+
+        let filters = build_section_filters(
+            &profile,
+            &build_section(SectionType::Oldest, 1, false),
+            None,
+        );
+        let expected = HashMap::from([("viewCount>>".to_string(), "0".to_string())]);
+        assert_eq!(expected, filters);
+
+        let filters = build_section_filters(
+            &profile,
+            &build_section(SectionType::LeastPlayed, 3, false),
+            None,
+        );
+        let expected = HashMap::from([("viewCount>>".to_string(), "2".to_string())]);
+        assert_eq!(expected, filters);
+
+        // A minimum number of plays still applies when never-played tracks are included
+        let filters = build_section_filters(
+            &profile,
+            &build_section(SectionType::LeastPlayed, 3, true),
+            None,
+        );
+        assert_eq!(expected, filters);
+
+        let filters = build_section_filters(
+            &profile,
+            &build_section(SectionType::Unplayed, 3, false),
+            None,
+        );
+        let expected = HashMap::from([("viewCount".to_string(), "0".to_string())]);
+        assert_eq!(expected, filters);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_profile_tracks_from_source() {
+        let source = FixtureTrackSource::new(vec![
+            build_played_track("1001", 0),
+            build_played_track("1002", 3),
+            build_played_track("1003", 0),
+            build_played_track("1004", 1),
+            build_played_track("1005", 2),
+        ]);
+        let profile = build_profile(ProfileSource::Library, None);
+        let sections = vec![
+            build_section(SectionType::Unplayed, 0),
+            build_section(SectionType::LeastPlayed, 0),
+        ];
+
+        let profile_tracks = fetch_profile_tracks(&source, &profile, &sections, &HashSet::new())
+            .await
+            .unwrap();
+
+        let expected = vec!["1001", "1004", "1003", "1005", "1002"];
+        assert_eq!(expected, profile_tracks.get_track_ids());
+    }
+
+    #[tokio::test]
+    async fn test_server_random_sends_random_sort() {
+        let server = crate::plex::mock_server::MockServer::start(|_| {
+            r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+        })
+        .await;
+        let profile = ProfileBuilder::default()
+            .profile_id(7)
+            .seeded_shuffle(true)
+            .current_refresh(1_700_000_000)
+            .build()
+            .unwrap();
+        let section = ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(0)
+            .randomize_tracks(false)
+            .section_type(SectionType::Unplayed)
+            .server_random(true)
+            .sorting("userRating:desc".to_string())
+            .build()
+            .unwrap();
+
+        fetch_section_tracks(&server.client(), &profile, &section, 12.0)
+            .await
+            .unwrap();
+
+        let requests = server.get_requests();
+        let (_, path) = requests[0].split_once(' ').unwrap();
+        let url = reqwest::Url::parse(&format!("{}{}", server.get_url(), &path[1..])).unwrap();
+        let query = url.query_pairs().collect::<HashMap<_, _>>();
+        assert_eq!(
+            format!("random:{}", refresh_seed(7, 1_700_000_000) as u32),
+            query["sort"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extra_filters_override_built_in_filters() {
+        let server = crate::plex::mock_server::MockServer::start(|_| {
+            r#"{"MediaContainer": {"size": 0, "Metadata": []}}"#.to_string()
+        })
+        .await;
+        let profile = ProfileBuilder::default().build().unwrap();
+        let section = ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(3)
+            .randomize_tracks(false)
+            .section_type(SectionType::Unplayed)
+            .extra_filters(HashMap::from([
+                ("decade".to_string(), "1990".to_string()),
+                ("userRating>>".to_string(), "8".to_string()),
+            ]))
+            .sorting("userRating:desc".to_string())
+            .build()
+            .unwrap();
+
+        fetch_section_tracks(&server.client(), &profile, &section, 12.0)
+            .await
+            .unwrap();
+
+        let requests = server.get_requests();
+        let (_, path) = requests[0].split_once(' ').unwrap();
+        let url = reqwest::Url::parse(&format!("{}{}", server.get_url(), &path[1..])).unwrap();
+        let query = url.query_pairs().collect::<HashMap<_, _>>();
+        assert_eq!("1990", query["decade"]);
+        assert_eq!("8", query["userRating>>"]);
+        assert_eq!("0", query["viewCount"]);
+    }
+
+    #[tokio::test]
+    async fn test_large_artist_filter_is_chunked() {
+        let server = crate::plex::mock_server::MockServer::start(|path| {
+            // Every chunk returns the same track, which is only kept once
+            let tracks = if path.contains("artist.id=1000,") {
+                vec![build_track("5001", 0, 0)]
+            } else {
+                vec![build_track("5001", 0, 0), build_track("5002", 0, 0)]
+            };
+            serde_json::json!({"MediaContainer": {"size": tracks.len(), "Metadata": tracks}})
+                .to_string()
+        })
+        .await;
+        let artist_ids = (1000..1250).map(|id| id.to_string()).collect_vec();
+        let filters = HashMap::from([
+            (
+                "artist.id".to_string(),
+                http_client::join_filter_values(&artist_ids),
+            ),
+            ("userRating>>".to_string(), "6".to_string()),
+            ("genre".to_string(), "Jazz".to_string()),
+        ]);
+
+        let tracks = fetch_in_artist_chunks(&server.client(), filters, vec!["viewCount"], None)
+            .await
+            .unwrap();
+
+        let ids = tracks.iter().map(|track| track.get_id()).collect_vec();
+        assert_eq!(vec!["5001", "5002"], ids);
+
+        let requests = server.get_requests();
+        assert_eq!(3, requests.len());
+        let mut requested_ids = vec![];
+        for request in requests {
+            let (_, path) = request.split_once(' ').unwrap();
+            let url = reqwest::Url::parse(&format!("{}{}", server.get_url(), &path[1..])).unwrap();
+            let query = url.query_pairs().collect::<HashMap<_, _>>();
+            assert_eq!("6", query["userRating>>"]);
+            assert_eq!("Jazz", query["genre"]);
+
+            let chunk = query["artist.id"]
+                .split(',')
+                .map(str::to_owned)
+                .collect_vec();
+            assert!(chunk.len() <= MAX_ARTIST_IDS_PER_REQUEST);
+            requested_ids.extend(chunk);
+        }
+        assert_eq!(artist_ids, requested_ids);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_artist_filter_honors_limit() {
+        // Two tracks by an artist in each of the three chunks
+        let source = FixtureTrackSource::new(vec![
+            build_played_track_with("5001", 0).artist_id("1000").build(),
+            build_played_track_with("5002", 0).artist_id("1000").build(),
+            build_played_track_with("5003", 0).artist_id("1100").build(),
+            build_played_track_with("5004", 0).artist_id("1100").build(),
+            build_played_track_with("5005", 0).artist_id("1200").build(),
+            build_played_track_with("5006", 0).artist_id("1200").build(),
+        ]);
+        let artist_ids = (1000..1250).map(|id| id.to_string()).collect_vec();
+        let filters = HashMap::from([(
+            "artist.id".to_string(),
+            http_client::join_filter_values(&artist_ids),
+        )]);
+
+        let tracks = fetch_in_artist_chunks(&source, filters.clone(), vec![], Some(3))
+            .await
+            .unwrap();
+        let ids = tracks.iter().map(|track| track.get_id()).collect_vec();
+        assert_eq!(vec!["5001", "5002", "5003"], ids);
+
+        let tracks = fetch_in_artist_chunks(&source, filters, vec![], None)
+            .await
+            .unwrap();
+        assert_eq!(6, tracks.len());
+    }
+
+    #[test]
+    fn test_item_order_reason() {
+        let build_section = |section_type, randomize_tracks| {
+            ProfileSectionBuilder::default()
+                .deduplicate_tracks_by_guid(false)
+                .deduplicate_tracks_by_title_and_artist(false)
+                .enabled(true)
+                .maximum_tracks_by_artist(0)
+                .minimum_track_rating(0)
+                .randomize_tracks(randomize_tracks)
+                .section_type(section_type)
+                .sorting("viewCount".to_string())
+                .build()
+                .unwrap()
+        };
+        let profile = ProfileBuilder::default().build().unwrap();
+
+        assert_eq!(None, item_order_reason(&profile, &[]));
+        assert_eq!(
+            None,
+            item_order_reason(&profile, &[build_section(SectionType::LeastPlayed, true)])
+        );
+        assert_eq!(
+            Some("its least played tracks are sorted by `viewCount`".to_string()),
+            item_order_reason(&profile, &[build_section(SectionType::LeastPlayed, false)])
+        );
+        assert_eq!(
+            Some("its sections are interleaved as unplayed tracks, then oldest tracks".to_string()),
+            item_order_reason(
+                &profile,
+                &[
+                    build_section(SectionType::Oldest, true),
+                    build_section(SectionType::Unplayed, true),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_duration_policy() {
+        let tracks = [
+            ("1001", Some(180_000)),
+            ("1002", None),
+            ("1003", None),
+            ("1004", Some(180_000)),
+            ("1005", Some(180_000)),
+        ]
+        .into_iter()
+        .map(|(id, duration)| match duration {
+            Some(duration) => build_played_track_with(id, 1).duration(duration).build(),
+            None => build_played_track(id, 1),
+        })
+        .collect_vec();
+        // Nine minutes
+        let time_limit = 0.15;
+
+        let limit_ids = |policy: MissingDurationPolicy| {
+            let mut tracks = tracks.clone();
+            apply_missing_duration_policy(&mut tracks, policy);
+            reduce_to_time_limit(&mut tracks, time_limit, get_missing_duration(policy));
+            tracks
+                .iter()
+                .map(|track| track.get_id().to_string())
+                .collect_vec()
+        };
+
+        assert_eq!(
+            vec!["1001", "1002", "1003", "1004", "1005"],
+            limit_ids(MissingDurationPolicy::CountAsZero)
+        );
+        assert_eq!(
+            vec!["1001", "1004", "1005"],
+            limit_ids(MissingDurationPolicy::Drop)
+        );
+        assert_eq!(
+            vec!["1001", "1002", "1003"],
+            limit_ids(MissingDurationPolicy::AssumeDefault)
+        );
+
+        // The assumed duration only counts against the time limit
+        let mut assumed = tracks.clone();
+        reduce_to_time_limit(&mut assumed, time_limit, ASSUMED_TRACK_DURATION_MS);
+        assert!(!assumed[1].has_duration());
+    }
+
+    #[test]
+    fn test_remove_tracks_outside_duration() {
+        let build_tracks = || {
+            [
+                ("1001", 14_999),
+                ("1002", 15_000),
+                ("1003", 240_000),
+                ("1004", 1_200_000),
+                ("1005", 1_200_001),
+            ]
+            .into_iter()
+            .map(|(id, duration)| build_played_track_with(id, 1).duration(duration).build())
+            .collect_vec()
+        };
+        let ids = |tracks: &[Track]| {
+            tracks
+                .iter()
+                .map(|track| track.get_id().to_string())
+                .collect_vec()
+        };
+
+        let mut tracks = build_tracks();
+        remove_tracks_outside_duration(&mut tracks, 0, 0);
+        assert_eq!(5, tracks.len());
+
+        let mut tracks = build_tracks();
+        remove_tracks_outside_duration(&mut tracks, 15, 0);
+        assert_eq!(vec!["1002", "1003", "1004", "1005"], ids(&tracks));
+
+        let mut tracks = build_tracks();
+        remove_tracks_outside_duration(&mut tracks, 0, 1200);
+        assert_eq!(vec!["1001", "1002", "1003", "1004"], ids(&tracks));
+
+        let mut tracks = build_tracks();
+        remove_tracks_outside_duration(&mut tracks, 15, 1200);
+        assert_eq!(vec!["1002", "1003", "1004"], ids(&tracks));
+    }
+
+    #[test]
+    fn test_remove_excluded_genres() {
+        let build_track_with_genres =
+            |id: &str, genres: &[&str]| build_played_track_with(id, 1).genres(genres).build();
+        let mut tracks = vec![
+            build_track_with_genres("1001", &["Rock"]),
+            build_track_with_genres("1002", &["Holiday", "Pop"]),
+            build_track_with_genres("1003", &[]),
+            build_track_with_genres("1004", &["holiday"]),
+        ];
+
+        remove_excluded_genres(&mut tracks, &[]);
+        assert_eq!(4, tracks.len());
+
+        remove_excluded_genres(&mut tracks, &["Holiday".to_string()]);
+        assert_eq!(
+            vec!["1001", "1003"],
+            tracks.iter().map(|track| track.get_id()).collect_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exclude_never_played_from_oldest() {
+        // Played tracks without a last played timestamp sort as the oldest tracks
+        let played = build_played_track_with("1002", 3)
+            .last_viewed_at(1_700_000_000)
+            .build();
+        let source = FixtureTrackSource::new(vec![build_played_track("1001", 2), played]);
+        let profile = build_profile(ProfileSource::Library, None);
+
+        let section = build_section(SectionType::Oldest, 0);
+        let tracks = fetch_section_tracks(&source, &profile, &section, 0.0)
+            .await
+            .unwrap();
+        assert_eq!(2, tracks.len());
+
+        let section = ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(0)
+            .minimum_track_rating(0)
+            .randomize_tracks(false)
+            .section_type(SectionType::Oldest)
+            .exclude_never_played(true)
+            .sorting("lastViewedAt".to_string())
+            .build()
+            .unwrap();
+        let tracks = fetch_section_tracks(&source, &profile, &section, 0.0)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["1002"],
+            tracks.iter().map(|t| t.get_id()).collect_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_avoid_recent_repeats() {
+        let build_track_with_guid = |id: &str| {
+            build_played_track_with(id, 0)
+                .guid(&format!("plex://track/{id}"))
+                .build()
+        };
+        let source = FixtureTrackSource::new(vec![
+            build_track_with_guid("1001"),
+            build_track_with_guid("1002"),
+            build_track_with_guid("1003"),
+        ]);
+        let profile = ProfileBuilder::default()
+            .avoid_recent_repeats_cycles(1)
+            .build()
+            .unwrap();
+        let sections = vec![build_section(SectionType::Unplayed, 0)];
+
+        let first = fetch_profile_tracks(&source, &profile, &sections, &HashSet::new())
+            .await
+            .unwrap();
+        assert_eq!(vec!["1001", "1002", "1003"], first.get_track_ids());
+
+        // The track used last cycle is excluded from this cycle
+        let recent_guids = HashSet::from(["plex://track/1002".to_string()]);
+        let second = fetch_profile_tracks(&source, &profile, &sections, &recent_guids)
+            .await
+            .unwrap();
+        assert_eq!(vec!["1001", "1003"], second.get_track_ids());
+    }
+
+    async fn fetch_with_empty_section(behavior: EmptySectionBehavior) -> Result<ProfileTracks> {
+        // Only played tracks are available, so the unplayed section is empty
+        let source = FixtureTrackSource::new(vec![
+            build_played_track("1001", 1),
+            build_played_track("1002", 2),
+        ]);
+        let profile = ProfileBuilder::default()
+            .empty_section_behavior(behavior)
+            .build()
+            .unwrap();
+        let sections = vec![
+            build_section(SectionType::Unplayed, 0),
+            build_section(SectionType::LeastPlayed, 0),
+        ];
+
+        fetch_profile_tracks(&source, &profile, &sections, &HashSet::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_empty_section_skip() {
+        let profile_tracks = fetch_with_empty_section(EmptySectionBehavior::Skip)
+            .await
+            .unwrap();
+        assert_eq!(vec!["1001", "1002"], profile_tracks.get_track_ids());
+    }
+
+    #[tokio::test]
+    async fn test_empty_section_warn() {
+        let profile_tracks = fetch_with_empty_section(EmptySectionBehavior::Warn)
+            .await
+            .unwrap();
+        assert_eq!(vec!["1001", "1002"], profile_tracks.get_track_ids());
+    }
+
+    #[tokio::test]
+    async fn test_empty_section_fail() {
+        let result = fetch_with_empty_section(EmptySectionBehavior::Fail).await;
+        // An empty section can never succeed on retry
+        assert!(!result.err().unwrap().is::<SourceUnavailable>());
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_is_source_unavailable() {
+        let server = MockServer::start(|_| "Internal Server Error".to_string()).await;
+        let profile = ProfileBuilder::default().build().unwrap();
+        let sections = vec![build_section(SectionType::Unplayed, 0)];
+
+        let result =
+            fetch_profile_tracks(&server.client(), &profile, &sections, &HashSet::new()).await;
+        assert!(result.err().unwrap().is::<SourceUnavailable>());
+    }
+
+    #[test]
+    fn test_merge_custom_order() {
+        let mut profile_tracks = ProfileTracksBuilder::default()
+            .unplayed(vec![
+                build_played_track("1001", 0),
+                build_played_track("1002", 0),
+            ])
+            .least_played(vec![build_played_track("2001", 1)])
+            .oldest(vec![
+                build_played_track("3001", 5),
+                build_played_track("3002", 6),
+            ])
+            .build()
+            .unwrap();
+
+        profile_tracks.merge(
+            &DEFAULT_MERGE_ORDER,
+            MergeStrategy::Interleave,
+            BalanceBy::TrackCount,
+        );
+        assert_eq!(
+            vec!["1001", "2001", "3001", "1002", "3002"],
+            profile_tracks.get_track_ids()
+        );
+
+        profile_tracks.merge(
+            &[
+                SectionType::Oldest,
+                SectionType::Unplayed,
+                SectionType::LeastPlayed,
+            ],
+            MergeStrategy::Interleave,
+            BalanceBy::TrackCount,
+        );
+        assert_eq!(
+            vec!["3001", "1001", "2001", "3002", "1002"],
+            profile_tracks.get_track_ids()
+        );
+    }
+
+    #[test]
+    fn test_trim_merged_by_artist() {
+        // Each section is within its own limit of two tracks by the same artist
+        let mut profile_tracks = ProfileTracksBuilder::default()
+            .unplayed(vec![
+                build_artist_track("1001", "900001", 0),
+                build_artist_track("1002", "900001", 0),
+            ])
+            .least_played(vec![
+                build_artist_track("2001", "900001", 1),
+                build_artist_track("2002", "900002", 1),
+            ])
+            .oldest(vec![
+                build_artist_track("3001", "900001", 5),
+                build_artist_track("3002", "900001", 6),
+            ])
+            .build()
+            .unwrap();
+        profile_tracks.merge(
+            &DEFAULT_MERGE_ORDER,
+            MergeStrategy::Interleave,
+            BalanceBy::TrackCount,
+        );
+
+        profile_tracks.trim_merged_by_artist(0);
+        assert_eq!(6, profile_tracks.get_track_ids().len());
+
+        profile_tracks.trim_merged_by_artist(3);
+        assert_eq!(
+            vec!["1001", "2001", "3001", "2002"],
+            profile_tracks.get_track_ids()
+        );
+    }
+
+    #[test]
+    fn test_merge_balance_by_duration() {
+        let build_timed_track = |id: String, seconds: i64| {
+            build_played_track_with(&id, 0)
+                .duration(seconds * 1000)
+                .build()
+        };
+        let mut profile_tracks = ProfileTracksBuilder::default()
+            .unplayed(
+                (0..4)
+                    .map(|i| build_timed_track(format!("100{i}"), 600))
+                    .collect_vec(),
+            )
+            .least_played(
+                (0..20)
+                    .map(|i| build_timed_track(format!("200{i}"), 120))
+                    .collect_vec(),
+            )
+            .build()
+            .unwrap();
+
+        // Returns the largest difference in playtime between the sections at any point in the
+        // merged playlist
+        let max_imbalance = |merged: &[Track]| {
+            let (mut unplayed, mut least_played) = (0, 0);
+            let mut max_imbalance = 0;
+            for track in merged {
+                if track.get_id().starts_with('1') {
+                    unplayed += track.get_track_duration();
+                } else {
+                    least_played += track.get_track_duration();
+                }
+                max_imbalance = max_imbalance.max((unplayed - least_played).abs());
+            }
+            max_imbalance
+        };
+
+        profile_tracks.merge(
+            &DEFAULT_MERGE_ORDER,
+            MergeStrategy::Interleave,
+            BalanceBy::TrackCount,
+        );
+        assert!(max_imbalance(profile_tracks.get_merged_tracks()) > 600_000);
+
+        profile_tracks.merge(
+            &DEFAULT_MERGE_ORDER,
+            MergeStrategy::Interleave,
+            BalanceBy::Duration,
+        );
+        assert_eq!(24, profile_tracks.get_merged_tracks().len());
+        assert!(max_imbalance(profile_tracks.get_merged_tracks()) <= 600_000);
+        assert_eq!(
+            vec!["1000", "2000", "2001", "2002", "2003", "2004", "1001"],
+            profile_tracks.get_track_ids()[..7]
+        );
+    }
+
+    #[test]
+    fn test_merge_concatenate() {
+        let mut profile_tracks = ProfileTracksBuilder::default()
+            .unplayed(vec![
+                build_played_track("1001", 0),
+                build_played_track("1002", 0),
+            ])
+            .least_played(vec![build_played_track("2001", 1)])
+            .oldest(vec![
+                build_played_track("3001", 5),
+                build_played_track("3002", 6),
+            ])
+            .build()
+            .unwrap();
+
+        profile_tracks.merge(
+            &DEFAULT_MERGE_ORDER,
+            MergeStrategy::Concatenate,
+            BalanceBy::TrackCount,
+        );
+        assert_eq!(
+            vec!["1001", "1002", "2001", "3001", "3002"],
+            profile_tracks.get_track_ids()
+        );
+
+        profile_tracks.merge(
+            &[
+                SectionType::Oldest,
+                SectionType::Unplayed,
+                SectionType::LeastPlayed,
+            ],
+            MergeStrategy::Concatenate,
+            BalanceBy::TrackCount,
+        );
+        assert_eq!(
+            vec!["3001", "3002", "1001", "1002", "2001"],
+            profile_tracks.get_track_ids()
+        );
+
+        // The track limit applies to the concatenated playlist
+        profile_tracks.apply_track_limit(3);
+        assert_eq!(vec!["3001", "3002", "1001"], profile_tracks.get_track_ids());
+    }
+
+    #[test]
+    fn test_validate_merge_order() {
+        let sections = vec![
+            build_section(SectionType::Unplayed, 0),
+            build_section(SectionType::Oldest, 0),
+        ];
+
+        assert!(
+            validate_merge_order(&[SectionType::Oldest, SectionType::Unplayed], &sections).is_ok()
+        );
+        assert!(validate_merge_order(&[SectionType::LeastPlayed], &sections).is_err());
+        assert!(
+            validate_merge_order(&[SectionType::Oldest, SectionType::Oldest], &sections).is_err()
+        );
+    }
+
+    #[test]
+    fn test_build_section_filters_library() {
+        let profile = build_profile(ProfileSource::Library, None);
+
+        let section = build_section(SectionType::Unplayed, 3);
+        let filters = build_section_filters(&profile, &section, None);
+        let expected = HashMap::from([
+            ("userRating>>".to_string(), "4".to_string()),
+            ("viewCount".to_string(), "0".to_string()),
+        ]);
+        assert_eq!(expected, filters);
+
+        let section = build_section(SectionType::Oldest, 0);
+        let filters = build_section_filters(&profile, &section, None);
+        let expected = HashMap::from([("viewCount>>".to_string(), "0".to_string())]);
+        assert_eq!(expected, filters);
+    }
+
+    #[test]
+    fn test_build_section_filters_collection() {
+        let profile = build_profile(ProfileSource::Collection, Some("5000"));
+        let section = build_section(SectionType::LeastPlayed, 0);
+        let artists = vec!["1001".to_string(), "1002".to_string()];
+
+        let filters = build_section_filters(&profile, &section, Some(&artists));
+        let expected = HashMap::from([
+            ("viewCount>>".to_string(), "0".to_string()),
+            ("artist.id".to_string(), "1001,1002".to_string()),
+        ]);
+        assert_eq!(expected, filters);
+    }
+
+    #[test]
+    fn test_build_section_filters_single_artist() {
+        let profile = build_profile(ProfileSource::SingleArtist, Some("1041"));
+        let section = build_section(SectionType::LeastPlayed, 0);
+
+        let filters = build_section_filters(&profile, &section, None);
+        let expected = HashMap::from([
+            ("viewCount>>".to_string(), "0".to_string()),
+            ("artist.id".to_string(), "1041".to_string()),
+        ]);
+        assert_eq!(expected, filters);
+    }
+
+    fn build_album_track(id: &str, album_id: &str) -> Track {
+        TrackBuilder::default().id(id).album_id(album_id).build()
+    }
+
+    /// Builds a large, deterministic list of tracks with many duplicate titles and artists
+    fn build_large_track_list(num_tracks: usize) -> Vec<Track> {
+        (0..num_tracks)
+            .map(|i| {
+                TrackBuilder::default()
+                    .id(&format!("{}", 100_000 + i))
+                    .title(&format!("Track {}", (i * 7919) % 1500))
+                    .artist(&format!("Artist {}", i % 97))
+                    .artist_guid(&format!("plex://artist/{:024}", i % 97))
+                    .guid(&format!("plex://track/{:024}", (i * 31) % 2000))
+                    .plays(((i * 13) % 9) as i32)
+                    .last_viewed_at(((i * 104_729) % 50_000) as i64 * 1000)
+                    .bitrate(((i * 17) % 320) as i64)
+                    .build()
+            })
+            .collect_vec()
+    }
+
+    /// Builds a track with the given id and content rating, sharing a title and artist
+    fn build_rated_track(id: &str, content_rating: &str) -> Track {
+        TrackBuilder::default()
+            .id(id)
+            .title("Get Back")
+            .artist("The Beatles")
+            .content_rating(content_rating)
+            .build()
+    }
+
+    #[test]
+    fn test_deduplicate_by_title_and_artist_keeps_preferred_version() {
+        let tracks = vec![
+            build_rated_track("100001", "clean"),
+            build_rated_track("100002", "explicit"),
+        ];
+
+        let mut deduplicated = tracks.clone();
+        deduplicate_by_title_and_artist(
+            &mut deduplicated,
+            DedupKey::TitleArtist,
+            ContentRatingPreference::NoPreference,
+        );
+        assert_eq!(
+            vec!["100001"],
+            deduplicated.iter().map(Track::get_id).collect_vec()
+        );
+
+        let mut deduplicated = tracks.clone();
+        deduplicate_by_title_and_artist(
+            &mut deduplicated,
+            DedupKey::TitleArtist,
+            ContentRatingPreference::PreferExplicit,
+        );
+        assert_eq!(
+            vec!["100002"],
+            deduplicated.iter().map(Track::get_id).collect_vec()
+        );
+
+        let mut deduplicated = tracks.into_iter().rev().collect_vec();
+        deduplicate_by_title_and_artist(
+            &mut deduplicated,
+            DedupKey::TitleArtist,
+            ContentRatingPreference::PreferClean,
+        );
+        assert_eq!(
+            vec!["100001"],
+            deduplicated.iter().map(Track::get_id).collect_vec()
+        );
+    }
+
+    /// Builds a track with the given id, title, artist, and guid
+    fn build_cover_track(id: &str, title: &str, artist: &str, guid: &str) -> Track {
+        TrackBuilder::default()
+            .id(id)
+            .title(title)
+            .artist(artist)
+            .guid(guid)
+            .build()
+    }
+
+    #[test]
+    fn test_deduplicate_by_dedup_key() {
+        let tracks = vec![
+            build_cover_track("100001", "Hallelujah", "Leonard Cohen", "plex://track/1"),
+            build_cover_track("100002", "Hallelujah", "Jeff Buckley", "plex://track/2"),
+            // The same recording on a compilation album
+            build_cover_track("100003", "Hallelujah", "Jeff Buckley", "plex://track/2"),
+            // A live recording with its own guid
+            build_cover_track("100004", "Hallelujah", "Jeff Buckley", "plex://track/3"),
+            build_cover_track("100005", "Suzanne", "Leonard Cohen", "plex://track/4"),
+        ];
+
+        for (dedup_key, expected) in [
+            (DedupKey::TitleArtist, vec!["100002", "100001", "100005"]),
+            (DedupKey::TitleOnly, vec!["100001", "100005"]),
+            (
+                DedupKey::GuidOnly,
+                vec!["100001", "100002", "100004", "100005"],
+            ),
+        ] {
+            let mut deduplicated = tracks.clone();
+            deduplicate_by_title_and_artist(
+                &mut deduplicated,
+                dedup_key,
+                ContentRatingPreference::NoPreference,
+            );
+            assert_eq!(
+                expected,
+                deduplicated.iter().map(Track::get_id).collect_vec(),
+                "{dedup_key}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_deduplication_matches_sequential() {
+        let tracks = build_large_track_list(10_000);
+
+        let mut parallel = tracks.clone();
+        deduplicate_by_title_and_artist(
+            &mut parallel,
+            DedupKey::TitleArtist,
+            ContentRatingPreference::NoPreference,
+        );
+        let sequential = tracks
+            .iter()
+            .sorted_by_key(|track| track.get_title_and_artist_sort_key())
+            .unique_by(|track| track.get_title_and_artist_sort_key())
+            .cloned()
+            .collect_vec();
+        assert_eq!(sequential, parallel);
+
+        let mut parallel = tracks.clone();
+        deduplicate_by_track_guid(&mut parallel);
+        let sequential = tracks
+            .iter()
+            .sorted_by_key(|track| (track.get_guid(), Reverse(track.get_bitrate())))
+            .unique_by(|track| track.get_guid())
+            .cloned()
+            .collect_vec();
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_parallel_trim_tracks_by_artist_matches_sequential() {
+        let tracks = build_large_track_list(10_000);
+
+        let mut parallel = tracks.clone();
+        trim_tracks_by_artist(&mut parallel, 5, SectionType::LeastPlayed, false);
+
+        let mut sequential = tracks.clone();
+        sequential.sort_by_key(|track| (track.get_plays(), track.get_last_played()));
+        let mut artist_occurrences: BTreeMap<String, u32> = BTreeMap::new();
+        sequential.retain(|track| {
+            let occurrences = artist_occurrences
+                .entry(track.get_artist_guid().to_owned())
+                .or_default();
+            *occurrences += 1;
+            *occurrences <= 5
+        });
+
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_trim_tracks_by_artist_normalizes_featured_artists() {
+        let build_artist_track = |id: &str, artist: &str, artist_guid: &str| {
+            build_played_track_with(id, 1)
+                .artist(artist)
+                .artist_guid(artist_guid)
+                .build()
+        };
+        let tracks = vec![
+            build_artist_track("1001", "Artist", "plex://artist/1"),
+            build_artist_track("1002", "Artist", "plex://artist/1"),
+            build_artist_track("1003", "Artist feat. Someone", "plex://artist/2"),
+            build_artist_track("1004", "Artist (ft. Someone Else)", "plex://artist/3"),
+            build_artist_track("1005", "Other Artist", "plex://artist/4"),
+        ];
+
+        let mut normalized = tracks.clone();
+        trim_tracks_by_artist(&mut normalized, 2, SectionType::LeastPlayed, true);
+        let ids = normalized.iter().map(|track| track.get_id()).collect_vec();
+        assert_eq!(vec!["1001", "1002", "1005"], ids);
+
+        let mut separate = tracks.clone();
+        trim_tracks_by_artist(&mut separate, 2, SectionType::LeastPlayed, false);
+        assert_eq!(5, separate.len());
+    }
+
+    #[test]
+    fn test_filter_by_album_completeness() {
+        let mut tracks = vec![
+            build_album_track("1001", "2001"),
+            build_album_track("1002", "2002"),
+            build_album_track("1003", "2002"),
+            build_album_track("1004", "2002"),
+            build_album_track("1005", "2002"),
+            build_album_track("1006", "2002"),
+        ];
+        let album_track_counts =
+            HashMap::from([("2001".to_string(), 10), ("2002".to_string(), 10)]);
+
+        filter_by_album_completeness(&mut tracks, &album_track_counts, 50);
+
+        let ids = tracks.iter().map(|track| track.get_id()).collect_vec();
+        assert_eq!(vec!["1002", "1003", "1004", "1005", "1006"], ids);
+    }
+
+    #[test]
+    fn test_deduplicate_lists_without_time_limit() {
+        let mut profile_tracks = ProfileTracksBuilder::default()
+            .least_played(vec![
+                build_played_track("1001", 1),
+                build_played_track("1002", 1),
+                build_played_track("1003", 2),
+            ])
+            .oldest(vec![
+                build_played_track("1002", 1),
+                build_played_track("1004", 5),
+            ])
+            .build()
+            .unwrap();
+
+        profile_tracks.deduplicate_lists(0.0);
+
+        let least_played = profile_tracks
+            .least_played
+            .iter()
+            .map(|track| track.get_id())
+            .collect_vec();
+        let oldest = profile_tracks
+            .oldest
+            .iter()
+            .map(|track| track.get_id())
+            .collect_vec();
+        assert_eq!(vec!["1001", "1003"], least_played);
+        assert_eq!(vec!["1002", "1004"], oldest);
+    }
+
+    #[test]
+    fn test_oldest_bucket_keys() {
+        // 2024-07-04 12:00:00 UTC, in milliseconds
+        let track = TrackBuilder::default()
+            .last_viewed_at(1_720_094_400_000)
+            .build();
+
+        assert_eq!("2024", get_oldest_bucket_key(&track, OldestBucket::Year));
+        assert_eq!(
+            "2024-07",
+            get_oldest_bucket_key(&track, OldestBucket::YearMonth)
+        );
+        assert_eq!(
+            "2024-W27",
+            get_oldest_bucket_key(&track, OldestBucket::YearWeek)
+        );
+    }
+
+    #[test]
+    fn test_randomizer_favor_rating() {
+        let tracks = vec![build_track("1001", 1, 0), build_track("1002", 5, 0)];
+
+        let favored = count_first_place(&tracks, "1002", RandomizationBias::FavorRating);
+        let uniform = count_first_place(&tracks, "1002", RandomizationBias::Uniform);
+
+        assert!(favored > RANDOMIZER_RUNS * 6 / 10);
+        assert!(favored > uniform);
+    }
+
+    #[test]
+    fn test_randomizer_favor_recent() {
+        let tracks = vec![
+            build_track("1001", 3, 1_000_000_000),
+            build_track("1002", 3, 1_700_000_000),
+        ];
+
+        let favored = count_first_place(&tracks, "1002", RandomizationBias::FavorRecent);
+        let uniform = count_first_place(&tracks, "1002", RandomizationBias::Uniform);
+
+        assert!(favored > RANDOMIZER_RUNS * 55 / 100);
+        assert!(favored > uniform);
+    }
+
+    #[test]
+    fn test_randomizer_keeps_all_tracks() {
+        let tracks = (0..10)
+            .map(|i| build_track(&format!("{}", 1000 + i), i % 6, i as i64))
+            .collect_vec();
+
+        let mut randomized = tracks.clone();
+        randomizer(
+            &mut randomized,
+            SectionType::LeastPlayed,
+            RandomizationBias::FavorRecent,
+            OldestBucket::default(),
+            0,
+            &mut StdRng::seed_from_u64(42),
+        );
+
+        assert_eq!(tracks.len(), randomized.len());
+        assert!(tracks.iter().all(|track| randomized.contains(track)));
+    }
+
+    fn build_profile_tracks(num_tracks: usize) -> ProfileTracks {
+        ProfileTracksBuilder::default()
+            .merged(vec![Track::default(); num_tracks])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_preview_lines_count() {
+        let profile_tracks = build_profile_tracks(30);
+        assert_eq!(5, profile_tracks.get_preview_lines(5, false).len());
+    }
+
+    #[test]
+    fn test_preview_lines_all() {
+        let profile_tracks = build_profile_tracks(30);
+        assert_eq!(30, profile_tracks.get_preview_lines(0, false).len());
+        assert_eq!(30, profile_tracks.get_preview_lines(100, false).len());
+    }
+
+    #[test]
+    fn test_preview_lines_relative_dates() {
+        let profile_tracks = build_profile_tracks(1);
+        assert!(profile_tracks.get_preview_lines(1, true)[0].ends_with("never "));
+    }
+
+    #[test]
+    fn test_artist_counts() {
+        let build_named_track = |artist: &str| TrackBuilder::default().artist(artist).build();
+        let profile_tracks = ProfileTracksBuilder::default()
+            .merged(
+                ["Yes", "Rush", "Genesis", "Rush", "Yes", "Rush", "Camel"]
+                    .into_iter()
+                    .map(build_named_track)
+                    .collect_vec(),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            vec![("Rush", 3), ("Yes", 2), ("Camel", 1), ("Genesis", 1)],
+            profile_tracks.get_artist_counts()
+        );
+    }
+
+    #[test]
+    fn test_find_artist_tracks_marks_trimmed_tracks() {
+        let tracks = vec![
+            build_artist_track("100001", "200001", 4),
+            build_artist_track("100002", "200001", 1),
+            build_artist_track("100003", "200002", 0),
+            build_artist_track("100004", "200001", 3),
+            build_artist_track("100005", "200001", 2),
+        ];
+        let section = ProfileSectionBuilder::default()
+            .deduplicate_tracks_by_guid(false)
+            .deduplicate_tracks_by_title_and_artist(false)
+            .enabled(true)
+            .maximum_tracks_by_artist(2)
+            .minimum_track_rating(0)
+            .randomize_tracks(false)
+            .section_type(SectionType::LeastPlayed)
+            .sorting("viewCount".to_string())
+            .build()
+            .unwrap();
+
+        let artist_tracks = find_artist_tracks(&section, &tracks, "200001");
+
+        assert_eq!(
+            vec![
+                ("100002", true),
+                ("100005", true),
+                ("100004", false),
+                ("100001", false)
+            ],
+            artist_tracks
+                .iter()
+                .map(|artist_track| (artist_track.track.get_id(), artist_track.kept))
+                .collect_vec()
+        );
+        assert!(artist_tracks
+            .iter()
+            .all(|artist_track| artist_track.section_type == SectionType::LeastPlayed));
+    }
+
+    #[test]
+    fn test_refresh_seed_is_reproducible() {
+        let tracks = (1..=20)
+            .map(|i| build_played_track(&(100_000 + i).to_string(), 1))
+            .collect_vec();
+        let shuffle = |profile_id: i32, refresh_timestamp: i64| {
+            let mut tracks = tracks.clone();
+            let mut rng = StdRng::seed_from_u64(refresh_seed(profile_id, refresh_timestamp));
+            randomizer(
+                &mut tracks,
+                SectionType::LeastPlayed,
+                RandomizationBias::Uniform,
+                OldestBucket::default(),
+                0,
+                &mut rng,
+            );
+            tracks
+                .iter()
+                .map(|track| track.get_id().to_owned())
+                .collect_vec()
+        };
+
+        assert_eq!(shuffle(1, 1_720_000_800), shuffle(1, 1_720_000_800));
+        assert_ne!(shuffle(1, 1_720_000_800), shuffle(1, 1_720_001_700));
+        assert_ne!(shuffle(1, 1_720_000_800), shuffle(2, 1_720_000_800));
+    }
+
+    #[test]
+    fn test_exclude_recently_played_disabled() {
+        // Last played timestamps are read as milliseconds
+        let now = jiff::Timestamp::now().as_millisecond();
+        let build_last_played_track = |id: &str, last_viewed_at: i64| {
+            build_played_track_with(id, 1)
+                .last_viewed_at(last_viewed_at)
+                .build()
+        };
+        let tracks = vec![
+            build_last_played_track("100001", now - 60 * 60 * 1000),
+            build_last_played_track("100002", now - 30 * 24 * 60 * 60 * 1000),
+        ];
+        let run_filters = |exclude_recently_played: bool| {
+            let section = ProfileSectionBuilder::default()
+                .deduplicate_tracks_by_guid(false)
+                .deduplicate_tracks_by_title_and_artist(false)
+                .enabled(true)
+                .maximum_tracks_by_artist(0)
+                .minimum_track_rating(0)
+                .randomize_tracks(false)
+                .exclude_recently_played(exclude_recently_played)
+                .section_type(SectionType::LeastPlayed)
+                .sorting("viewCount".to_string())
+                .build()
+                .unwrap();
+            let mut profile_tracks = ProfileTracksBuilder::default()
+                .least_played(tracks.clone())
+                .build()
+                .unwrap();
+            profile_tracks.run_manual_filters(&[section], 0.0, &mut StdRng::seed_from_u64(42));
+            profile_tracks
+                .least_played
+                .iter()
+                .map(|track| track.get_id().to_owned())
+                .collect_vec()
+        };
+
+        assert_eq!(vec!["100002"], run_filters(true));
+        assert_eq!(vec!["100002", "100001"], run_filters(false));
+    }
 }
@@ -2,6 +2,7 @@ use std::fmt::{Display, Formatter};
 use std::time;
 use std::time::Duration;
 
+use crate::plex::models::tracks;
 use crate::plex::models::tracks::Track;
 use crate::profiles::ProfileAction;
 
@@ -28,18 +29,13 @@ impl RefreshResult {
         self.tracks.len()
     }
 
-    /// In Milliseconds
-    fn get_total_duration(&self) -> i64 {
-        self.tracks.iter().map(|t| t.get_track_duration()).sum()
-    }
-
     fn get_duration(&self) -> Duration {
-        Duration::from_millis(self.get_total_duration() as u64)
+        tracks::total_duration(&self.tracks)
     }
 
     fn get_avg_track_duration(&self) -> String {
         let avg_track_duration =
-            (self.get_total_duration() as f64 / self.get_size() as f64).floor() as u64;
+            (self.get_duration().as_millis() as f64 / self.get_size() as f64).floor() as u64;
         let avg_track_duration = time::Duration::from_millis(avg_track_duration);
         humantime::format_duration(avg_track_duration).to_string()
     }
@@ -49,7 +45,7 @@ impl RefreshResult {
     }
 
     fn get_action(&self) -> &str {
-        if self.action == ProfileAction::Create {
+        if matches!(self.action, ProfileAction::Create { .. }) {
             "created"
         } else {
             "updated"
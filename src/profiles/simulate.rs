@@ -0,0 +1,135 @@
+//! Runs a profile's filters and merge against tracks loaded from a fixture file, without a plex
+//! server or the database, to reproduce filter bugs offline
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+
+use crate::plex::models::tracks::Track;
+use crate::profiles::import;
+use crate::profiles::profile_tracks::{self, ProfileTracks};
+use crate::profiles::track_source::FixtureTrackSource;
+
+/// Builds a profile's playlist from the tracks in a fixture file
+///
+/// The profile file uses the same format as the files read by `profile import-dir`, and sections
+/// that are not marked as enabled in it are skipped. The fixture is either a JSON array of tracks or a plex
+/// response containing them, such as one saved from `plex raw`.
+pub async fn simulate_profile(profile_path: &Path, tracks_path: &Path) -> Result<ProfileTracks> {
+    let mut profile_file = import::load_profile_file(profile_path).await?;
+    let num_enabled = profile_file
+        .sections
+        .iter()
+        .filter(|section| section.is_enabled())
+        .count();
+    profile_file
+        .profile
+        .derive_section_fields(num_enabled as u32);
+
+    let contents = tokio::fs::read_to_string(tracks_path)
+        .await
+        .with_context(|| format!("Unable to read fixture file `{}`", tracks_path.display()))?;
+    let tracks = parse_fixture_tracks(&contents)
+        .with_context(|| format!("Unable to parse fixture file `{}`", tracks_path.display()))?;
+
+    profile_tracks::fetch_profile_tracks(
+        &FixtureTrackSource::new(tracks),
+        &profile_file.profile,
+        &profile_file.sections,
+        &HashSet::new(),
+    )
+    .await
+}
+
+/// Parses the tracks in a fixture, which is either an array of tracks or a plex response
+fn parse_fixture_tracks(contents: &str) -> Result<Vec<Track>> {
+    let mut json: Value = serde_json::from_str(contents)?;
+    let tracks = match json {
+        Value::Array(_) => json,
+        _ => json["MediaContainer"]["Metadata"].take(),
+    };
+
+    if !tracks.is_array() {
+        return Err(anyhow!(
+            "Expected an array of tracks or a plex response with `MediaContainer.Metadata`"
+        ));
+    }
+
+    Ok(serde_json::from_value(tracks)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::models::tracks::TrackBuilder;
+
+    use super::*;
+
+    fn build_track_json(id: &str, artist: &str, plays: i32) -> Value {
+        let track = TrackBuilder::default()
+            .id(id)
+            .title(&format!("Track {id}"))
+            .artist(artist)
+            .artist_id(&format!("9{}", &id[1..]))
+            .plays(plays)
+            .duration(180_000)
+            .build();
+        serde_json::to_value(track).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_simulate_profile_from_fixture() {
+        let dir = std::env::temp_dir().join(format!("hitomi-simulate-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let profile_path = dir.join("profile.json");
+        let profile = r#"{
+            "title": "Morning Mix",
+            "time_limit": 1,
+            "sections": [
+                {
+                    "enabled": true,
+                    "section_type": "Unplayed",
+                    "sorting": "userRating:desc",
+                    "exclude_recently_played": false
+                },
+                {
+                    "enabled": true,
+                    "section_type": "LeastPlayed",
+                    "sorting": "viewCount",
+                    "exclude_recently_played": false
+                }
+            ]
+        }"#;
+        tokio::fs::write(&profile_path, profile).await.unwrap();
+
+        // The fixture is saved as a plex response, as `plex raw` would print it
+        let tracks_path = dir.join("tracks.json");
+        let tracks = serde_json::json!({"MediaContainer": {"Metadata": [
+            build_track_json("100001", "Rush", 0),
+            build_track_json("100002", "Yes", 0),
+            build_track_json("100003", "Rush", 4),
+            build_track_json("100004", "Yes", 2),
+        ]}});
+        tokio::fs::write(&tracks_path, tracks.to_string())
+            .await
+            .unwrap();
+
+        let profile_tracks = simulate_profile(&profile_path, &tracks_path).await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(
+            vec!["100001", "100004", "100002", "100003"],
+            profile_tracks.unwrap().get_track_ids()
+        );
+    }
+
+    #[test]
+    fn test_parse_fixture_tracks_rejects_other_json() {
+        assert!(parse_fixture_tracks(r#"{"MediaContainer": {}}"#).is_err());
+        assert_eq!(0, parse_fixture_tracks("[]").unwrap().len());
+    }
+}
@@ -0,0 +1,243 @@
+//! Sources from which profiles fetch their tracks
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use anyhow::Result;
+use jiff::{Timestamp, ToSpan};
+use simplelog::warn;
+
+use crate::plex::models::tracks::Track;
+use crate::plex::PlexClient;
+use crate::types::plex::hub_identifier::HubIdentifier;
+
+/// A source of tracks for building profiles
+///
+/// [`PlexClient`] is the only source used by the application. Other implementations allow the
+/// filter and merge logic to be exercised without a plex server.
+pub trait TrackSource {
+    /// Fetches tracks matching the given filters, sorting, and limit
+    fn fetch(
+        &self,
+        filters: HashMap<String, String>,
+        sort: Vec<&str>,
+        limit: Option<i32>,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send;
+
+    /// Fetches the ids of every artist in a collection
+    fn fetch_collection_artists(
+        &self,
+        collection_id: &str,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    /// Fetches the total number of tracks on an album
+    fn fetch_album_track_count(&self, album_id: &str) -> impl Future<Output = Result<u32>> + Send;
+
+    /// Fetches the ids of every artist in a hub
+    ///
+    /// Returns an empty list if the hub is unavailable
+    fn fetch_hub_artists(
+        &self,
+        hub_identifier: &HubIdentifier,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send;
+}
+
+/// The filters honored by [`FixtureTrackSource`]
+const FIXTURE_FILTERS: [&str; 6] = [
+    "viewCount",
+    "viewCount>>",
+    "userRating>>",
+    "genre!",
+    "artist.id",
+    "addedAt>>",
+];
+
+/// A [`TrackSource`] that serves tracks loaded ahead of time, such as from a fixture file
+///
+/// The filters built for profile sections (`viewCount`, `viewCount>>`, `userRating>>`, `genre!`,
+/// `artist.id`, and `addedAt>>`) and the limit are honored, including when they come from a
+/// section's extra filters. Any other filter is ignored with a warning, and sorting is left to the
+/// manual filters run after fetching.
+#[derive(Clone, Debug, Default)]
+pub struct FixtureTrackSource {
+    tracks: Vec<Track>,
+}
+
+impl FixtureTrackSource {
+    pub fn new(tracks: Vec<Track>) -> Self {
+        Self { tracks }
+    }
+}
+
+impl TrackSource for FixtureTrackSource {
+    async fn fetch(
+        &self,
+        filters: HashMap<String, String>,
+        _sort: Vec<&str>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Track>> {
+        for (key, value) in &filters {
+            if !FIXTURE_FILTERS.contains(&key.as_str()) {
+                warn!("Filter `{key}={value}` is not supported by the fixture and was ignored");
+            }
+        }
+
+        let tracks = self
+            .tracks
+            .iter()
+            .filter(|track| {
+                filters
+                    .iter()
+                    .all(|(key, value)| matches_filter(track, key, value))
+            })
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .cloned()
+            .collect();
+
+        Ok(tracks)
+    }
+
+    async fn fetch_collection_artists(&self, _collection_id: &str) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn fetch_album_track_count(&self, _album_id: &str) -> Result<u32> {
+        Ok(0)
+    }
+
+    async fn fetch_hub_artists(&self, _hub_identifier: &HubIdentifier) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+/// Returns whether a track matches a plex filter
+///
+/// Unsupported filters and values that cannot be parsed match every track.
+fn matches_filter(track: &Track, key: &str, value: &str) -> bool {
+    match key {
+        "viewCount" => value
+            .parse()
+            .map_or(true, |plays: i32| track.get_plays() == plays),
+        "viewCount>>" => value
+            .parse()
+            .map_or(true, |plays: i32| track.get_plays() > plays),
+        "userRating>>" => value
+            .parse()
+            .map_or(true, |rating: f32| track.get_user_rating() > rating),
+        "genre!" => !track.has_any_genre(&value.split(',').map(String::from).collect::<Vec<_>>()),
+        "artist.id" => value.split(',').any(|id| id == track.get_artist_id()),
+        "addedAt>>" => {
+            parse_added_at(value).map_or(true, |added_at| track.get_added_at() > added_at)
+        }
+        _ => true,
+    }
+}
+
+/// Parses the value of an `addedAt>>` filter, either a number of days ago such as `-30d` or a
+/// unix timestamp in seconds
+fn parse_added_at(value: &str) -> Option<Timestamp> {
+    match value
+        .strip_prefix('-')
+        .and_then(|days| days.strip_suffix('d'))
+    {
+        Some(days) => {
+            let hours = days.parse::<i64>().ok()? * 24;
+            Timestamp::now().checked_sub(hours.hours()).ok()
+        }
+        None => Timestamp::from_second(value.parse().ok()?).ok(),
+    }
+}
+
+impl TrackSource for PlexClient {
+    async fn fetch(
+        &self,
+        filters: HashMap<String, String>,
+        sort: Vec<&str>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Track>> {
+        self.fetch_music(filters, sort, limit).await
+    }
+
+    async fn fetch_collection_artists(&self, collection_id: &str) -> Result<Vec<String>> {
+        PlexClient::fetch_collection_artists(self, collection_id).await
+    }
+
+    async fn fetch_album_track_count(&self, album_id: &str) -> Result<u32> {
+        PlexClient::fetch_album_track_count(self, album_id).await
+    }
+
+    async fn fetch_hub_artists(&self, hub_identifier: &HubIdentifier) -> Result<Vec<String>> {
+        match self.fetch_hub(hub_identifier).await? {
+            Some(hub) => Ok(hub.get_artist_ids()),
+            None => {
+                warn!("Hub `{hub_identifier}` is unavailable. Falling back to the entire library.");
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::plex::models::tracks::TrackBuilder;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fixture_honors_section_filters() {
+        let source = FixtureTrackSource::new(vec![
+            TrackBuilder::default()
+                .id("1001")
+                .artist_id("9001")
+                .rating(8.0)
+                .plays(2)
+                .build(),
+            // Rated too low
+            TrackBuilder::default()
+                .id("1002")
+                .artist_id("9001")
+                .rating(4.0)
+                .plays(2)
+                .build(),
+            // Excluded genre
+            TrackBuilder::default()
+                .id("1003")
+                .artist_id("9001")
+                .rating(8.0)
+                .plays(2)
+                .genres(&["Christmas"])
+                .build(),
+            // Other artist
+            TrackBuilder::default()
+                .id("1004")
+                .artist_id("9002")
+                .rating(8.0)
+                .plays(2)
+                .build(),
+            // Never played
+            TrackBuilder::default()
+                .id("1005")
+                .artist_id("9001")
+                .rating(8.0)
+                .build(),
+        ]);
+        let filters = HashMap::from([
+            ("userRating>>".to_string(), "4".to_string()),
+            ("viewCount>>".to_string(), "0".to_string()),
+            ("genre!".to_string(), "Christmas,Holiday".to_string()),
+            ("artist.id".to_string(), "9001,9003".to_string()),
+        ]);
+
+        let tracks = source.fetch(filters, vec![], None).await.unwrap();
+
+        assert_eq!(
+            vec!["1001"],
+            tracks
+                .iter()
+                .map(|track| track.get_id())
+                .collect::<Vec<_>>()
+        );
+    }
+}
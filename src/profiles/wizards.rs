@@ -1,21 +1,28 @@
 //! Profile wizards
 
+use std::collections::HashMap;
+
 use anyhow::{anyhow, Context, Result};
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Confirm, Input, MultiSelect, Select};
-use simplelog::info;
+use dialoguer::{Confirm, Input, MultiSelect, Select, Sort};
+use simplelog::{info, warn};
 use strum::VariantNames;
 
-use crate::db;
 use crate::plex::PlexClient;
 use crate::profiles::manager::ProfileManager;
 use crate::profiles::profile::{Profile, ProfileBuilder};
 use crate::profiles::profile_section::{ProfileSection, ProfileSectionBuilder};
-use crate::profiles::{ProfileSource, SectionType, VALID_INTERVALS};
-use crate::types::profiles::profile_section_sort::ProfileSectionSort;
+use crate::profiles::{
+    BalanceBy, ContentRatingPreference, DedupKey, EmptySectionBehavior, MergeStrategy,
+    MissingDurationPolicy, OldestBucket, ProfileSource, RandomizationBias, SectionType,
+    VALID_INTERVALS,
+};
+use crate::types::plex::hub_identifier::HubIdentifier;
+use crate::types::profiles::profile_section_sort::{ProfileSectionSort, MAX_SORT_FIELDS};
 use crate::types::profiles::profile_source_id::ProfileSourceId;
 use crate::types::profiles::refresh_interval::RefreshInterval;
 use crate::types::Title;
+use crate::{config, db};
 
 /// The main entrypoint of the wizard
 pub async fn create_profile_wizard(
@@ -23,34 +30,114 @@ pub async fn create_profile_wizard(
 ) -> Result<(Profile, Vec<ProfileSection>)> {
     let profile_name = set_profile_name(manager).await?;
 
-    let summary = set_summary()?;
     let refresh_interval = select_refresh_interval()?;
     let time_limit = set_time_limit()?;
+    let (active_hours_start, active_hours_end) = set_active_hours()?;
 
     let profile_source = select_profile_source()?;
+    let (profile_source, hub_identifier) = if profile_source == ProfileSource::Hub {
+        match select_hub(manager.get_plex_client()).await? {
+            Some(hub_identifier) => (profile_source, Some(hub_identifier)),
+            None => (ProfileSource::Library, None),
+        }
+    } else {
+        (profile_source, None)
+    };
     let profile_source_id =
         select_profile_source_id(manager.get_plex_client(), profile_source).await?;
 
-    let sections = select_profile_sections()?;
+    let genres = manager
+        .get_plex_client()
+        .fetch_genres()
+        .await
+        .unwrap_or_else(|err| {
+            warn!("Unable to fetch genres from plex: {err}");
+            vec![]
+        });
+    let sections = select_profile_sections(&genres)?;
+    let minimum_valid_sections = set_minimum_valid_sections(sections.len())?;
+    let merge_order = select_merge_order(&sections)?;
+    let merge_strategy = select_merge_strategy(&sections)?;
+    let balance_by = select_balance_by(merge_strategy)?;
+    let max_tracks_by_artist_global = set_max_tracks_by_artist_global()?;
+    let empty_section_behavior = select_empty_section_behavior()?;
+    let avoid_recent_repeats_cycles = set_avoid_recent_repeats_cycles()?;
+    let snapshot_count = set_snapshot_count()?;
+    let minimum_playlist_size = set_minimum_playlist_size()?;
+    let fail_below_minimum_size = minimum_playlist_size > 0
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Should a refresh below the minimum size fail instead of being skipped?")
+            .default(false)
+            .interact()?;
+    let seeded_shuffle = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Should tracks be shuffled with a seed derived from the refresh time, so that each refresh can be reproduced?")
+        .default(false)
+        .interact()?;
+    let summary_only = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Should refreshes only update the playlist summary, leaving its tracks untouched?",
+        )
+        .default(false)
+        .interact()?;
 
-    let profile = ProfileBuilder::default()
+    let mut profile = ProfileBuilder::default()
         .title(profile_name)
-        .summary(summary)
         .profile_source(profile_source)
         .profile_source_id(profile_source_id)
+        .hub_identifier(hub_identifier)
         .refresh_interval(refresh_interval)
         .time_limit(time_limit)
+        .empty_section_behavior(empty_section_behavior)
+        .merge_order(merge_order)
+        .merge_strategy(merge_strategy)
+        .balance_by(balance_by)
+        .max_tracks_by_artist_global(max_tracks_by_artist_global)
+        .avoid_recent_repeats_cycles(avoid_recent_repeats_cycles)
+        .snapshot_count(snapshot_count)
+        .minimum_playlist_size(minimum_playlist_size)
+        .fail_below_minimum_size(fail_below_minimum_size)
+        .seeded_shuffle(seeded_shuffle)
+        .minimum_valid_sections(minimum_valid_sections)
+        .summary_only(summary_only)
+        .active_hours_start(active_hours_start)
+        .active_hours_end(active_hours_end)
         .build()?;
 
+    // The summary is set last so that the template can use the rest of the profile
+    let default_summary = config::load_config()
+        .await?
+        .get_default_summary_template()
+        .map(|template| profile.render_summary_template(template, sections.len()))
+        .unwrap_or_default();
+    profile.set_summary(set_summary(default_summary)?);
+
     Ok((profile, sections))
 }
 
 async fn set_profile_name(manager: &ProfileManager) -> Result<Title> {
-    let profile_name: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("What is the name of your new profile? This will also be the name of the playlist on the plex server.")
-        .interact_text()?;
-    let title = Title::try_new(profile_name.clone())
-        .with_context(|| "Error setting profile/playlist title from wizard")?;
+    let plex_client = manager.get_plex_client();
+
+    // Asks for another title until the playlist title is short enough or the user keeps it
+    let (profile_name, title) = loop {
+        let profile_name: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("What is the name of your new profile? This will also be the name of the playlist on the plex server.")
+            .interact_text()?;
+        let title = Title::try_new(profile_name.clone())
+            .with_context(|| "Error setting profile/playlist title from wizard")?;
+
+        if confirm_title_length(
+            &plex_client.get_playlist_title(&title),
+            manager.get_config().get_title_length_warning(),
+            |length| {
+                Ok(Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("The playlist title is {length} characters long, and plex may truncate it. Do you want to keep it?"))
+                    .default(false)
+                    .interact()?)
+            },
+        )? {
+            break (profile_name, title);
+        }
+    };
 
     if db::profiles::fetch_profile_by_title(&title)
         .await?
@@ -82,10 +169,10 @@ async fn set_profile_name(manager: &ProfileManager) -> Result<Title> {
     Ok(title)
 }
 
-fn set_summary() -> Result<String> {
+fn set_summary(default_summary: String) -> Result<String> {
     let summary = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("What is the summary for your new profile? This will also be the summary of the playlist on the plex server.")
-        .default(String::default())
+        .default(default_summary)
         .interact_text()?;
 
     Ok(summary)
@@ -102,13 +189,80 @@ fn select_refresh_interval() -> Result<RefreshInterval> {
 }
 
 fn set_time_limit() -> Result<u32> {
-    let time_limit = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("Enter a time limit in hours for the profile, or `0` for no time limit:")
+    let time_limit: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter a time limit for the profile in hours or as a duration, e.g. `24`, `1d`, or `1d12h`, or `0` for no time limit:")
         .default("24".to_string())
-        .interact_text()?
-        .parse::<u32>()?;
+        .validate_with(|input: &String| parse_time_limit(input).map(|_| ()))
+        .interact_text()?;
+
+    parse_time_limit(&time_limit).map_err(|err| anyhow!(err))
+}
+
+/// The longest time limit accepted by the wizard, in hours
+const MAX_TIME_LIMIT_HOURS: u64 = 365 * 24;
+
+/// Parses a time limit into hours
+///
+/// Plain integers are hours. Anything else is read as a duration, e.g. `1d` or `1d12h`, which
+/// must be a whole number of hours since time limits are stored in hours. A value of `0`
+/// represents no time limit.
+fn parse_time_limit(input: &str) -> Result<u32, String> {
+    let input = input.trim();
+    let hours = if let Ok(hours) = input.parse::<u64>() {
+        hours
+    } else {
+        let duration = humantime::parse_duration(input)
+            .map_err(|err| format!("Invalid time limit `{input}`: {err}"))?;
+        if duration.is_zero() {
+            return Err("The time limit must be greater than zero".to_string());
+        }
+        if duration.as_secs() % 3600 != 0 || duration.subsec_nanos() != 0 {
+            return Err(format!(
+                "The time limit `{input}` must be a whole number of hours, e.g. `2h` instead of `90m`"
+            ));
+        }
+        duration.as_secs() / 3600
+    };
+
+    if hours > MAX_TIME_LIMIT_HOURS {
+        return Err(format!(
+            "The time limit cannot be longer than {MAX_TIME_LIMIT_HOURS} hours"
+        ));
+    }
+
+    Ok(hours as u32)
+}
+
+fn set_active_hours() -> Result<(Option<u32>, Option<u32>)> {
+    let active_hours: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the hours (local time) during which the profile should refresh, e.g. `8-23`, or leave blank to always refresh:")
+        .allow_empty(true)
+        .validate_with(|input: &String| -> Result<(), &str> {
+            parse_active_hours(input)
+                .map(|_| ())
+                .ok_or("Active hours must be two hours between 0 and 23, e.g. `8-23`")
+        })
+        .interact_text()?;
 
-    Ok(time_limit)
+    Ok(parse_active_hours(&active_hours).unwrap_or_default())
+}
+
+/// Parses active hours in the form of `start-end`. An empty string represents no active hours.
+fn parse_active_hours(input: &str) -> Option<(Option<u32>, Option<u32>)> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Some((None, None));
+    }
+
+    let (start, end) = input.split_once('-')?;
+    let start = start
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|hour| *hour <= 23)?;
+    let end = end.trim().parse::<u32>().ok().filter(|hour| *hour <= 23)?;
+
+    Some((Some(start), Some(end)))
 }
 
 fn select_profile_source() -> Result<ProfileSource> {
@@ -127,7 +281,7 @@ async fn select_profile_source_id(
     profile_source: ProfileSource,
 ) -> Result<Option<ProfileSourceId>> {
     let id: Option<String> = match profile_source {
-        ProfileSource::Library => None,
+        ProfileSource::Library | ProfileSource::Hub => None,
         ProfileSource::Collection => {
             let collections = plex_client.get_collections();
             let titles = collections
@@ -191,38 +345,233 @@ async fn select_profile_source_id(
     })
 }
 
-fn select_profile_sections() -> Result<Vec<ProfileSection>> {
-    let defaults = &[false, false, false];
-    let selections = MultiSelect::with_theme(&ColorfulTheme::default())
-        .with_prompt("Which sections do you want to include in your profile?")
-        .items(SectionType::VARIANTS)
-        .defaults(defaults)
+/// Lists the hubs available for the music library and prompts the user to select one
+///
+/// Returns `None` if the server does not expose any hubs
+async fn select_hub(plex_client: &PlexClient) -> Result<Option<HubIdentifier>> {
+    info!("Fetching hubs. Please wait...");
+    let hubs = plex_client.fetch_hubs().await?;
+
+    if hubs.is_empty() {
+        warn!("No hubs are available for your music library. Falling back to the entire library.");
+        return Ok(None);
+    }
+
+    let titles = hubs.iter().map(|x| x.get_title()).collect::<Vec<&str>>();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select a hub:")
+        .default(0)
+        .items(&titles)
         .interact()?;
 
-    let selections = if selections.is_empty() {
-        vec![0, 1, 2]
-    } else {
-        selections
+    Ok(Some(hubs[selection].get_identifier().to_owned()))
+}
+
+fn select_profile_sections(genres: &[String]) -> Result<Vec<ProfileSection>> {
+    let defaults = &[false, false, false];
+    let selections = loop {
+        let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(
+                "Which sections do you want to include in your profile? (Press `Esc` to cancel)",
+            )
+            .items(SectionType::VARIANTS)
+            .defaults(defaults)
+            .interact_opt()?
+            .ok_or_else(|| anyhow!("Profile creation cancelled"))?;
+
+        let include_all = || {
+            Ok(Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("No sections selected; include all three?")
+                .default(false)
+                .interact()?)
+        };
+        if let Some(selections) = resolve_section_selections(selections, include_all)? {
+            break selections;
+        }
     };
 
     let mut sections = vec![];
 
     if selections.contains(&0) {
-        sections.push(build_profile_section(SectionType::Unplayed)?)
+        sections.push(build_profile_section(SectionType::Unplayed, genres)?)
     }
 
     if selections.contains(&1) {
-        sections.push(build_profile_section(SectionType::LeastPlayed)?)
+        sections.push(build_profile_section(SectionType::LeastPlayed, genres)?)
     }
 
     if selections.contains(&2) {
-        sections.push(build_profile_section(SectionType::Oldest)?)
+        sections.push(build_profile_section(SectionType::Oldest, genres)?)
     }
 
     Ok(sections)
 }
 
-fn build_profile_section(section_type: SectionType) -> Result<ProfileSection> {
+/// Asks `keep_title` to confirm a playlist title longer than `max_length` characters
+///
+/// Returns `false` if it is declined, so that another title is asked for.
+fn confirm_title_length(
+    playlist_title: &str,
+    max_length: u32,
+    keep_title: impl FnOnce(usize) -> Result<bool>,
+) -> Result<bool> {
+    let length = playlist_title.chars().count();
+    if length <= max_length as usize {
+        return Ok(true);
+    }
+
+    keep_title(length)
+}
+
+/// Resolves the sections selected in the wizard
+///
+/// If no sections were selected, `include_all` asks whether to include every section. Returns
+/// `None` if it is declined, so that the sections are selected again.
+fn resolve_section_selections(
+    selections: Vec<usize>,
+    include_all: impl FnOnce() -> Result<bool>,
+) -> Result<Option<Vec<usize>>> {
+    if !selections.is_empty() {
+        return Ok(Some(selections));
+    }
+
+    if include_all()? {
+        Ok(Some((0..SectionType::VARIANTS.len()).collect()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Prompts the user to order the selected sections. Returns an empty order if the default order
+/// is kept, so that the default order is stored.
+fn select_merge_order(sections: &[ProfileSection]) -> Result<Vec<SectionType>> {
+    let section_types = sections
+        .iter()
+        .map(|section| section.get_section_type())
+        .collect::<Vec<_>>();
+    if section_types.len() <= 1 {
+        return Ok(vec![]);
+    }
+
+    let order = Sort::with_theme(&ColorfulTheme::default())
+        .with_prompt("Order the sections in which tracks are merged into the playlist:")
+        .items(&section_types)
+        .interact()?;
+
+    let merge_order = order
+        .into_iter()
+        .map(|i| section_types[i])
+        .collect::<Vec<_>>();
+
+    if merge_order == section_types {
+        Ok(vec![])
+    } else {
+        Ok(merge_order)
+    }
+}
+
+fn select_merge_strategy(sections: &[ProfileSection]) -> Result<MergeStrategy> {
+    if sections.len() <= 1 {
+        return Ok(MergeStrategy::default());
+    }
+
+    let choices = MergeStrategy::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select how the sections should be merged into the playlist:")
+        .default(0)
+        .items(choices)
+        .interact()?;
+
+    Ok(MergeStrategy::from_repr(selection).unwrap())
+}
+
+fn select_balance_by(merge_strategy: MergeStrategy) -> Result<BalanceBy> {
+    if merge_strategy != MergeStrategy::Interleave {
+        return Ok(BalanceBy::default());
+    }
+
+    let choices = BalanceBy::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select how interleaved sections should be balanced:")
+        .default(BalanceBy::default() as usize)
+        .items(choices)
+        .interact()?;
+
+    Ok(BalanceBy::from_repr(selection).unwrap())
+}
+
+fn select_empty_section_behavior() -> Result<EmptySectionBehavior> {
+    let choices = EmptySectionBehavior::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select what should happen when a section returns no tracks:")
+        .default(0)
+        .items(choices)
+        .interact()?;
+
+    Ok(EmptySectionBehavior::from_repr(selection).unwrap())
+}
+
+fn set_max_tracks_by_artist_global() -> Result<u32> {
+    let maximum = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the maximum number of tracks by a single artist across the whole playlist, or `0` for no limit:")
+        .default("0".to_string())
+        .interact_text()?
+        .parse::<u32>()?;
+
+    Ok(maximum)
+}
+
+fn set_avoid_recent_repeats_cycles() -> Result<u32> {
+    let cycles = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the number of previous refreshes whose tracks should not be repeated, or `0` to allow repeats:")
+        .default("0".to_string())
+        .interact_text()?
+        .parse::<u32>()?;
+
+    Ok(cycles)
+}
+
+fn set_snapshot_count() -> Result<u32> {
+    let count = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the number of previous versions of the playlist to keep as snapshots, or `0` to keep none:")
+        .default("0".to_string())
+        .interact_text()?
+        .parse::<u32>()?;
+
+    Ok(count)
+}
+
+fn set_minimum_valid_sections(num_sections: usize) -> Result<u32> {
+    if num_sections <= 1 {
+        return Ok(1);
+    }
+
+    let minimum = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the number of sections that must return tracks for a refresh to update the playlist:")
+        .default(1)
+        .validate_with(|input: &u32| -> Result<(), String> {
+            if *input as usize <= num_sections {
+                Ok(())
+            } else {
+                Err(format!("The minimum cannot be greater than {num_sections} sections"))
+            }
+        })
+        .interact_text()?;
+
+    Ok(minimum)
+}
+
+fn set_minimum_playlist_size() -> Result<u32> {
+    let size = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the minimum number of tracks a refreshed playlist must contain, or `0` for no minimum:")
+        .default("0".to_string())
+        .interact_text()?
+        .parse::<u32>()?;
+
+    Ok(size)
+}
+
+fn build_profile_section(section_type: SectionType, genres: &[String]) -> Result<ProfileSection> {
     println!("\nBuilding Section: {section_type}");
 
     let deduplicate_tracks_by_guid = Confirm::with_theme(&ColorfulTheme::default())
@@ -235,12 +584,27 @@ fn build_profile_section(section_type: SectionType) -> Result<ProfileSection> {
         .default(true)
         .interact()?;
 
+    let (dedup_key, content_rating_preference) = if deduplicate_by_track_and_artist {
+        (select_dedup_key()?, select_content_rating_preference()?)
+    } else {
+        (DedupKey::default(), ContentRatingPreference::default())
+    };
+
     let maximum_tracks_by_artists =
         Input::with_theme(&ColorfulTheme::default())
             .with_prompt("Enter a maximum number of tracks that can appear in a playlist by a single artist. (A value of `0` disables any limit.)")
             .default(25)
             .interact_text()?;
 
+    let normalize_featured_artists = if maximum_tracks_by_artists > 0 {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Should tracks featuring other artists (e.g., `Artist feat. X`) count against the primary artist's limit?")
+            .default(true)
+            .interact()?
+    } else {
+        false
+    };
+
     let minimum_track_rating = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("Enter a minimum star rating for included tracks:")
         .default(3)
@@ -253,29 +617,346 @@ fn build_profile_section(section_type: SectionType) -> Result<ProfileSection> {
         })
         .interact_text()?;
 
+    let minimum_album_completeness = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the minimum percentage of an album's tracks that must qualify before any of its tracks are included. (A value of `0` disables this check.)")
+        .default(0)
+        .validate_with(|input: &u32| -> Result<(), &str> {
+            if *input <= 100 {
+                Ok(())
+            } else {
+                Err("Minimum album completeness cannot be greater than 100")
+            }
+        })
+        .interact_text()?;
+
+    let exclude_recently_played = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Do you want to exclude tracks played within the last day?")
+        .default(true)
+        .interact()?;
+
     let randomize = Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt("Do you want to randomize the track order?")
         .default(true)
         .interact()?;
 
+    let randomization_bias = if randomize {
+        select_randomization_bias()?
+    } else {
+        RandomizationBias::default()
+    };
+
+    let keep_top_n_fixed: u32 = if randomize {
+        Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Enter the number of tracks at the front to keep in order before shuffling the rest. (A value of `0` shuffles every track.)")
+            .default(0)
+            .interact_text()?
+    } else {
+        0
+    };
+
+    let oldest_bucket = if randomize && section_type == SectionType::Oldest {
+        select_oldest_bucket()?
+    } else {
+        OldestBucket::default()
+    };
+
+    let least_played_includes_unplayed = if section_type == SectionType::LeastPlayed {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want to include tracks that have never been played?")
+            .default(false)
+            .interact()?
+    } else {
+        false
+    };
+
+    let minimum_plays = if section_type == SectionType::Unplayed || least_played_includes_unplayed {
+        0
+    } else {
+        Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Enter the number of plays a track needs to be included in this section. (A value of `0` or `1` includes every played track.)")
+            .default(0)
+            .interact_text()?
+    };
+
+    let boost_recently_added = if section_type == SectionType::Unplayed {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want recently added tracks to come before older tracks with the same rating?")
+            .default(false)
+            .interact()?
+    } else {
+        false
+    };
+
+    let use_net_plays = if section_type == SectionType::LeastPlayed {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want to order tracks by their plays minus their skips?")
+            .default(false)
+            .interact()?
+    } else {
+        false
+    };
+
+    let exclude_never_played = if section_type == SectionType::Oldest {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want to exclude tracks that have never been played?")
+            .default(false)
+            .interact()?
+    } else {
+        false
+    };
+
+    let exclude_genres = select_exclude_genres(genres)?;
+
+    let min_duration_seconds: u32 = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Enter a minimum track duration in seconds. (A value of `0` disables this bound.)",
+        )
+        .default(0)
+        .interact_text()?;
+
+    let max_duration_seconds = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Enter a maximum track duration in seconds. (A value of `0` disables this bound.)",
+        )
+        .default(0)
+        .validate_with(|input: &u32| -> Result<(), &str> {
+            if *input == 0 || *input >= min_duration_seconds {
+                Ok(())
+            } else {
+                Err("Maximum duration cannot be less than the minimum duration")
+            }
+        })
+        .interact_text()?;
+
+    let missing_duration_policy = select_missing_duration_policy()?;
+    let extra_filters = set_extra_filters()?;
+
+    let server_random = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Do you want plex to return tracks in a random order, skipping sorting and shuffling?",
+        )
+        .default(false)
+        .interact()?;
+
     // TODO get valid sort fields from plex
     let section_sort = ProfileSectionSort::default_from(section_type);
-    let sorting = Input::with_theme(&ColorfulTheme::default())
+    let sorting = if server_random {
+        section_sort.into_inner()
+    } else {
+        Input::with_theme(&ColorfulTheme::default())
         .with_prompt("Enter a comma separated list of fields to sort")
         .default(section_sort.into_inner())
-        // TODO validate
-        .interact_text()?;
+        .validate_with(|input: &String| -> Result<(), String> {
+            ProfileSectionSort::try_new(input).map(|_| ()).map_err(|_| {
+                format!("Sorting must be up to {MAX_SORT_FIELDS} comma separated fields, each optionally followed by `:desc`")
+            })
+        })
+        .interact_text()?
+    };
 
     let section = ProfileSectionBuilder::default()
         .enabled(true)
         .section_type(section_type)
         .deduplicate_tracks_by_guid(deduplicate_tracks_by_guid)
         .deduplicate_tracks_by_title_and_artist(deduplicate_by_track_and_artist)
+        .dedup_key(dedup_key)
+        .content_rating_preference(content_rating_preference)
         .maximum_tracks_by_artist(maximum_tracks_by_artists)
+        .normalize_featured_artists(normalize_featured_artists)
         .minimum_track_rating(minimum_track_rating)
+        .minimum_album_completeness(minimum_album_completeness)
+        .exclude_recently_played(exclude_recently_played)
         .randomize_tracks(randomize)
+        .randomization_bias(randomization_bias)
+        .keep_top_n_fixed(keep_top_n_fixed)
+        .oldest_bucket(oldest_bucket)
+        .exclude_never_played(exclude_never_played)
+        .least_played_includes_unplayed(least_played_includes_unplayed)
+        .minimum_plays(minimum_plays)
+        .use_net_plays(use_net_plays)
+        .boost_recently_added(boost_recently_added)
+        .exclude_genres(exclude_genres)
+        .min_duration_seconds(min_duration_seconds)
+        .max_duration_seconds(max_duration_seconds)
+        .missing_duration_policy(missing_duration_policy)
+        .server_random(server_random)
+        .extra_filters(extra_filters)
         .sorting(sorting)
         .build()?;
 
     Ok(section)
 }
+
+fn set_extra_filters() -> Result<HashMap<String, String>> {
+    let input: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter any extra plex filters as `key=value` pairs separated by `;` (e.g., `decade=1990; label=Favorites`). Leave blank for none.")
+        .allow_empty(true)
+        .validate_with(|input: &String| parse_extra_filters(input).map(|_| ()))
+        .interact_text()?;
+
+    parse_extra_filters(&input).map_err(|err| anyhow!(err))
+}
+
+/// Parses `key=value` pairs separated by `;` into plex filters
+fn parse_extra_filters(input: &str) -> Result<HashMap<String, String>, String> {
+    input
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(format!("`{pair}` is not a `key=value` pair")),
+        })
+        .collect()
+}
+
+fn select_exclude_genres(genres: &[String]) -> Result<Vec<String>> {
+    if genres.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select any genres to exclude from this section:")
+        .items(genres)
+        .interact()?;
+
+    let exclude_genres = selections
+        .into_iter()
+        .map(|i| genres[i].to_owned())
+        .collect::<Vec<_>>();
+
+    Ok(exclude_genres)
+}
+
+fn select_randomization_bias() -> Result<RandomizationBias> {
+    let choices = RandomizationBias::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select which tracks should be favored when randomizing:")
+        .default(0)
+        .items(choices)
+        .interact()?;
+
+    Ok(RandomizationBias::from_repr(selection).unwrap())
+}
+
+fn select_missing_duration_policy() -> Result<MissingDurationPolicy> {
+    let choices = MissingDurationPolicy::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select how tracks without a duration count against the time limit:")
+        .default(MissingDurationPolicy::default() as usize)
+        .items(choices)
+        .interact()?;
+
+    Ok(MissingDurationPolicy::from_repr(selection).unwrap())
+}
+
+fn select_dedup_key() -> Result<DedupKey> {
+    let choices = DedupKey::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select what duplicate tracks must share (e.g., `Title Only` keeps a single cover of a song):")
+        .default(DedupKey::default() as usize)
+        .items(choices)
+        .interact()?;
+
+    Ok(DedupKey::from_repr(selection).unwrap())
+}
+
+fn select_content_rating_preference() -> Result<ContentRatingPreference> {
+    let choices = ContentRatingPreference::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select which version of a track to keep when both explicit and clean versions are available:")
+        .default(ContentRatingPreference::default() as usize)
+        .items(choices)
+        .interact()?;
+
+    Ok(ContentRatingPreference::from_repr(selection).unwrap())
+}
+
+fn select_oldest_bucket() -> Result<OldestBucket> {
+    let choices = OldestBucket::VARIANTS;
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select how oldest tracks should be grouped by their last played date when randomizing:")
+        .default(OldestBucket::default() as usize)
+        .items(choices)
+        .interact()?;
+
+    Ok(OldestBucket::from_repr(selection).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_extra_filters() {
+        assert_eq!(HashMap::new(), parse_extra_filters(" ").unwrap());
+        assert_eq!(
+            HashMap::from([
+                ("decade".to_string(), "1990".to_string()),
+                ("label".to_string(), "Favorites".to_string()),
+                ("userRating>>".to_string(), "6".to_string()),
+            ]),
+            parse_extra_filters("decade=1990; label = Favorites;userRating>>=6;").unwrap()
+        );
+        assert!(parse_extra_filters("decade").is_err());
+        assert!(parse_extra_filters("=1990").is_err());
+    }
+
+    #[test]
+    fn test_resolve_section_selections() {
+        assert_eq!(
+            Some(vec![1]),
+            resolve_section_selections(vec![1], || panic!("should not prompt")).unwrap()
+        );
+        assert_eq!(
+            Some(vec![0, 1, 2]),
+            resolve_section_selections(vec![], || Ok(true)).unwrap()
+        );
+        assert_eq!(
+            None,
+            resolve_section_selections(vec![], || Ok(false)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_confirm_title_length() {
+        assert!(confirm_title_length("Daily Mix", 80, |_| panic!("should not prompt")).unwrap());
+
+        let title = "A".repeat(81);
+        let mut prompted_length = None;
+        assert!(confirm_title_length(&title, 80, |length| {
+            prompted_length = Some(length);
+            Ok(true)
+        })
+        .unwrap());
+        assert_eq!(Some(81), prompted_length);
+        assert!(!confirm_title_length(&title, 80, |_| Ok(false)).unwrap());
+    }
+
+    #[test]
+    fn test_parse_time_limit() {
+        assert_eq!(Ok(24), parse_time_limit("24"));
+        assert_eq!(Ok(0), parse_time_limit("0"));
+        assert_eq!(Ok(24), parse_time_limit("1d"));
+        assert_eq!(Ok(2), parse_time_limit("120m"));
+        assert_eq!(Ok(36), parse_time_limit("1d12h"));
+        assert_eq!(Ok(36), parse_time_limit(" 1d 12h "));
+    }
+
+    #[test]
+    fn test_parse_time_limit_invalid() {
+        assert!(parse_time_limit("").is_err());
+        assert!(parse_time_limit("soon").is_err());
+        assert!(parse_time_limit("0m").is_err());
+        assert!(parse_time_limit("366d").is_err());
+        assert!(parse_time_limit("9000").is_err());
+        assert!(parse_time_limit("90m")
+            .unwrap_err()
+            .contains("whole number of hours"));
+        assert!(parse_time_limit("2h30m").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use nutype::nutype;
+
+#[nutype(
+    derive(
+        Clone,
+        Debug,
+        Default,
+        Deserialize,
+        Display,
+        Serialize,
+        AsRef,
+        Deref,
+        PartialEq
+    ),
+    default = "music.recent.played",
+    sanitize(trim),
+    validate(not_empty, len_char_max = 250)
+)]
+pub struct HubIdentifier(String);
+
+#[cfg(test)]
+mod hub_identifier_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_valid_hub_identifier() {
+        let valid = "music.recent.played.artists";
+        let hub_identifier = HubIdentifier::try_new(valid).unwrap();
+        assert_eq!(valid, hub_identifier.into_inner())
+    }
+
+    #[test]
+    fn test_invalid_hub_identifier_empty() {
+        let expected = Err(HubIdentifierError::NotEmptyViolated);
+        let result = HubIdentifier::try_new("  ");
+        assert_eq!(expected, result)
+    }
+}
@@ -1,4 +1,6 @@
 pub mod guid;
+pub mod hub_identifier;
 pub mod plex_id;
 pub mod plex_key;
 pub mod plex_token;
+pub mod plex_url;
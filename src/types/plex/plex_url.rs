@@ -0,0 +1,53 @@
+use nutype::nutype;
+use reqwest::Url;
+
+/// The url of a plex server, normalized to always end with a trailing slash
+///
+/// Without the trailing slash, [`Url::join`] replaces the last segment of the url's path, so a
+/// server behind a reverse proxy at `http://host/plex` would lose its `/plex` prefix.
+#[nutype(
+    derive(Clone, Debug, Default, Deserialize, Display, Serialize, AsRef, Deref, PartialEq),
+    default = "http://127.0.0.1:32400/",
+    sanitize(trim, with = add_trailing_slash),
+    validate(not_empty, predicate = |url| Url::parse(url).is_ok())
+)]
+pub struct PlexUrl(String);
+
+fn add_trailing_slash(url: String) -> String {
+    if url.is_empty() || url.ends_with('/') {
+        url
+    } else {
+        format!("{url}/")
+    }
+}
+
+#[cfg(test)]
+mod plex_url_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_plex_url_adds_trailing_slash() {
+        let expected = "http://127.0.0.1:32400/";
+        assert_eq!(
+            expected,
+            PlexUrl::try_new("http://127.0.0.1:32400").unwrap().as_ref()
+        );
+        assert_eq!(
+            expected,
+            PlexUrl::try_new(" http://127.0.0.1:32400/ ")
+                .unwrap()
+                .as_ref()
+        );
+    }
+
+    #[test]
+    fn test_invalid_plex_url() {
+        assert_eq!(Err(PlexUrlError::NotEmptyViolated), PlexUrl::try_new(""));
+        assert_eq!(
+            Err(PlexUrlError::PredicateViolated),
+            PlexUrl::try_new("not a url")
+        );
+    }
+}
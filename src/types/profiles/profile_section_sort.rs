@@ -4,8 +4,17 @@ use std::sync::LazyLock;
 
 use crate::profiles::SectionType;
 
-static PROFILE_SECTION_SORT_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^(([A-Za-z]+:?[A-Za-z]*),?)+$").unwrap());
+/// Matches between one and [`MAX_SORT_FIELDS`] comma separated `field` or `field:desc` tokens
+static PROFILE_SECTION_SORT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"^[A-Za-z]+(:desc)?(,[A-Za-z]+(:desc)?){{0,{}}}$",
+        MAX_SORT_FIELDS - 1
+    ))
+    .unwrap()
+});
+
+/// The maximum number of fields a section can be sorted by
+pub const MAX_SORT_FIELDS: usize = 8;
 
 #[nutype(
     derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize, AsRef, Deref),
@@ -34,3 +43,50 @@ impl ProfileSectionSort {
         Self::try_new(sort).unwrap()
     }
 }
+
+#[cfg(test)]
+mod profile_section_sort_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_valid_profile_section_sort() {
+        let sort = "userRating:desc,viewCount,lastViewedAt";
+        let result = ProfileSectionSort::try_new(sort).unwrap();
+        assert_eq!(sort, result.into_inner());
+
+        for section_type in [
+            SectionType::Unplayed,
+            SectionType::LeastPlayed,
+            SectionType::Oldest,
+        ] {
+            ProfileSectionSort::default_from(section_type);
+        }
+    }
+
+    #[test]
+    fn test_too_many_sort_fields() {
+        let sort = ["viewCount"; MAX_SORT_FIELDS].join(",");
+        assert!(ProfileSectionSort::try_new(sort).is_ok());
+
+        let sort = ["viewCount"; MAX_SORT_FIELDS + 1].join(",");
+        let expected = Err(ProfileSectionSortError::RegexViolated);
+        assert_eq!(expected, ProfileSectionSort::try_new(sort));
+    }
+
+    #[test]
+    fn test_double_colon_sort_field() {
+        let expected = Err(ProfileSectionSortError::RegexViolated);
+        assert_eq!(expected, ProfileSectionSort::try_new("viewCount:desc:desc"));
+        assert_eq!(expected, ProfileSectionSort::try_new("A:B:C"));
+    }
+
+    #[test]
+    fn test_empty_sort_fields() {
+        let expected = Err(ProfileSectionSortError::RegexViolated);
+        assert_eq!(expected, ProfileSectionSort::try_new("viewCount,"));
+        assert_eq!(expected, ProfileSectionSort::try_new("viewCount,,guid"));
+        assert_eq!(expected, ProfileSectionSort::try_new(""));
+    }
+}
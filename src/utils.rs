@@ -1,6 +1,6 @@
 use crate::types::profiles::refresh_interval::RefreshInterval;
 use jiff::tz::TimeZone;
-use jiff::{Error, Timestamp, Zoned};
+use jiff::{civil, Error, Timestamp, Zoned};
 
 /// Constructs a `vec` of valid refresh minutes from a given refresh intervals
 pub fn build_refresh_minutes(refresh_interval: &RefreshInterval) -> Vec<u32> {
@@ -19,6 +19,47 @@ pub fn get_yesterday() -> Result<Zoned, Error> {
     get_current_datetime().yesterday()
 }
 
+/// Resolves a time given on the command line in the time zone of `now`
+///
+/// A time of day such as `18:00` is its next occurrence after `now`. A date and time such as
+/// `2024-06-01 18:00` is used as is.
+pub fn parse_schedule_time(input: &str, now: &Zoned) -> Result<Zoned, Error> {
+    let input = input.trim();
+    // A time also parses from a date and time, so dates are tried first
+    if let Ok(datetime) = input.replacen(' ', "T", 1).parse::<civil::DateTime>() {
+        return datetime.to_zoned(now.time_zone().clone());
+    }
+
+    let time = input.parse::<civil::Time>()?;
+    let today = now
+        .date()
+        .to_datetime(time)
+        .to_zoned(now.time_zone().clone())?;
+    if today > *now {
+        Ok(today)
+    } else {
+        today.tomorrow()
+    }
+}
+
+/// Describes how long ago `then` was relative to `now`, e.g. `3 days ago`
+///
+/// Anything within the last day is `today`. Months and years are approximated as 30 and 365 days.
+pub fn humanize_since(then: Timestamp, now: Timestamp) -> String {
+    let days = (now.as_second() - then.as_second()) / 86_400;
+
+    let plural =
+        |count: i64, unit: &str| format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" });
+    match days {
+        ..=0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..=6 => plural(days, "day"),
+        7..=29 => plural(days / 7, "week"),
+        30..=364 => plural(days / 30, "month"),
+        _ => plural(days / 365, "year"),
+    }
+}
+
 pub fn truncate_string(s: &str, max_chars: usize) -> &str {
     match s.char_indices().nth(max_chars) {
         None => s,
@@ -50,6 +91,48 @@ mod tests {
         assert_ne!(EXPECTED_MINUTES.to_vec(), minutes);
     }
 
+    #[test]
+    fn test_parse_schedule_time() {
+        let now = civil::date(2024, 6, 1)
+            .at(12, 30, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+        let expected = |day: i8, hour: i8, minute: i8| {
+            civil::date(2024, 6, day)
+                .at(hour, minute, 0, 0)
+                .to_zoned(TimeZone::UTC)
+                .unwrap()
+        };
+
+        assert_eq!(
+            expected(1, 18, 0),
+            parse_schedule_time("18:00", &now).unwrap()
+        );
+        assert_eq!(
+            expected(2, 9, 15),
+            parse_schedule_time("09:15", &now).unwrap()
+        );
+        assert_eq!(
+            expected(3, 18, 0),
+            parse_schedule_time("2024-06-03 18:00", &now).unwrap()
+        );
+        assert!(parse_schedule_time("6pm", &now).is_err());
+    }
+
+    #[test]
+    fn test_humanize_since() {
+        let now = Timestamp::from_second(1_700_000_000).unwrap();
+        let days_ago = |days: i64| Timestamp::from_second(now.as_second() - days * 86_400).unwrap();
+
+        assert_eq!("today", humanize_since(now, now));
+        assert_eq!("yesterday", humanize_since(days_ago(1), now));
+        assert_eq!("3 days ago", humanize_since(days_ago(3), now));
+        assert_eq!("1 week ago", humanize_since(days_ago(7), now));
+        assert_eq!("3 weeks ago", humanize_since(days_ago(21), now));
+        assert_eq!("2 months ago", humanize_since(days_ago(65), now));
+        assert_eq!("4 years ago", humanize_since(days_ago(1500), now));
+    }
+
     #[test]
     fn test_truncate_string() {
         let str = "It's not possible to convince a monkey to give you a banana by promising it infinite bananas when they die.";